@@ -0,0 +1,506 @@
+//! A Debug Adapter Protocol frontend speaking over stdio.
+//! Spec: https://microsoft.github.io/debug-adapter-protocol/specification
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use wasminspect_debugger::{
+    describe_error, reload_main_module, typed_value, Breakpoint, CommandContext, CommandResult,
+    Debugger, MainDebugger, OutputPrinter, Process, Radix, RunResult, StepStyle,
+};
+use wasminspect_vm::Signal;
+
+/// wasminspect has only one thread of execution
+const THREAD_ID: i64 = 1;
+const LOCALS_REFERENCE: i64 = 1;
+const STACK_REFERENCE: i64 = 2;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    seq: i64,
+    command: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ProtocolMessage {
+    Response {
+        seq: i64,
+        request_seq: i64,
+        success: bool,
+        command: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<Value>,
+    },
+    Event {
+        seq: i64,
+        event: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<Value>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchArguments {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetBreakpointsArguments {
+    source: Source,
+    #[serde(default)]
+    breakpoints: Vec<SourceBreakpoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Source {
+    path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SourceBreakpoint {
+    line: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VariablesArguments {
+    variables_reference: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct EvaluateArguments {
+    expression: String,
+}
+
+pub fn read_message<R: BufRead>(reader: &mut R) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let content_length = content_length.ok_or_else(|| anyhow!("missing Content-Length header"))?;
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+    Ok(Some(content))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, content: &[u8]) -> io::Result<()> {
+    write!(writer, "Content-Length: {}\r\n\r\n", content.len())?;
+    writer.write_all(content)?;
+    writer.flush()
+}
+
+/// Collects the output of debugger commands so that it can be forwarded
+/// as `output` events instead of being written to stdout.
+#[derive(Clone, Default)]
+struct BufferedPrinter {
+    lines: Rc<RefCell<Vec<(&'static str, String)>>>,
+}
+
+impl BufferedPrinter {
+    fn take(&self) -> Vec<(&'static str, String)> {
+        std::mem::take(&mut *self.lines.borrow_mut())
+    }
+}
+
+impl OutputPrinter for BufferedPrinter {
+    fn println(&self, output: &str) {
+        self.lines
+            .borrow_mut()
+            .push(("console", format!("{}\n", output)));
+    }
+    fn eprintln(&self, output: &str) {
+        self.lines
+            .borrow_mut()
+            .push(("stderr", format!("{}\n", output)));
+    }
+}
+
+struct Session<W: Write> {
+    process: Process<MainDebugger>,
+    context: CommandContext,
+    printer: BufferedPrinter,
+    writer: W,
+    seq: i64,
+    wasi_args: Vec<String>,
+    source_breakpoints: HashMap<String, Vec<usize>>,
+    terminated: bool,
+}
+
+pub fn run<R: BufRead, W: Write>(mut reader: R, writer: W) -> anyhow::Result<()> {
+    let (process, mut context) = wasminspect_debugger::start_debugger(None, vec![], vec![])?;
+    let printer = BufferedPrinter::default();
+    context.printer = Box::new(printer.clone());
+    let mut session = Session {
+        process,
+        context,
+        printer,
+        writer,
+        seq: 0,
+        wasi_args: vec![],
+        source_breakpoints: HashMap::new(),
+        terminated: false,
+    };
+
+    while let Some(content) = read_message(&mut reader)? {
+        let request: Request = match serde_json::from_slice(&content) {
+            Ok(request) => request,
+            Err(err) => {
+                log::warn!("Ignoring malformed DAP message: {}", err);
+                continue;
+            }
+        };
+        log::debug!("Received DAP request: {:?}", request);
+        if !session.handle_request(request)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+impl<W: Write> Session<W> {
+    fn send(&mut self, message: ProtocolMessage) -> anyhow::Result<()> {
+        let content = serde_json::to_vec(&message)?;
+        write_message(&mut self.writer, &content)?;
+        Ok(())
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn send_event(&mut self, event: &str, body: Option<Value>) -> anyhow::Result<()> {
+        let seq = self.next_seq();
+        self.send(ProtocolMessage::Event {
+            seq,
+            event: event.to_string(),
+            body,
+        })
+    }
+
+    fn flush_output(&mut self) -> anyhow::Result<()> {
+        for (category, output) in self.printer.take() {
+            self.send_event(
+                "output",
+                Some(json!({ "category": category, "output": output })),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns false when the client asked to end the session
+    fn handle_request(&mut self, request: Request) -> anyhow::Result<bool> {
+        let result = self.dispatch(&request);
+        self.flush_output()?;
+        let seq = self.next_seq();
+        let response = match result {
+            Ok(body) => ProtocolMessage::Response {
+                seq,
+                request_seq: request.seq,
+                success: true,
+                command: request.command.clone(),
+                message: None,
+                body,
+            },
+            Err(err) => ProtocolMessage::Response {
+                seq,
+                request_seq: request.seq,
+                success: false,
+                command: request.command.clone(),
+                message: Some(err.to_string()),
+                body: None,
+            },
+        };
+        self.send(response)?;
+
+        match request.command.as_str() {
+            "initialize" => self.send_event("initialized", None)?,
+            "disconnect" => return Ok(false),
+            "configurationDone" | "continue" => self.report_stop("breakpoint")?,
+            "next" => self.report_stop("step")?,
+            _ => (),
+        }
+        Ok(true)
+    }
+
+    fn dispatch(&mut self, request: &Request) -> anyhow::Result<Option<Value>> {
+        let arguments = request.arguments.clone();
+        match request.command.as_str() {
+            "initialize" => Ok(Some(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsEvaluateForHovers": false,
+            }))),
+            "launch" => {
+                let args: LaunchArguments = serde_json::from_value(arguments)?;
+                self.launch(args)?;
+                Ok(None)
+            }
+            "setBreakpoints" => {
+                let args: SetBreakpointsArguments = serde_json::from_value(arguments)?;
+                Ok(Some(self.set_breakpoints(args)))
+            }
+            "configurationDone" => {
+                self.process
+                    .debugger
+                    .instantiate(HashMap::new(), &self.wasi_args)?;
+                let result = self.process.debugger.run(None, vec![]);
+                self.record_run_result(result);
+                Ok(None)
+            }
+            "threads" => Ok(Some(json!({
+                "threads": [{ "id": THREAD_ID, "name": "main" }],
+            }))),
+            "stackTrace" => Ok(Some(self.stack_trace()?)),
+            "scopes" => Ok(Some(json!({
+                "scopes": [
+                    { "name": "Locals", "variablesReference": LOCALS_REFERENCE, "expensive": false },
+                    { "name": "Operand Stack", "variablesReference": STACK_REFERENCE, "expensive": false },
+                ],
+            }))),
+            "variables" => {
+                let args: VariablesArguments = serde_json::from_value(arguments)?;
+                Ok(Some(self.variables(args.variables_reference)))
+            }
+            "continue" => {
+                let result = self.process.debugger.process();
+                self.record_run_result(result);
+                Ok(Some(json!({ "allThreadsContinued": true })))
+            }
+            "next" => {
+                self.step_over_line()?;
+                Ok(None)
+            }
+            "evaluate" => {
+                let args: EvaluateArguments = serde_json::from_value(arguments)?;
+                let result = self
+                    .process
+                    .dispatch_command(&args.expression, &mut self.context)?;
+                if let Some(CommandResult::ProcessFinish(values)) = result {
+                    self.terminated = true;
+                    self.printer.println(&format!("{:?}", values));
+                }
+                let output = self
+                    .printer
+                    .take()
+                    .into_iter()
+                    .map(|(_, line)| line)
+                    .collect::<String>();
+                Ok(Some(json!({ "result": output, "variablesReference": 0 })))
+            }
+            "disconnect" => Ok(None),
+            other => Err(anyhow!("unsupported request '{}'", other)),
+        }
+    }
+
+    fn launch(&mut self, args: LaunchArguments) -> anyhow::Result<()> {
+        let bytes = std::fs::read(&args.program)
+            .with_context(|| format!("failed to read {}", args.program))?;
+        let basename = std::path::Path::new(&args.program)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&args.program)
+            .to_string();
+        reload_main_module(
+            &mut self.process.debugger,
+            &mut self.context,
+            &bytes,
+            basename,
+        )?;
+        self.wasi_args = args.args;
+        Ok(())
+    }
+
+    fn set_breakpoints(&mut self, args: SetBreakpointsArguments) -> Value {
+        let path = args.source.path.unwrap_or_default();
+        for inst_offset in self.source_breakpoints.remove(&path).unwrap_or_default() {
+            self.process
+                .debugger
+                .delete_breakpoint(Breakpoint::Instruction { inst_offset });
+        }
+        let mut offsets = vec![];
+        let mut breakpoints = vec![];
+        for bp in args.breakpoints {
+            match self.context.sourcemap.find_code_offset(&path, bp.line) {
+                Some(inst_offset) => {
+                    self.process
+                        .debugger
+                        .set_breakpoint(Breakpoint::Instruction { inst_offset });
+                    offsets.push(inst_offset);
+                    breakpoints.push(json!({ "verified": true, "line": bp.line }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "line": bp.line,
+                    "message": "no code found for this line",
+                })),
+            }
+        }
+        self.source_breakpoints.insert(path, offsets);
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn record_run_result(&mut self, result: anyhow::Result<RunResult>) {
+        match result {
+            Ok(RunResult::Finish(values)) => {
+                self.terminated = true;
+                self.printer.println(&format!("{:?}", values));
+            }
+            // The adapter never sets a timeout
+            Ok(RunResult::Breakpoint) | Ok(RunResult::Timeout) => {}
+            // Keep the session alive so the faulting frame can be inspected
+            Ok(RunResult::Trap(err)) => {
+                let output = describe_error(&err, &self.context);
+                self.printer.eprintln(&output);
+            }
+            Err(err) => {
+                self.terminated = true;
+                let output = describe_error(&err, &self.context);
+                self.printer.eprintln(&output);
+            }
+        }
+    }
+
+    fn report_stop(&mut self, reason: &str) -> anyhow::Result<()> {
+        self.flush_output()?;
+        if self.terminated {
+            self.send_event("terminated", None)
+        } else {
+            self.send_event(
+                "stopped",
+                Some(json!({ "reason": reason, "threadId": THREAD_ID })),
+            )
+        }
+    }
+
+    fn current_offset(&self) -> anyhow::Result<usize> {
+        let (insts, next_index) = self.process.debugger.instructions()?;
+        insts
+            .get(next_index)
+            .or_else(|| insts.last())
+            .map(|inst| inst.offset)
+            .ok_or_else(|| anyhow!("no instruction in the current function"))
+    }
+
+    fn stack_trace(&self) -> anyhow::Result<Value> {
+        let debugger = &self.process.debugger;
+        let mut frames = vec![];
+        for (index, name) in debugger.frame().iter().rev().enumerate() {
+            let mut frame = json!({
+                "id": index,
+                "name": name,
+                "line": 0,
+                "column": 0,
+            });
+            // Only the innermost frame knows its program counter
+            if index == 0 {
+                let line_info = self
+                    .current_offset()
+                    .ok()
+                    .and_then(|offset| self.context.sourcemap.find_line_info(offset));
+                if let Some(line_info) = line_info {
+                    frame["source"] = json!({ "path": line_info.filepath });
+                    frame["line"] = json!(line_info.line.unwrap_or(0));
+                    frame["column"] = json!(Into::<u64>::into(line_info.column));
+                }
+            }
+            frames.push(frame);
+        }
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&self, reference: i64) -> Value {
+        let (prefix, values) = match reference {
+            LOCALS_REFERENCE => ("local", self.process.debugger.locals()),
+            STACK_REFERENCE => ("stack", self.process.debugger.stack_values()),
+            _ => ("", vec![]),
+        };
+        let variables = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let (ty, value) = typed_value(value, Radix::Dec);
+                json!({
+                    "name": format!("{}{}", prefix, index),
+                    "value": value,
+                    "type": ty,
+                    "variablesReference": 0,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({ "variables": variables })
+    }
+
+    fn step_over_line(&mut self) -> anyhow::Result<()> {
+        let sourcemap = self.context.sourcemap.as_ref();
+        let line_of = |offset: usize| {
+            sourcemap
+                .find_line_info(offset)
+                .map(|info| (info.filepath, info.line))
+        };
+        let initial_line = line_of(self.current_offset()?);
+        loop {
+            match self.process.debugger.step(StepStyle::InstOver)? {
+                Signal::Next => {}
+                Signal::Breakpoint => return Ok(()),
+                Signal::End => {
+                    let result = self.process.debugger.process();
+                    self.record_run_result(result);
+                    return Ok(());
+                }
+            }
+            // Without source info, fall back to stepping a single instruction
+            if initial_line.is_none() || line_of(self.current_offset()?) != initial_line {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_framing() {
+        let mut buffer = vec![];
+        write_message(&mut buffer, br#"{"seq":1}"#).unwrap();
+        write_message(&mut buffer, br#"{"seq":2}"#).unwrap();
+
+        let mut reader = io::BufReader::new(buffer.as_slice());
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(br#"{"seq":1}"#.to_vec())
+        );
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(br#"{"seq":2}"#.to_vec())
+        );
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
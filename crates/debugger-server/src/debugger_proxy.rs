@@ -1,7 +1,17 @@
 use futures::SinkExt;
-use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::mpsc, usize};
+use lazy_static::lazy_static;
 use std::{
-    sync::{Arc, Mutex},
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+    sync::mpsc,
+    usize,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, TryLockError,
+    },
     thread,
 };
 use tokio_tungstenite::tungstenite::Message;
@@ -10,19 +20,127 @@ use wasmparser::FuncType;
 use crate::rpc::{self, WasmExport};
 use crate::serialization;
 use wasminspect_debugger::{
-    try_load_dwarf, CommandContext, CommandResult, Debugger, Interactive, MainDebugger, Process,
+    reload_main_module, CommandContext, CommandResult, Debugger, Interactive, MainDebugger,
+    Process, ProgressCallback,
+};
+use wasminspect_vm::{
+    GlobalAddr, GlobalInstance, HostFuncBody, HostMemory, HostValue, MemoryAddr, NumVal, Trap,
+    WasmValue,
 };
-use wasminspect_vm::{HostFuncBody, HostValue, MemoryAddr, NumVal, Trap, WasmValue};
 
 static VERSION: &str = "0.2.0";
 
 pub type ProcessRef = Rc<RefCell<Process<MainDebugger>>>;
 pub type CommandCtxRef = Rc<RefCell<CommandContext>>;
+/// Host imports declared by the client through `Import` before `Init`
+pub type ImportsRef = Rc<RefCell<Vec<rpc::WasmImportModule>>>;
+pub type BreakpointsRef = Rc<RefCell<RemoteBreakpoints>>;
+
+/// Breakpoints set through `SetBreakpoint`, by the id handed to the client
+#[derive(Default)]
+pub struct RemoteBreakpoints {
+    next_id: u32,
+    by_id: BTreeMap<u32, rpc::Breakpoint>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SessionId(u64);
+
+impl SessionId {
+    pub fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "session-{}", self.0)
+    }
+}
+
+/// Debugger state owned by a single connection. It lives on the connection's
+/// own thread, so sessions never share a `MainDebugger` or `Store`.
+pub struct Session {
+    pub id: SessionId,
+    pub process: ProcessRef,
+    pub context: CommandCtxRef,
+    pub imports: ImportsRef,
+    pub breakpoints: BreakpointsRef,
+}
+
+impl Session {
+    pub fn new(id: SessionId) -> anyhow::Result<Self> {
+        let (process, context) = wasminspect_debugger::start_debugger(None, vec![], vec![])?;
+        Ok(Self {
+            id,
+            process: Rc::new(RefCell::new(process)),
+            context: Rc::new(RefCell::new(context)),
+            imports: ImportsRef::default(),
+            breakpoints: BreakpointsRef::default(),
+        })
+    }
+}
+
+/// Requests for a session, `None` once its connection is closed
+pub type RequestSender = mpsc::Sender<Option<Message>>;
+
+/// Open sessions by id. Only the channel to each session's thread is kept
+/// here; the debugger state itself never leaves that thread.
+#[derive(Default)]
+pub struct SessionRegistry {
+    senders: Mutex<HashMap<SessionId, RequestSender>>,
+}
+
+impl SessionRegistry {
+    fn senders(&self) -> MutexGuard<'_, HashMap<SessionId, RequestSender>> {
+        self.senders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn insert(&self, id: SessionId, sender: RequestSender) {
+        self.senders().insert(id, sender);
+    }
+
+    /// Forget the session, telling its thread to stop receiving
+    pub fn remove(&self, id: SessionId) {
+        if let Some(sender) = self.senders().remove(&id) {
+            // The thread may have exited already, leaving no receiver
+            let _ = sender.send(None);
+        }
+    }
+
+    /// Hand `msg` to the session `id`, failing if it's gone
+    pub fn send(&self, id: SessionId, msg: Message) -> anyhow::Result<()> {
+        let senders = self.senders();
+        let sender = senders
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown session {}", id))?;
+        sender
+            .send(Some(msg))
+            .map_err(|_| anyhow::anyhow!("Debugger thread is gone"))
+    }
+}
+
+lazy_static! {
+    /// The terminal is the only thing sessions share, so interactive
+    /// prompts are serialized across them.
+    static ref CONSOLE_LOCK: Mutex<()> = Mutex::new(());
+    /// Every open connection of this server
+    pub static ref SESSIONS: SessionRegistry = SessionRegistry::default();
+}
+
+pub fn try_lock_console() -> Option<MutexGuard<'static, ()>> {
+    // A session which panicked while prompting doesn't lock others out
+    match CONSOLE_LOCK.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
 
 pub fn handle_request<S: futures::Sink<Message> + Unpin + Send + 'static>(
     req: rpc::Request,
-    process: ProcessRef,
-    context: CommandCtxRef,
+    session: &Session,
     tx: Arc<Mutex<S>>,
     rx: Arc<mpsc::Receiver<Option<Message>>>,
 ) -> rpc::Response
@@ -31,13 +149,21 @@ where
 {
     match req {
         rpc::Request::Text(ref req) => {
-            log::debug!("Received TextRequest: {:?}", req);
+            log::debug!("[{}] Received TextRequest: {:?}", session.id, req);
         }
         rpc::Request::Binary(ref req) => {
-            log::debug!("Received BinaryRequest: {:?}", req.kind);
+            log::debug!("[{}] Received BinaryRequest: {:?}", session.id, req.kind);
         }
     };
-    let res = match _handle_request(req, process, context, tx, rx) {
+    let res = match _handle_request(
+        req,
+        session.process.clone(),
+        session.context.clone(),
+        session.imports.clone(),
+        session.breakpoints.clone(),
+        tx,
+        rx,
+    ) {
         Ok(res) => res,
         Err(err) => rpc::TextResponse::Error {
             message: err.to_string(),
@@ -47,10 +173,10 @@ where
 
     match res {
         rpc::Response::Text(ref req) => {
-            log::debug!("Sending TextResponse: {:?}", req);
+            log::debug!("[{}] Sending TextResponse: {:?}", session.id, req);
         }
         rpc::Response::Binary { ref kind, .. } => {
-            log::debug!("Sending BinaryResponse: {:?}", kind);
+            log::debug!("[{}] Sending BinaryResponse: {:?}", session.id, kind);
         }
     };
     res
@@ -70,7 +196,6 @@ fn from_js_number(value: rpc::JSNumber, ty: &wasmparser::Type) -> WasmValue {
     }
 }
 
-#[allow(dead_code)]
 fn to_vm_wasm_value(value: &rpc::WasmValue) -> WasmValue {
     match value {
         rpc::WasmValue::F32 { value } => WasmValue::F32((*value).to_bits()),
@@ -189,8 +314,15 @@ where
                         tx.clone(),
                     )?;
                 }
-                rpc::Request::Text(rpc::TextRequest::CallExported { name, args }) => {
-                    let res = call_exported(name, args, process.clone(), context.clone()).unwrap();
+                rpc::Request::Binary(req) if is_memory_request(&req) => {
+                    let res = host_memory_request(req, ctx.mem)
+                        .map_err(|e| Trap::HostFunctionError(Box::new(e)))?;
+                    blocking_send_response(res, tx.clone())?;
+                }
+                rpc::Request::Text(rpc::TextRequest::CallExported { name, args, .. }) => {
+                    let res =
+                        call_exported(name, args, process.clone(), context.clone(), false, None)
+                            .unwrap();
                     blocking_send_response(res, tx.clone())?;
                 }
                 other => {
@@ -211,10 +343,98 @@ where
     })
 }
 
+fn is_memory_request(req: &rpc::BinaryRequest) -> bool {
+    matches!(
+        req.kind,
+        rpc::BinaryRequestKind::ReadMemory | rpc::BinaryRequestKind::WriteMemory
+    )
+}
+
+fn out_of_bounds(addr: usize, length: usize, memory_size: usize) -> rpc::Response {
+    rpc::TextResponse::MemoryAccessOutOfBounds {
+        addr,
+        length,
+        memory_size,
+    }
+    .into()
+}
+
+/// Serve memory requests arriving while a host function borrows the memory
+fn host_memory_request(
+    req: rpc::BinaryRequest,
+    mem: &mut [u8],
+) -> Result<rpc::Response, rpc::RequestError> {
+    let memory_size = mem.len();
+    match req.kind {
+        rpc::BinaryRequestKind::ReadMemory => {
+            let rpc::ReadMemoryArgs { addr, len } = serialization::deserialize_read_memory(&req)?;
+            let region = addr.checked_add(len).and_then(|end| mem.get(addr..end));
+            Ok(match region {
+                Some(bytes) => rpc::Response::Binary {
+                    kind: rpc::BinaryResponseKind::ReadMemoryResult,
+                    bytes: bytes.to_vec(),
+                },
+                None => out_of_bounds(addr, len, memory_size),
+            })
+        }
+        rpc::BinaryRequestKind::WriteMemory => {
+            let rpc::WriteMemoryArgs { addr, bytes } =
+                serialization::deserialize_write_memory(&req)?;
+            let region = addr
+                .checked_add(bytes.len())
+                .and_then(|end| mem.get_mut(addr..end));
+            Ok(match region {
+                Some(region) => {
+                    region.copy_from_slice(bytes);
+                    rpc::TextResponse::StoreMemoryResult.into()
+                }
+                None => out_of_bounds(addr, bytes.len(), memory_size),
+            })
+        }
+        kind => Err(rpc::RequestError::MalformedBinaryRequest(kind)),
+    }
+}
+
 type ImportModule = HashMap<String, HostValue>;
 
+fn declared_global<'a>(
+    declared: &'a [rpc::WasmImportModule],
+    module: &str,
+    field: &str,
+) -> Option<&'a rpc::WasmValue> {
+    declared
+        .iter()
+        .filter(|m| m.name == module)
+        .flat_map(|m| m.imports.iter())
+        .find_map(|import| match import {
+            rpc::WasmImport::Global { name, value } if name == field => Some(value),
+            _ => None,
+        })
+}
+
+fn remote_global(
+    value: &rpc::WasmValue,
+    ty: wasmparser::GlobalType,
+    module: &str,
+    field: &str,
+) -> anyhow::Result<HostValue> {
+    let value = to_vm_wasm_value(value);
+    if !value.isa(ty.content_type) {
+        return Err(anyhow::anyhow!(
+            "type mismatch for imported global {}.{}: expected {:?} but got {:?}",
+            module,
+            field,
+            ty.content_type,
+            value
+        ));
+    }
+    let global = GlobalInstance::new(value, ty);
+    Ok(HostValue::Global(Rc::new(RefCell::new(global))))
+}
+
 fn remote_import_module<S: futures::Sink<Message> + Unpin + Send + 'static>(
     bytes: &[u8],
+    declared: &[rpc::WasmImportModule],
     process: ProcessRef,
     context: CommandCtxRef,
     tx: Arc<Mutex<S>>,
@@ -242,18 +462,30 @@ where
                     let import = import?;
                     module_imports.insert((import.module, import.field), import);
 
+                    let field_name = match import.field {
+                        Some(field_name) => field_name,
+                        None => continue,
+                    };
                     let ty_idx = match import.ty {
                         wasmparser::ImportSectionEntryType::Function(ty_idx) => ty_idx,
+                        wasmparser::ImportSectionEntryType::Global(ty) => {
+                            if let Some(value) =
+                                declared_global(declared, import.module, field_name)
+                            {
+                                let global = remote_global(value, ty, import.module, field_name)?;
+                                modules
+                                    .entry(import.module.to_string())
+                                    .or_default()
+                                    .insert(field_name.to_string(), global);
+                            }
+                            continue;
+                        }
                         _ => continue,
                     };
                     let ty = match types.get(&ty_idx) {
                         Some(wasmparser::TypeDef::Func(ty)) => ty,
                         _ => continue,
                     };
-                    let field_name = match import.field {
-                        Some(field_name) => field_name,
-                        None => continue,
-                    };
 
                     let func = remote_call_fn(
                         field_name.to_string(),
@@ -314,14 +546,20 @@ fn module_exports(bytes: &[u8]) -> anyhow::Result<Vec<WasmExport>> {
     Ok(exports)
 }
 
+/// Called with the executed instruction count and the current function
+type ProgressFn = (u64, ProgressCallback);
+
+/// Call the exported function `name`. Breakpoint hits are reported with
+/// `Stopped` if `stop_remotely`, or open the console otherwise.
 fn call_exported(
     name: String,
     args: Vec<f64>,
     process: ProcessRef,
     context: CommandCtxRef,
+    stop_remotely: bool,
+    progress: Option<ProgressFn>,
 ) -> Result<rpc::Response, anyhow::Error> {
     use rpc::*;
-    use wasminspect_debugger::RunResult;
 
     let func = process.borrow().debugger.lookup_func(&name)?;
     let func_ty = process.borrow().debugger.func_type(func)?;
@@ -333,16 +571,44 @@ fn call_exported(
         .zip(func_ty.params.iter())
         .map(|(arg, ty)| from_js_number(*arg, ty))
         .collect();
-    let result = { process.borrow_mut().debugger.execute_func(func, args) };
+    let result = {
+        let mut process = process.borrow_mut();
+        process.debugger.set_progress(progress);
+        let result = process.debugger.execute_func(func, args);
+        process.debugger.set_progress(None);
+        result
+    };
+    run_response(result, process, context, stop_remotely)
+}
+
+fn run_response(
+    result: anyhow::Result<wasminspect_debugger::RunResult>,
+    process: ProcessRef,
+    context: CommandCtxRef,
+    stop_remotely: bool,
+) -> Result<rpc::Response, anyhow::Error> {
+    use rpc::*;
+    use wasminspect_debugger::RunResult;
+
     match result {
         Ok(RunResult::Finish(values)) => {
             let values = values.iter().map(from_vm_wasm_value).collect();
             Ok(TextResponse::CallResult { values }.into())
         }
-        Ok(RunResult::Breakpoint) => {
-            // use std::borrow::{Borrow, BorrowMut};
+        // Remote calls never set a timeout
+        Ok(RunResult::Breakpoint) | Ok(RunResult::Timeout) if stop_remotely => {
+            let frames = backtrace_frames(&process.borrow().debugger);
+            Ok(TextResponse::Stopped { frames }.into())
+        }
+        Ok(RunResult::Breakpoint) | Ok(RunResult::Timeout) => {
+            // Waiting for the console would hang this session until the one
+            // owning it exits
+            let _console = try_lock_console().ok_or_else(|| {
+                anyhow::anyhow!("stopped, but another session is using the console")
+            })?;
             let mut interactive = Interactive::new_with_loading_history().unwrap();
-            let mut result = { interactive.run_loop(&*context.borrow(), process.clone())? };
+            interactive.set_completer(process.borrow().completer());
+            let mut result = { interactive.run_loop(&mut *context.borrow_mut(), process.clone())? };
             loop {
                 match result {
                     CommandResult::ProcessFinish(values) => {
@@ -353,37 +619,72 @@ fn call_exported(
                         let cmd_result = {
                             process
                                 .borrow_mut()
-                                .dispatch_command("process continue", &*context.borrow())?
+                                .dispatch_command("process continue", &mut *context.borrow_mut())?
                         };
                         match cmd_result {
                             Some(r) => {
                                 result = r;
                             }
                             None => {
-                                result =
-                                    interactive.run_loop(&*context.borrow(), process.clone())?;
+                                result = interactive
+                                    .run_loop(&mut *context.borrow_mut(), process.clone())?;
                             }
                         }
                     }
                 }
             }
         }
-        Err(msg) => {
+        Ok(RunResult::Trap(msg)) | Err(msg) => {
+            let _console = match try_lock_console() {
+                Some(console) => console,
+                None => return Err(msg),
+            };
             let mut interactive = Interactive::new_with_loading_history().unwrap();
+            interactive.set_completer(process.borrow().completer());
             {
                 let err = format!("Error while calling exported function: {}", msg);
                 context.borrow().printer.eprintln(&err);
-                interactive.run_loop(&*context.borrow(), process)?
+                interactive.run_loop(&mut *context.borrow_mut(), process)?
             };
             Err(msg)
         }
     }
 }
 
+/// Frames from the innermost, like `thread backtrace`
+fn backtrace_frames(debugger: &MainDebugger) -> Vec<rpc::Frame> {
+    debugger
+        .frame()
+        .into_iter()
+        .zip(debugger.raw_frames())
+        .rev()
+        .map(|(name, raw)| rpc::Frame {
+            name,
+            func_index: raw.func_index,
+            inst_offset: raw.inst_offset,
+        })
+        .collect()
+}
+
+fn to_debugger_breakpoint(breakpoint: &rpc::Breakpoint) -> wasminspect_debugger::Breakpoint {
+    use wasminspect_debugger::Breakpoint;
+    match breakpoint.clone() {
+        rpc::Breakpoint::Function { name } => Breakpoint::Function { name },
+        rpc::Breakpoint::FunctionIndex { index } => Breakpoint::FunctionIndex { index },
+        rpc::Breakpoint::Instruction { inst_offset } => Breakpoint::Instruction { inst_offset },
+        rpc::Breakpoint::Line { file, line } => Breakpoint::Line {
+            filepath: file,
+            line,
+        },
+    }
+}
+
 fn _handle_request<S: futures::Sink<Message> + Unpin + Send + 'static>(
     req: rpc::Request,
     process: ProcessRef,
     context: CommandCtxRef,
+    imports: ImportsRef,
+    breakpoints: BreakpointsRef,
     tx: Arc<Mutex<S>>,
     rx: Arc<mpsc::Receiver<Option<Message>>>,
 ) -> Result<rpc::Response, anyhow::Error>
@@ -398,25 +699,54 @@ where
     match req {
         Binary(req) => match req.kind {
             Init => {
-                let imports =
-                    remote_import_module(req.bytes, process.clone(), context.clone(), tx, rx)?;
-                process
-                    .borrow_mut()
-                    .debugger
-                    .load_main_module(req.bytes, "_remote_main".to_string())?;
-                process.borrow_mut().debugger.instantiate(imports, &[])?;
-                match try_load_dwarf(
-                    &req.bytes.to_vec(),
-                    &mut *Clone::clone(&context).borrow_mut(),
-                ) {
-                    Ok(_) => (),
-                    Err(err) => {
-                        log::warn!("Failed to load dwarf info: {}", err);
+                let config = process.borrow().debugger.config().clone();
+                if let Err(err) = wasminspect_vm::validate(req.bytes, &config) {
+                    return Ok(TextResponse::ValidationError {
+                        offset: err.offset,
+                        message: err.message,
                     }
+                    .into());
                 }
+                let imports = remote_import_module(
+                    req.bytes,
+                    &imports.borrow(),
+                    process.clone(),
+                    context.clone(),
+                    tx,
+                    rx,
+                )?;
+                reload_main_module(
+                    &mut process.borrow_mut().debugger,
+                    &mut *context.borrow_mut(),
+                    req.bytes,
+                    "_remote_main".to_string(),
+                )?;
+                process.borrow_mut().debugger.instantiate(imports, &[])?;
                 let exports = module_exports(req.bytes)?;
                 Ok(rpc::Response::Text(TextResponse::Init { exports }))
             }
+            ReadMemory => {
+                let ReadMemoryArgs { addr, len } = serialization::deserialize_read_memory(&req)?;
+                let memory = main_memory(&process.borrow().debugger)?;
+                let memory = memory.borrow();
+                match memory.load(addr, len) {
+                    Ok(bytes) => Ok(rpc::Response::Binary {
+                        kind: rpc::BinaryResponseKind::ReadMemoryResult,
+                        bytes: bytes.to_vec(),
+                    }),
+                    Err(_) => Ok(out_of_bounds(addr, len, memory.data_len())),
+                }
+            }
+            WriteMemory => {
+                let WriteMemoryArgs { addr, bytes } =
+                    serialization::deserialize_write_memory(&req)?;
+                let memory = main_memory(&process.borrow().debugger)?;
+                let mut memory = memory.borrow_mut();
+                match memory.store(addr, bytes) {
+                    Ok(()) => Ok(TextResponse::StoreMemoryResult.into()),
+                    Err(_) => Ok(out_of_bounds(addr, bytes.len(), memory.data_len())),
+                }
+            }
         },
         Text(InitMemory) => {
             let init_memory = rpc::Response::Binary {
@@ -429,8 +759,52 @@ where
             value: VERSION.to_string(),
         }
         .into()),
+        Text(Import { modules }) => {
+            for module in modules.iter() {
+                for import in module.imports.iter() {
+                    match import {
+                        WasmImport::Func { .. } | WasmImport::Global { .. } => (),
+                        WasmImport::Mem { name } | WasmImport::Table { name } => {
+                            return Err(anyhow::anyhow!(
+                                "importing {}.{} is not supported yet",
+                                module.name,
+                                name
+                            ))
+                        }
+                    }
+                }
+            }
+            imports.borrow_mut().extend(modules);
+            Ok(TextResponse::ImportResult.into())
+        }
         Text(CallResult { .. }) => unreachable!(),
-        Text(CallExported { name, args }) => call_exported(name, args, process, context),
+        Text(CallExported {
+            name,
+            args,
+            progress_interval,
+        }) => {
+            let stop_remotely = !breakpoints.borrow().by_id.is_empty();
+            let progress = progress_interval.map(|interval| -> ProgressFn {
+                let report = move |instruction_count, function: &str| {
+                    let progress = TextResponse::Progress {
+                        instruction_count,
+                        function: function.to_string(),
+                    };
+                    if let Err(err) = blocking_send_response(progress.into(), tx.clone()) {
+                        log::warn!("Failed to send progress: {}", err);
+                    }
+                };
+                (interval, Box::new(report))
+            });
+            call_exported(name, args, process, context, stop_remotely, progress)
+        }
+        Text(Continue) => {
+            if !process.borrow().debugger.is_running() {
+                return Err(anyhow::anyhow!("no call is stopped"));
+            }
+            let result = process.borrow().debugger.process();
+            run_response(result, process, context, true)
+        }
         Text(LoadMemory {
             name,
             offset,
@@ -455,9 +829,84 @@ where
             }
             Ok(TextResponse::StoreMemoryResult.into())
         }
+        Text(Backtrace) => {
+            let frames = backtrace_frames(&process.borrow().debugger);
+            Ok(TextResponse::BacktraceResult { frames }.into())
+        }
+        Text(Locals) => {
+            let values = process.borrow().debugger.locals();
+            let values = values.iter().map(from_vm_wasm_value).collect();
+            Ok(TextResponse::LocalsResult { values }.into())
+        }
+        Text(Globals) => {
+            let process = process.borrow();
+            let debugger = &process.debugger;
+            let store = debugger.store()?;
+            let module_index = debugger.main_module_index()?;
+            let values = (0..)
+                .map_while(|index| store.global(GlobalAddr::new_unsafe(module_index, index)))
+                .map(|global| from_vm_wasm_value(&global.borrow().value()))
+                .collect();
+            Ok(TextResponse::GlobalsResult { values }.into())
+        }
+        Text(Stack) => {
+            let values = process.borrow().debugger.stack_values();
+            let values = values.iter().map(from_vm_wasm_value).collect();
+            Ok(TextResponse::StackResult { values }.into())
+        }
+        Text(SetBreakpoint { breakpoint }) => {
+            let mut process = process.borrow_mut();
+            let target = to_debugger_breakpoint(&breakpoint);
+            process.debugger.set_breakpoint(target.clone());
+            let resolved = !process
+                .debugger
+                .resolve_breakpoints(&*context.borrow().sourcemap)
+                .contains(&target);
+            let mut breakpoints = breakpoints.borrow_mut();
+            let id = breakpoints.next_id;
+            breakpoints.next_id += 1;
+            breakpoints.by_id.insert(id, breakpoint);
+            Ok(TextResponse::SetBreakpointResult { id, resolved }.into())
+        }
+        Text(ClearBreakpoint { id }) => {
+            let mut breakpoints = breakpoints.borrow_mut();
+            let breakpoint = breakpoints
+                .by_id
+                .remove(&id)
+                .ok_or_else(|| anyhow::anyhow!("no breakpoint with id {}", id))?;
+            // The same breakpoint may have been set again under another id
+            if !breakpoints.by_id.values().any(|other| *other == breakpoint) {
+                let target = to_debugger_breakpoint(&breakpoint);
+                process.borrow_mut().debugger.delete_breakpoint(target);
+            }
+            Ok(TextResponse::ClearBreakpointResult.into())
+        }
+        Text(ListBreakpoints) => {
+            let process = process.borrow();
+            let breakpoints = breakpoints
+                .borrow()
+                .by_id
+                .iter()
+                .map(|(id, breakpoint)| BreakpointInfo {
+                    id: *id,
+                    breakpoint: breakpoint.clone(),
+                    hit_count: process
+                        .debugger
+                        .breakpoint_hits(&to_debugger_breakpoint(breakpoint))
+                        .hit_count,
+                })
+                .collect();
+            Ok(TextResponse::ListBreakpointsResult { breakpoints }.into())
+        }
     }
 }
 
+fn main_memory(debugger: &MainDebugger) -> Result<Rc<RefCell<HostMemory>>, anyhow::Error> {
+    debugger
+        .main_memory()?
+        .ok_or_else(|| anyhow::anyhow!("no memory in main module"))
+}
+
 fn memory_addr_by_name(name: &str, debugger: &MainDebugger) -> Result<MemoryAddr, anyhow::Error> {
     let addr = debugger
         .main_module()?
@@ -465,3 +914,57 @@ fn memory_addr_by_name(name: &str, debugger: &MainDebugger) -> Result<MemoryAddr
         .ok_or_else(|| anyhow::anyhow!("no exported memory"))?;
     Ok(addr)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_are_keyed_by_id() -> anyhow::Result<()> {
+        let registry = SessionRegistry::default();
+        let (first, second) = (SessionId::next(), SessionId::next());
+        let (first_tx, first_rx) = mpsc::channel();
+        let (second_tx, second_rx) = mpsc::channel();
+        registry.insert(first, first_tx);
+        registry.insert(second, second_tx);
+
+        registry.send(second, Message::Text("hello".to_string()))?;
+        assert!(first_rx.try_recv().is_err());
+        assert_eq!(
+            second_rx.try_recv()?,
+            Some(Message::Text("hello".to_string()))
+        );
+
+        registry.remove(first);
+        assert_eq!(first_rx.try_recv()?, None);
+        assert!(registry
+            .send(first, Message::Text("hello".to_string()))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sessions_own_their_debugger() -> anyhow::Result<()> {
+        let first = Session::new(SessionId::next())?;
+        let second = Session::new(SessionId::next())?;
+        reload_main_module(
+            &mut first.process.borrow_mut().debugger,
+            &mut *first.context.borrow_mut(),
+            b"(module (func (export \"f\")))",
+            "first.wat".to_string(),
+        )?;
+        assert!(first
+            .process
+            .borrow()
+            .debugger
+            .main_module_source()
+            .is_some());
+        assert!(second
+            .process
+            .borrow()
+            .debugger
+            .main_module_source()
+            .is_none());
+        Ok(())
+    }
+}
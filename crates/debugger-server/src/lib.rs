@@ -1,3 +1,4 @@
+mod dap;
 mod debugger_proxy;
 mod rpc;
 mod serialization;
@@ -15,6 +16,13 @@ pub async fn start(addr: SocketAddr) {
     run(addr).await;
 }
 
+/// Serve the Debug Adapter Protocol over stdin/stdout until the client disconnects
+pub fn start_dap() -> anyhow::Result<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    dap::run(stdin.lock(), stdout.lock())
+}
+
 async fn remote_api(req: Request<Body>) -> Result<Response<Body>, anyhow::Error> {
     match (req.method(), req.uri().path()) {
         (&Method::GET, "/debugger") => {
@@ -16,13 +16,20 @@ pub enum WasmValue {
 pub type JSNumber = f64;
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum WasmImport {
     Func { name: String },
-    Global { name: String },
+    Global { name: String, value: WasmValue },
     Mem { name: String },
     Table { name: String },
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WasmImportModule {
+    pub name: String,
+    pub imports: Vec<WasmImport>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum WasmExport {
@@ -42,9 +49,48 @@ pub enum WasmExport {
     },
 }
 
+/// A call frame of the paused program
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Frame {
+    pub name: String,
+    #[serde(rename = "funcIndex")]
+    pub func_index: usize,
+    #[serde(rename = "instOffset")]
+    pub inst_offset: usize,
+}
+
+/// Where to stop, like the options of `breakpoint set`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Breakpoint {
+    Function {
+        name: String,
+    },
+    FunctionIndex {
+        index: u32,
+    },
+    Instruction {
+        #[serde(rename = "instOffset")]
+        inst_offset: usize,
+    },
+    Line {
+        file: String,
+        line: u64,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BreakpointInfo {
+    pub id: u32,
+    pub breakpoint: Breakpoint,
+    #[serde(rename = "hitCount")]
+    pub hit_count: u64,
+}
+
 #[derive(Debug)]
 pub enum RequestError {
     InvalidBinaryRequestKind(u8),
+    MalformedBinaryRequest(BinaryRequestKind),
     InvalidTextRequestJSON(Box<dyn std::error::Error + Send + Sync>),
     InvalidMessageType(String),
     CallArgumentLengthMismatch,
@@ -61,10 +107,16 @@ impl std::error::Error for RequestError {}
 #[serde(tag = "type")]
 pub enum TextRequest {
     Version,
+    Import {
+        modules: Vec<WasmImportModule>,
+    },
     InitMemory,
     CallExported {
         name: String,
         args: Vec<JSNumber>,
+        /// Report `Progress` every this many executed instructions
+        #[serde(default, rename = "progressInterval")]
+        progress_interval: Option<u64>,
     },
     CallResult {
         values: Vec<JSNumber>,
@@ -79,11 +131,28 @@ pub enum TextRequest {
         offset: usize,
         bytes: Vec<u8>,
     },
+    Backtrace,
+    Locals,
+    Globals,
+    Stack,
+    SetBreakpoint {
+        breakpoint: Breakpoint,
+    },
+    ClearBreakpoint {
+        id: u32,
+    },
+    ListBreakpoints,
+    /// Resume a call stopped at a breakpoint
+    Continue,
 }
 
-#[derive(FromPrimitive, Debug)]
+#[derive(FromPrimitive, Clone, Copy, Debug)]
 pub enum BinaryRequestKind {
     Init = 0,
+    /// Payload: little-endian u32 `addr` and u32 `len`
+    ReadMemory = 1,
+    /// Payload: little-endian u32 `addr` followed by the bytes to write
+    WriteMemory = 2,
 }
 
 const WASM_MAGIC: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
@@ -112,6 +181,18 @@ impl<'a> BinaryRequest<'a> {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct ReadMemoryArgs {
+    pub addr: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct WriteMemoryArgs<'a> {
+    pub addr: usize,
+    pub bytes: &'a [u8],
+}
+
 #[derive(Debug)]
 pub enum Request<'a> {
     Text(TextRequest),
@@ -124,6 +205,7 @@ pub enum TextResponse {
     Version {
         value: String,
     },
+    ImportResult,
     Init {
         exports: Vec<WasmExport>,
     },
@@ -139,6 +221,53 @@ pub enum TextResponse {
         bytes: Vec<u8>,
     },
     StoreMemoryResult,
+    /// Frames from the innermost, like `thread backtrace`
+    BacktraceResult {
+        frames: Vec<Frame>,
+    },
+    /// Locals of the innermost frame
+    LocalsResult {
+        values: Vec<WasmValue>,
+    },
+    /// Globals of the main module by index
+    GlobalsResult {
+        values: Vec<WasmValue>,
+    },
+    /// Operand stack values from the bottom
+    StackResult {
+        values: Vec<WasmValue>,
+    },
+    /// `resolved` is false if the breakpoint doesn't map to any code yet
+    SetBreakpointResult {
+        id: u32,
+        resolved: bool,
+    },
+    ClearBreakpointResult,
+    ListBreakpointsResult {
+        breakpoints: Vec<BreakpointInfo>,
+    },
+    /// Sent while a `CallExported` asking for progress runs
+    Progress {
+        #[serde(rename = "instructionCount")]
+        instruction_count: u64,
+        function: String,
+    },
+    /// A call hit a breakpoint set with `SetBreakpoint`. The program can be
+    /// inspected and resumed with `Continue`.
+    Stopped {
+        frames: Vec<Frame>,
+    },
+    MemoryAccessOutOfBounds {
+        addr: usize,
+        length: usize,
+        #[serde(rename = "memorySize")]
+        memory_size: usize,
+    },
+    /// The module sent with `Init` is invalid
+    ValidationError {
+        offset: usize,
+        message: String,
+    },
     Error {
         message: String,
     },
@@ -147,6 +276,7 @@ pub enum TextResponse {
 #[repr(u8)]
 pub enum BinaryResponseKind {
     InitMemory = 0,
+    ReadMemoryResult = 1,
 }
 
 #[derive(Debug)]
@@ -30,3 +30,106 @@ pub fn serialize_response(response: rpc::Response) -> Message {
         }
     }
 }
+
+fn read_u32(bytes: &[u8], kind: rpc::BinaryRequestKind) -> Result<u32, rpc::RequestError> {
+    if bytes.len() < 4 {
+        return Err(rpc::RequestError::MalformedBinaryRequest(kind));
+    }
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[0..4]);
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub fn deserialize_read_memory(
+    req: &rpc::BinaryRequest,
+) -> Result<rpc::ReadMemoryArgs, rpc::RequestError> {
+    if req.bytes.len() != 8 {
+        return Err(rpc::RequestError::MalformedBinaryRequest(req.kind));
+    }
+    let addr = read_u32(&req.bytes[0..4], req.kind)? as usize;
+    let len = read_u32(&req.bytes[4..8], req.kind)? as usize;
+    Ok(rpc::ReadMemoryArgs { addr, len })
+}
+
+pub fn deserialize_write_memory<'a>(
+    req: &rpc::BinaryRequest<'a>,
+) -> Result<rpc::WriteMemoryArgs<'a>, rpc::RequestError> {
+    let addr = read_u32(req.bytes, req.kind)? as usize;
+    Ok(rpc::WriteMemoryArgs {
+        addr,
+        bytes: &req.bytes[4..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_memory_requests() {
+        let message = Message::binary(vec![1, 0x10, 0, 0, 0, 4, 0, 0, 0]);
+        let req = match deserialize_request(&message).unwrap() {
+            rpc::Request::Binary(req) => req,
+            other => panic!("unexpected request {:?}", other),
+        };
+        assert_eq!(
+            deserialize_read_memory(&req).unwrap(),
+            rpc::ReadMemoryArgs { addr: 16, len: 4 }
+        );
+
+        let message = Message::binary(vec![2, 0x10, 0, 0, 0, 0xaa, 0xbb]);
+        let req = match deserialize_request(&message).unwrap() {
+            rpc::Request::Binary(req) => req,
+            other => panic!("unexpected request {:?}", other),
+        };
+        assert_eq!(
+            deserialize_write_memory(&req).unwrap(),
+            rpc::WriteMemoryArgs {
+                addr: 16,
+                bytes: &[0xaa, 0xbb],
+            }
+        );
+
+        let message = Message::binary(vec![1, 0x10, 0]);
+        let req = match deserialize_request(&message).unwrap() {
+            rpc::Request::Binary(req) => req,
+            other => panic!("unexpected request {:?}", other),
+        };
+        assert!(deserialize_read_memory(&req).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_inspection_requests() {
+        let message = Message::text(r#"{"type":"Locals"}"#);
+        assert!(matches!(
+            deserialize_request(&message).unwrap(),
+            rpc::Request::Text(rpc::TextRequest::Locals)
+        ));
+
+        let response = rpc::TextResponse::BacktraceResult {
+            frames: vec![rpc::Frame {
+                name: "main".to_string(),
+                func_index: 3,
+                inst_offset: 0x2c,
+            }],
+        };
+        assert_eq!(
+            serialize_response(response.into()),
+            Message::text(
+                r#"{"type":"BacktraceResult","frames":[{"name":"main","funcIndex":3,"instOffset":44}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_deserialize_set_breakpoint() {
+        let message = Message::text(
+            r#"{"type":"SetBreakpoint","breakpoint":{"kind":"Instruction","instOffset":44}}"#,
+        );
+        let breakpoint = match deserialize_request(&message).unwrap() {
+            rpc::Request::Text(rpc::TextRequest::SetBreakpoint { breakpoint }) => breakpoint,
+            other => panic!("unexpected request {:?}", other),
+        };
+        assert_eq!(breakpoint, rpc::Breakpoint::Instruction { inst_offset: 44 });
+    }
+}
@@ -1,21 +1,20 @@
 use std::{
-    cell::RefCell,
-    rc::Rc,
+    pin::Pin,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, MutexGuard,
     },
+    task::Poll,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::anyhow;
-use futures::{Sink, SinkExt, StreamExt};
-use lazy_static::lazy_static;
+use futures::{future, Sink, SinkExt, StreamExt};
 use wasminspect_debugger::Interactive;
 
+use crate::rpc;
 use crate::{debugger_proxy, serialization};
-use crate::{debugger_proxy::ProcessRef, rpc};
 use headers::{
     Connection, Header, HeaderMapExt, SecWebsocketAccept, SecWebsocketKey, SecWebsocketVersion,
     Upgrade,
@@ -30,6 +29,12 @@ use tokio_tungstenite::tungstenite::{
 };
 use tokio_tungstenite::WebSocketStream;
 
+/// How often clients are pinged to tell whether they're still there
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+/// Sessions of clients which haven't sent anything, pongs included, for this
+/// long are torn down
+const PONG_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub async fn socket_handshake<F, Fut>(
     req: Request<Body>,
     connect: F,
@@ -95,8 +100,7 @@ where
 
 async fn handle_incoming_message<S: Sink<Message> + Unpin + Send + 'static>(
     message: Message,
-    process: ProcessRef,
-    context: debugger_proxy::CommandCtxRef,
+    session: &debugger_proxy::Session,
     tx: Arc<Mutex<S>>,
     rx: Arc<mpsc::Receiver<Option<Message>>>,
 ) -> Result<(), S::Error>
@@ -105,9 +109,9 @@ where
 {
     match serialization::deserialize_request(&message) {
         Ok(req) => {
-            let res = debugger_proxy::handle_request(req, process, context, tx.clone(), rx);
+            let res = debugger_proxy::handle_request(req, session, tx.clone(), rx);
             let msg = serialization::serialize_response(res);
-            tx.lock().unwrap().send(msg).await?;
+            lock_sink(&tx).send(msg).await?;
             Ok(())
         }
         Err(e) => {
@@ -115,119 +119,199 @@ where
                 message: e.to_string(),
             };
             let msg = serialization::serialize_response(response.into());
-            tx.lock().unwrap().send(msg).await?;
+            lock_sink(&tx).send(msg).await?;
             Ok(())
         }
     }
 }
 
-lazy_static! {
-    static ref CONNECTION_LOCK: Arc<tokio::sync::Mutex<bool>> =
-        Arc::new(tokio::sync::Mutex::new(false));
+/// The sink stays usable even if a thread panicked while sending
+fn lock_sink<S>(tx: &Mutex<S>) -> MutexGuard<'_, S> {
+    tx.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Send a ping without holding the sink's lock across polls, which would make
+/// the connection's future not `Send`
+async fn send_ping<S: Sink<Message> + Unpin>(tx: &Mutex<S>) -> Result<(), S::Error> {
+    let mut ping = Some(Message::Ping(vec![]));
+    future::poll_fn(|cx| {
+        let mut tx = lock_sink(tx);
+        if let Some(msg) = ping.take() {
+            if Pin::new(&mut *tx).poll_ready(cx)?.is_pending() {
+                ping = Some(msg);
+                return Poll::Pending;
+            }
+            Pin::new(&mut *tx).start_send(msg)?;
+        }
+        Pin::new(&mut *tx).poll_flush(cx)
+    })
+    .await
 }
 
 pub async fn establish_connection(upgraded: Upgraded) -> Result<(), anyhow::Error> {
-    let _guard = CONNECTION_LOCK.lock().await;
-    let result = _establish_connection(upgraded).await;
+    let session_id = debugger_proxy::SessionId::next();
+    log::debug!("[{}] Connection established", session_id);
+    let result = _establish_connection(upgraded, session_id).await;
+    log::debug!("[{}] Connection closed", session_id);
     result
 }
 
-async fn _establish_connection(upgraded: Upgraded) -> Result<(), anyhow::Error> {
+async fn _establish_connection(
+    upgraded: Upgraded,
+    session_id: debugger_proxy::SessionId,
+) -> Result<(), anyhow::Error> {
     let config = WebSocketConfig {
         max_message_size: None,
         ..WebSocketConfig::default()
     };
     let ws = WebSocketStream::from_raw_socket(upgraded, protocol::Role::Server, Some(config)).await;
     let (tx, mut rx) = ws.split();
+    let tx = Arc::new(Mutex::new(tx));
+    let debugger_tx = tx.clone();
     let (request_tx, request_rx) = mpsc::channel::<Option<Message>>();
     let connection_finished = Arc::new(AtomicBool::new(false));
     let connection_finished_reader = connection_finished.clone();
 
-    let handle = thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async move {
-            log::debug!("Start debugger thread");
-            let (process, dbg_context) =
-                wasminspect_debugger::start_debugger(None, vec![], vec![]).unwrap();
-            let process = Rc::new(RefCell::new(process));
-
-            let mut last_line: Option<String> = None;
-            let step_timeout = Duration::from_millis(500);
-            if std::env::var("WASMINSPECT_SERVER_NO_INTERACTIVE").is_err() {
-                let mut interactive = Interactive::new_with_loading_history().unwrap();
-                loop {
-                    if connection_finished_reader.load(Ordering::Relaxed) {
-                        interactive.interface.cancel_read_line().unwrap();
-                        log::debug!("Debugger thread interrupted");
-                        break;
+    let handle = thread::Builder::new()
+        .name(session_id.to_string())
+        .spawn(move || {
+            let rt = match tokio::runtime::Runtime::new() {
+                Ok(rt) => rt,
+                Err(err) => {
+                    log::error!("[{}] Failed to create runtime: {}", session_id, err);
+                    return;
+                }
+            };
+            rt.block_on(async move {
+                log::debug!("[{}] Start debugger thread", session_id);
+                let session = match debugger_proxy::Session::new(session_id) {
+                    Ok(session) => session,
+                    Err(err) => {
+                        log::error!("[{}] Failed to start debugger: {:?}", session_id, err);
+                        return;
                     }
-                    let result = interactive.run_step(
-                        &dbg_context,
-                        process.clone(),
-                        &mut last_line,
-                        Some(step_timeout),
-                    );
-                    match result {
-                        Ok(Some(wasminspect_debugger::CommandResult::Exit)) => {
+                };
+
+                let mut last_line: Option<String> = None;
+                let step_timeout = Duration::from_millis(500);
+                // Another session may already own the terminal, skip the prompt in that case
+                let console = if std::env::var("WASMINSPECT_SERVER_NO_INTERACTIVE").is_err() {
+                    debugger_proxy::try_lock_console()
+                } else {
+                    None
+                };
+                let interactive = console.as_ref().and_then(|_| {
+                    Interactive::new_with_loading_history()
+                        .map_err(|err| {
+                            log::error!("[{}] Failed to open the console: {}", session_id, err)
+                        })
+                        .ok()
+                });
+                if let Some(mut interactive) = interactive {
+                    interactive.set_completer(session.process.borrow().completer());
+                    loop {
+                        if connection_finished_reader.load(Ordering::Relaxed) {
+                            if let Err(err) = interactive.interface.cancel_read_line() {
+                                log::error!(
+                                    "[{}] Failed to cancel the prompt: {}",
+                                    session_id,
+                                    err
+                                );
+                            }
+                            log::debug!("Debugger thread interrupted");
                             break;
                         }
-                        Ok(Some(_)) => unreachable!("unexpected run_step result"),
-                        Ok(None) => continue,
-                        Err(e) => {
-                            log::error!("catch error in run_step: {:?}", e);
+                        let result = interactive.run_step(
+                            &mut *session.context.borrow_mut(),
+                            session.process.clone(),
+                            &mut last_line,
+                            Some(step_timeout),
+                        );
+                        match result {
+                            Ok(Some(wasminspect_debugger::CommandResult::Exit)) => {
+                                break;
+                            }
+                            Ok(Some(_)) => unreachable!("unexpected run_step result"),
+                            Ok(None) => continue,
+                            Err(e) => {
+                                log::error!("catch error in run_step: {:?}", e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                drop(console);
+                log::debug!("[{}] Start receiving messages", session_id);
+
+                let tx = debugger_tx;
+                let request_rx = Arc::new(request_rx);
+                loop {
+                    let msg = match request_rx.recv() {
+                        Ok(Some(msg)) => msg,
+                        Ok(None) => break,
+                        Err(_) => break,
+                    };
+                    match handle_incoming_message(msg, &session, tx.clone(), request_rx.clone())
+                        .await
+                    {
+                        Ok(()) => continue,
+                        Err(err) => {
+                            log::error!("[{}] Sink error: {}", session_id, err);
                             break;
                         }
                     }
                 }
-            }
-            log::debug!("Start receiving messages");
+            });
+        })?;
+    debugger_proxy::SESSIONS.insert(session_id, request_tx);
 
-            let tx = Arc::new(Mutex::new(tx));
-            let request_rx = Arc::new(request_rx);
-            let dbg_context = Rc::new(RefCell::new(dbg_context));
-            loop {
-                let msg = match request_rx.recv() {
-                    Ok(Some(msg)) => msg,
-                    Ok(None) => break,
-                    Err(_) => break,
-                };
-                match handle_incoming_message(
-                    msg,
-                    process.clone(),
-                    dbg_context.clone(),
-                    tx.clone(),
-                    request_rx.clone(),
-                )
-                .await
-                {
-                    Ok(()) => continue,
-                    Err(err) => {
-                        log::error!("Sink error: {}", err);
+    let mut result = Ok(());
+    let mut heartbeat = tokio::time::interval(PING_INTERVAL);
+    let mut last_seen = Instant::now();
+    loop {
+        tokio::select! {
+            msg = rx.next() => match msg {
+                Some(Ok(msg)) => {
+                    last_seen = Instant::now();
+                    // Pings are answered by the stream itself
+                    if matches!(msg, Message::Ping(_) | Message::Pong(_)) {
+                        continue;
+                    }
+                    if let Err(err) = debugger_proxy::SESSIONS.send(session_id, msg) {
+                        log::error!("[{}] {}", session_id, err);
                         break;
                     }
                 }
-            }
-        });
-    });
-
-    while let Some(msg) = rx.next().await {
-        match msg {
-            Ok(msg) => {
-                request_tx.send(Some(msg))?;
-            }
-            Err(e) => {
-                request_tx.send(None).unwrap();
-                return Err(e.into());
+                Some(Err(e)) => {
+                    result = Err(e.into());
+                    break;
+                }
+                None => break,
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > PONG_TIMEOUT {
+                    log::error!("[{}] Client stopped answering pings", session_id);
+                    break;
+                }
+                if let Err(err) = send_ping(&tx).await {
+                    log::error!("[{}] Failed to send ping: {}", session_id, err);
+                    break;
+                }
             }
         }
     }
 
     log::debug!("Start epilogue of socket");
     connection_finished.store(true, Ordering::Relaxed);
-    request_tx.send(None).unwrap();
-    handle.join().unwrap();
+    debugger_proxy::SESSIONS.remove(session_id);
+    // Don't block the runtime's worker while the debugger thread winds down
+    match tokio::task::spawn_blocking(move || handle.join()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(_)) => log::error!("[{}] Debugger thread panicked", session_id),
+        Err(err) => log::error!("[{}] Failed to join debugger thread: {}", session_id, err),
+    }
     log::debug!("End epilogue of socket");
-    Ok(())
+    result
 }
 
 #[cfg(test)]
@@ -0,0 +1,108 @@
+use super::command::AliasCommand;
+use anyhow::{anyhow, Result};
+
+/// Alias defined at runtime by `alias <name> <expansion>`
+///
+/// `$1`, `$2`, ... in the expansion are replaced by the arguments given to
+/// the alias and `$*` by all of them. If the expansion refers to none, the
+/// arguments are appended to it instead.
+pub struct UserAlias {
+    name: String,
+    expansion: String,
+}
+
+impl UserAlias {
+    pub fn new(name: String, expansion: String) -> Self {
+        Self { name, expansion }
+    }
+
+    fn expand(&self, args: &[&str]) -> Result<String> {
+        let mut line = String::new();
+        let mut substituted = false;
+        let mut chars = self.expansion.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                line.push(c);
+                continue;
+            }
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                line += &shell_words::join(args);
+                substituted = true;
+                continue;
+            }
+            let mut digits = String::new();
+            while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(*digit);
+                chars.next();
+            }
+            match digits.parse::<usize>() {
+                Ok(0) | Err(_) => {
+                    line.push('$');
+                    line += &digits;
+                }
+                Ok(index) => {
+                    let arg = args.get(index - 1).ok_or_else(|| {
+                        anyhow!("alias '{}' expects argument ${}", self.name, index)
+                    })?;
+                    line += &shell_words::quote(arg);
+                    substituted = true;
+                }
+            }
+        }
+        if !substituted && !args.is_empty() {
+            line.push(' ');
+            line += &shell_words::join(args);
+        }
+        Ok(line)
+    }
+}
+
+impl AliasCommand for UserAlias {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.expansion
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        self.expand(&args[1..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alias(expansion: &str) -> UserAlias {
+        UserAlias::new("a".to_string(), expansion.to_string())
+    }
+
+    #[test]
+    fn expand_substitutes_numbered_arguments() {
+        let alias = alias("memory read -c $2 $1");
+        assert_eq!(
+            alias.expand(&["0x10", "4"]).unwrap(),
+            "memory read -c 4 0x10"
+        );
+        assert_eq!(
+            alias.expand(&["0x10"]).unwrap_err().to_string(),
+            "alias 'a' expects argument $2"
+        );
+    }
+
+    #[test]
+    fn expand_appends_unreferenced_arguments() {
+        assert_eq!(
+            alias("breakpoint set -n").expand(&["main"]).unwrap(),
+            "breakpoint set -n main"
+        );
+        assert_eq!(
+            alias("expression $*").expand(&["a b", "c"]).unwrap(),
+            "expression 'a b' c"
+        );
+        assert_eq!(alias("echo $0 $").expand(&[]).unwrap(), "echo $0 $");
+    }
+}
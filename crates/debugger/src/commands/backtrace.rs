@@ -14,7 +14,12 @@ impl AliasCommand for BacktraceCommand {
         "bt"
     }
 
-    fn run(&self, _args: Vec<&str>) -> Result<String> {
-        Ok("thread backtrace".to_string())
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        let mut line = "thread backtrace".to_string();
+        for arg in &args[1..] {
+            line += " ";
+            line += arg;
+        }
+        Ok(line)
     }
 }
@@ -1,4 +1,5 @@
 use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
 use super::debugger::{Breakpoint, Debugger};
 use anyhow::{anyhow, Result};
 use structopt::StructOpt;
@@ -16,20 +17,39 @@ enum Opts {
     /// Sets a breakpoint for the given symbol in executable
     #[structopt(name = "set")]
     Set(SetOpts),
+    /// Lists breakpoints with how many times they were hit
+    #[structopt(name = "list")]
+    List,
 }
 
 #[derive(StructOpt)]
 struct SetOpts {
     #[structopt(short, long)]
     name: Option<String>,
+    /// Break on entry to a function, by name, export name or index
+    #[structopt(long)]
+    function: Option<String>,
     #[structopt(short, long)]
     address: Option<String>,
+    /// Source file of a line breakpoint, used with --line
+    #[structopt(short, long, requires = "line")]
+    file: Option<String>,
+    #[structopt(short, long, requires = "file")]
+    line: Option<u64>,
+    /// Number of hits to pass before stopping
+    #[structopt(long)]
+    ignore: Option<u64>,
 }
 
 impl SetOpts {
     fn breakpoint(self) -> Result<Breakpoint> {
         if let Some(name) = self.name {
             Ok(Breakpoint::Function { name })
+        } else if let Some(function) = self.function {
+            match function.parse::<u32>() {
+                Ok(index) => Ok(Breakpoint::FunctionIndex { index }),
+                Err(_) => Ok(Breakpoint::Function { name: function }),
+            }
         } else if let Some(address) = self.address {
             let address = if address.starts_with("0x") {
                 let raw = address.trim_start_matches("0x");
@@ -40,6 +60,8 @@ impl SetOpts {
             Ok(Breakpoint::Instruction {
                 inst_offset: address,
             })
+        } else if let (Some(filepath), Some(line)) = (self.file, self.line) {
+            Ok(Breakpoint::Line { filepath, line })
         } else {
             Err(anyhow!("no breakpoint option"))
         }
@@ -55,16 +77,52 @@ impl<D: Debugger> Command<D> for BreakpointCommand {
         "Commands for operating on breakpoints."
     }
 
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
         debugger: &mut D,
-        _context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
             Opts::Set(opts) => {
-                debugger.set_breakpoint(opts.breakpoint()?);
+                let ignore = opts.ignore;
+                let breakpoint = opts.breakpoint()?;
+                debugger.set_breakpoint(breakpoint.clone());
+                if let Some(count) = ignore {
+                    debugger.set_ignore_count(&breakpoint, count);
+                }
+                if debugger
+                    .resolve_breakpoints(&*context.sourcemap)
+                    .contains(&breakpoint)
+                {
+                    let output = format!("Breakpoint {} doesn't map to any code yet", breakpoint);
+                    context.printer.eprintln(&output);
+                }
+                Ok(None)
+            }
+            Opts::List => {
+                let breakpoints = debugger.breakpoints();
+                if breakpoints.is_empty() {
+                    context.printer.println("No breakpoints currently set.");
+                }
+                for (index, breakpoint) in breakpoints.iter().enumerate() {
+                    let hits = debugger.breakpoint_hits(breakpoint);
+                    let mut output = format!(
+                        "{}: {}, hit count = {}",
+                        index + 1,
+                        breakpoint,
+                        hits.hit_count
+                    );
+                    if hits.ignore_count > 0 {
+                        output += &format!(", ignore count = {}", hits.ignore_count);
+                    }
+                    context.printer.println(&output);
+                }
                 Ok(None)
             }
         }
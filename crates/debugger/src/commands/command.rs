@@ -1,4 +1,6 @@
+use super::completion::CompletionTree;
 use super::debugger::{Debugger, OutputPrinter};
+use super::names::NameMap;
 use super::sourcemap::SourceMap;
 use super::subroutine::SubroutineMap;
 use anyhow::Result;
@@ -7,6 +9,7 @@ use wasminspect_vm::WasmValue;
 pub struct CommandContext {
     pub sourcemap: Box<dyn SourceMap>,
     pub subroutine: Box<dyn SubroutineMap>,
+    pub names: NameMap,
     pub printer: Box<dyn OutputPrinter>,
 }
 
@@ -24,15 +27,23 @@ pub trait Command<D: Debugger> {
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>>;
+    /// Subcommands and flags offered by tab completion
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::default()
+    }
 }
 
 pub trait AliasCommand {
-    fn name(&self) -> &'static str;
-    fn description(&self) -> &'static str {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str {
         "No description yet"
     }
     fn run(&self, args: Vec<&str>) -> Result<String>;
+    /// Subcommands and flags offered by tab completion
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::default()
+    }
 }
@@ -0,0 +1,168 @@
+use linefeed::{Completer, Completion, Prompter, Terminal};
+
+/// Subcommands and flags accepted after a command or subcommand name
+#[derive(Default)]
+pub struct CompletionTree {
+    subcommands: Vec<(String, CompletionTree)>,
+    flags: Vec<String>,
+}
+
+impl CompletionTree {
+    /// Collect the subcommands and flags of a `StructOpt` definition,
+    /// e.g. `CompletionTree::from_app(&Opts::clap())`
+    pub fn from_app(app: &clap::App) -> Self {
+        let switches = app
+            .p
+            .flags()
+            .map(|flag| (flag.s.long, flag.s.short))
+            .chain(app.p.opts().map(|opt| (opt.s.long, opt.s.short)));
+        let mut flags: Vec<_> = switches
+            .filter_map(|(long, short)| match (long, short) {
+                (Some(long), _) => Some(format!("--{}", long)),
+                (None, Some(short)) => Some(format!("-{}", short)),
+                (None, None) => None,
+            })
+            .collect();
+        // clap only adds --help when parsing, so it is missing from the definition
+        flags.push("--help".to_string());
+        flags.sort();
+        flags.dedup();
+
+        let mut subcommands: Vec<_> = app
+            .p
+            .subcommands()
+            .map(|app| (app.p.meta.name.clone(), Self::from_app(app)))
+            .collect();
+        subcommands.sort_by(|a, b| a.0.cmp(&b.0));
+        Self { subcommands, flags }
+    }
+
+    /// Complete one of `words` and nothing after it
+    pub fn from_words(words: impl Iterator<Item = String>) -> Self {
+        let mut subcommands: Vec<_> = words.map(|word| (word, Self::default())).collect();
+        subcommands.sort_by(|a, b| a.0.cmp(&b.0));
+        Self {
+            subcommands,
+            flags: vec![],
+        }
+    }
+
+    /// Words following `words`, the subcommand path already typed
+    fn candidates(&self, words: &[&str], word: &str) -> Vec<String> {
+        let mut node = self;
+        for typed in words {
+            if let Some((_, child)) = node.subcommands.iter().find(|(name, _)| name == typed) {
+                node = child;
+            }
+        }
+        let words = if word.starts_with('-') {
+            node.flags.iter().collect::<Vec<_>>()
+        } else {
+            node.subcommands.iter().map(|(name, _)| name).collect()
+        };
+        words
+            .into_iter()
+            .filter(|name| name.starts_with(word))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Completes command names at the start of a line and the subcommands and
+/// flags of the typed command after it
+pub struct CommandCompleter {
+    commands: Vec<(String, CompletionTree)>,
+}
+
+impl CommandCompleter {
+    pub fn new(mut commands: Vec<(String, CompletionTree)>) -> Self {
+        commands.sort_by(|a, b| a.0.cmp(&b.0));
+        Self { commands }
+    }
+
+    /// Completions of `word` when preceded by `line`
+    pub fn candidates(&self, line: &str, word: &str) -> Vec<String> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None => self
+                .commands
+                .iter()
+                .map(|(name, _)| name)
+                .filter(|name| name.starts_with(word))
+                .cloned()
+                .collect(),
+            Some(command) => match self.commands.iter().find(|(name, _)| name == command) {
+                Some((_, tree)) => tree.candidates(&words.collect::<Vec<_>>(), word),
+                None => vec![],
+            },
+        }
+    }
+}
+
+impl<Term: Terminal> Completer<Term> for CommandCompleter {
+    fn complete(
+        &self,
+        word: &str,
+        prompter: &Prompter<Term>,
+        start: usize,
+        _end: usize,
+    ) -> Option<Vec<Completion>> {
+        let line = &prompter.buffer()[..start];
+        let completions = self.candidates(line, word);
+        Some(completions.into_iter().map(Completion::simple).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structopt::StructOpt;
+
+    // Only the definition is used, the options are never parsed
+    #[allow(dead_code)]
+    #[derive(StructOpt)]
+    enum Opts {
+        #[structopt(name = "set")]
+        Set {
+            #[structopt(short, long)]
+            file: Option<String>,
+            #[structopt(short, long)]
+            line: Option<u64>,
+            #[structopt(short)]
+            verbose: bool,
+        },
+        #[structopt(name = "list")]
+        List,
+    }
+
+    fn completer() -> CommandCompleter {
+        CommandCompleter::new(vec![
+            (
+                "breakpoint".to_string(),
+                CompletionTree::from_app(&Opts::clap()),
+            ),
+            ("bt".to_string(), CompletionTree::default()),
+        ])
+    }
+
+    #[test]
+    fn completes_command_names_at_line_start() {
+        assert_eq!(completer().candidates("", "b"), vec!["breakpoint", "bt"]);
+        assert_eq!(completer().candidates("  ", "br"), vec!["breakpoint"]);
+    }
+
+    #[test]
+    fn completes_subcommands_and_flags() {
+        let completer = completer();
+        assert_eq!(completer.candidates("breakpoint ", ""), vec!["list", "set"]);
+        assert_eq!(
+            completer.candidates("breakpoint set ", "--"),
+            vec!["--file", "--help", "--line"]
+        );
+        assert_eq!(
+            completer.candidates("breakpoint set --file a.c ", "-"),
+            vec!["--file", "--help", "--line", "-v"]
+        );
+        assert!(completer.candidates("unknown ", "--").is_empty());
+    }
+}
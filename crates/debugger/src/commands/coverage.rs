@@ -0,0 +1,124 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
+use super::debugger::{Debugger, FunctionCoverage};
+use super::symbol::demangle_symbol;
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use structopt::StructOpt;
+
+pub struct CoverageCommand {}
+
+impl CoverageCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Start recording executed instructions
+    #[structopt(name = "start")]
+    Start,
+    /// Stop recording, keeping the coverage so far
+    #[structopt(name = "stop")]
+    Stop,
+    /// Write covered code offsets per function as JSON
+    #[structopt(name = "dump")]
+    Dump { path: String },
+    /// Show the percentage of executed instructions per function
+    #[structopt(name = "summary")]
+    Summary,
+}
+
+#[derive(Serialize)]
+struct CoverageReport<'a> {
+    functions: &'a [FunctionCoverage],
+}
+
+impl<D: Debugger> Command<D> for CoverageCommand {
+    fn name(&self) -> &'static str {
+        "coverage"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for inspecting executed code coverage."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Start => debugger.set_recording_coverage(true),
+            Opts::Stop => debugger.set_recording_coverage(false),
+            Opts::Dump { path } => {
+                let coverage = recorded_coverage(debugger, context)?;
+                let file = std::fs::File::create(&path)
+                    .with_context(|| format!("failed to create {}", path))?;
+                serde_json::to_writer(
+                    file,
+                    &CoverageReport {
+                        functions: &coverage,
+                    },
+                )?;
+                context
+                    .printer
+                    .println(&format!("Coverage written to {}", path));
+            }
+            Opts::Summary => {
+                let coverage = recorded_coverage(debugger, context)?;
+                let mut covered = 0;
+                let mut total = 0;
+                for func in &coverage {
+                    covered += func.covered_offsets.len();
+                    total += func.instruction_count;
+                    let output = format!(
+                        "{:>6.2}% {:>6}/{:<6} {}",
+                        percentage(func.covered_offsets.len(), func.instruction_count),
+                        func.covered_offsets.len(),
+                        func.instruction_count,
+                        demangle_symbol(&func.name)
+                    );
+                    context.printer.println(&output);
+                }
+                let output = format!(
+                    "{:>6.2}% {:>6}/{:<6} total",
+                    percentage(covered, total),
+                    covered,
+                    total
+                );
+                context.printer.println(&output);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Coverage recorded so far, with a hint when recording is off
+fn recorded_coverage<D: Debugger>(
+    debugger: &D,
+    context: &CommandContext,
+) -> Result<Vec<FunctionCoverage>> {
+    if !debugger.is_recording_coverage() {
+        context
+            .printer
+            .eprintln("Coverage recording is off, start it with `coverage start`");
+    }
+    debugger.coverage()
+}
+
+fn percentage(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    }
+}
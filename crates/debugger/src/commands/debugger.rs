@@ -1,42 +1,271 @@
+use super::command::CommandContext;
+use super::sourcemap::SourceMap;
+use super::symbol::demangle_symbol;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use wasminspect_vm::{HostValue, Instruction, ModuleIndex, Signal, Store, WasmValue};
 
-#[derive(Default, Clone)]
+/// Debugger settings, listed and changed by the `settings` command
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DebuggerOpts {
     pub watch_memory: bool,
+    /// Stop at the trapping instruction instead of aborting the run
+    #[serde(default)]
+    pub break_on_trap: bool,
+    /// Number of instructions shown after stepping or by `disassemble --pc`
+    #[serde(default = "default_disassemble_count")]
+    pub disassemble_count: usize,
+    /// Highlight disassembly and source listings when printing to a terminal
+    #[serde(default)]
+    pub color: bool,
+    /// Views printed, in order, whenever a command advances execution
+    #[serde(default = "default_stop_display")]
+    pub stop_display: Vec<StopView>,
+    /// Stop when a declared local is read before it was written
+    #[serde(default)]
+    pub poison_locals: bool,
 }
 
+fn default_disassemble_count() -> usize {
+    4
+}
+
+fn default_stop_display() -> Vec<StopView> {
+    vec![StopView::Source, StopView::Asm]
+}
+
+/// A view of the stopped process, shown by the `stop-display` setting
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StopView {
+    Asm,
+    Source,
+    Locals,
+}
+
+impl StopView {
+    pub const ALL: [StopView; 3] = [StopView::Asm, StopView::Source, StopView::Locals];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StopView::Asm => "asm",
+            StopView::Source => "source",
+            StopView::Locals => "locals",
+        }
+    }
+
+    /// The command printing this view
+    pub fn command(self) -> &'static str {
+        match self {
+            StopView::Asm => "disassemble --pc",
+            StopView::Source => "list",
+            StopView::Locals => "local read",
+        }
+    }
+}
+
+impl Default for DebuggerOpts {
+    fn default() -> Self {
+        Self {
+            watch_memory: false,
+            break_on_trap: false,
+            disassemble_count: default_disassemble_count(),
+            color: false,
+            stop_display: default_stop_display(),
+            poison_locals: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Breakpoint {
-    Function { name: String },
-    Instruction { inst_offset: usize },
+    Function {
+        name: String,
+    },
+    /// Entry of the function at `index` in the main module
+    FunctionIndex {
+        index: u32,
+    },
+    Instruction {
+        inst_offset: usize,
+    },
+    /// Resolved to a code offset whenever debug info is (re)loaded
+    Line {
+        filepath: String,
+        line: u64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BreakpointHits {
+    pub hit_count: u64,
+    /// Number of hits to pass before stopping
+    pub ignore_count: u64,
+}
+
+#[derive(Serialize)]
+pub struct FunctionCoverage {
+    pub name: String,
+    /// Code offsets of executed instructions in ascending order
+    pub covered_offsets: Vec<usize>,
+    pub instruction_count: usize,
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Function { name } => write!(f, "{}", name),
+            Self::FunctionIndex { index } => write!(f, "func #{}", index),
+            Self::Instruction { inst_offset } => write!(f, "0x{:>08x}", inst_offset),
+            Self::Line { filepath, line } => write!(f, "{}:{}", filepath, line),
+        }
+    }
 }
 
 pub enum RunResult {
     Finish(Vec<WasmValue>),
     Breakpoint,
+    /// Stopped at a trapping instruction because `break-on-trap` is enabled.
+    /// The execution state is kept, but the process can't continue.
+    Trap(anyhow::Error),
+    /// Stopped because the run took longer than the timeout given to
+    /// `set_timeout`. The execution state is kept.
+    Timeout,
 }
 
 #[derive(Clone, Copy)]
 pub enum StepStyle {
+    /// Execute one instruction, entering the callee of a call. Calls to
+    /// host functions run to completion since they have no instructions.
     InstIn,
+    /// Execute one instruction, running a callee of a call to completion
+    /// unless a breakpoint in it is hit
     InstOver,
+    /// Run until the current function returns to its caller
     Out,
 }
 
+/// A trap raised while executing the instruction at `code_offset`
+#[derive(Debug)]
+pub struct TrapError {
+    pub message: String,
+    pub code_offset: usize,
+    /// Code offset of the called function's body on a call type mismatch,
+    /// to name it from the debug info
+    pub callee_offset: Option<usize>,
+    /// Where the guest panicked, if the trap ended a Rust panic
+    pub panic: Option<RustPanic>,
+}
+
+/// A Rust panic which ended in `unreachable`
+#[derive(Debug)]
+pub struct RustPanic {
+    /// The innermost function outside of the standard library
+    pub function: String,
+    /// Code offset of the call in `function` which panicked
+    pub code_offset: usize,
+    /// The standard library function `function` called
+    pub entry: String,
+}
+
+impl std::fmt::Display for TrapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TrapError {}
+
+/// Format an error and its causes for display, pointing at the source
+/// location of the faulting instruction if it was a trap and debug info is
+/// available, naming the callee of a mismatched call in the source and
+/// where a Rust panic started
+pub fn describe_error(err: &anyhow::Error, context: &CommandContext) -> String {
+    let trap = err.downcast_ref::<TrapError>();
+    let location = trap.and_then(|trap| context.sourcemap.find_line_info(trap.code_offset));
+    let mut output = match location {
+        Some(line_info) => format!("{:#} at {}", err, line_info),
+        None => format!("{:#}", err),
+    };
+    let callee = trap
+        .and_then(|trap| trap.callee_offset)
+        .and_then(|offset| context.subroutine.function_name(offset));
+    if let Some(name) = callee {
+        output += &format!("\n >> callee is `{}` in the source", name);
+    }
+    if let Some(panic) = trap.and_then(|trap| trap.panic.as_ref()) {
+        output += &format!("\n >> panicked in `{}`", demangle_symbol(&panic.function));
+        if let Some(line_info) = context.sourcemap.find_line_info(panic.code_offset) {
+            output += &format!(" at {}", line_info);
+        }
+        output += &format!(" via `{}`", demangle_symbol(&panic.entry));
+    }
+    output
+}
+
+/// An operand stack entry, ordered from the bottom of the stack
+pub enum StackEntry {
+    Value(WasmValue),
+    Label { kind: &'static str, arity: usize },
+    Frame { name: String },
+}
+
 pub struct FunctionFrame {
     pub module_index: ModuleIndex,
     pub argument_count: usize,
 }
 
+/// A call frame as the VM sees it, without any symbol information
+pub struct RawFrame {
+    /// Index of the function in its module's function index space
+    pub func_index: usize,
+    /// Code offset of the next instruction, or of the call in callers
+    pub inst_offset: usize,
+    /// Code offset in the caller to return to, `None` for the outermost frame
+    pub ret_offset: Option<usize>,
+}
+
+/// Role of a piece of output, which printers may render in color
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Plain,
+    /// The `->` marker pointing at the current instruction or line
+    Marker,
+    Opcode,
+    Immediate,
+    CurrentLine,
+    ContextLine,
+}
+
 pub trait OutputPrinter {
     fn println(&self, _: &str);
     fn eprintln(&self, _: &str);
+    /// Print a line made of `spans`. Printers without color support print
+    /// the text as is.
+    fn println_styled(&self, spans: &[(Style, &str)]) {
+        let line: String = spans.iter().map(|(_, text)| *text).collect();
+        self.println(&line);
+    }
+    /// Enable or disable colored output where the printer supports it
+    fn set_color(&self, _enable: bool) {}
+    /// Print a command's result, as `text` for people or as `record` with
+    /// the same data for tools. Printers without JSON output print `text`,
+    /// or nothing if it's empty.
+    fn print_record(&self, text: &str, _record: &serde_json::Value) {
+        if !text.is_empty() {
+            self.println(text);
+        }
+    }
 }
 pub type RawHostModule = std::collections::HashMap<String, HostValue>;
 
 pub trait Debugger {
     fn get_opts(&self) -> DebuggerOpts;
     fn set_opts(&mut self, opts: DebuggerOpts);
+    /// Break into the debugger once execution runs longer than `timeout`
+    fn set_timeout(&mut self, timeout: Option<std::time::Duration>);
+    fn load_main_module(&mut self, module: &[u8], name: String) -> Result<()>;
+    fn main_module_source(&self) -> Option<(&[u8], &str)>;
     fn instantiate(
         &mut self,
         host_modules: std::collections::HashMap<String, RawHostModule>,
@@ -46,12 +275,45 @@ pub trait Debugger {
     fn is_running(&self) -> bool;
     fn frame(&self) -> Vec<String>;
     fn current_frame(&self) -> Option<FunctionFrame>;
+    /// Activation records from the outermost, in the same order as `frame`
+    fn raw_frames(&self) -> Vec<RawFrame>;
     fn locals(&self) -> Vec<WasmValue>;
     fn memory(&self) -> Result<Vec<u8>>;
     fn store(&self) -> Result<&Store>;
+    fn main_module_index(&self) -> Result<ModuleIndex>;
     fn set_breakpoint(&mut self, breakpoint: Breakpoint);
+    fn delete_breakpoint(&mut self, breakpoint: Breakpoint);
+    fn breakpoints(&self) -> Vec<Breakpoint>;
+    /// Don't stop at `breakpoint` until it's been hit more than `count` times
+    fn set_ignore_count(&mut self, breakpoint: &Breakpoint, count: u64);
+    fn breakpoint_hits(&self, breakpoint: &Breakpoint) -> BreakpointHits;
+    /// Stop the next time the instruction at `inst_offset` is executed, then forget it.
+    /// `None` clears the pending one.
+    fn set_temporary_breakpoint(&mut self, inst_offset: Option<usize>);
+    /// The temporary breakpoint which hasn't been hit yet
+    fn temporary_breakpoint(&self) -> Option<usize>;
+    /// Count executed instructions per function from now on, or stop counting.
+    /// Counting is off by default since it costs a map update per instruction.
+    fn set_profiling(&mut self, enabled: bool);
+    fn is_profiling(&self) -> bool;
+    /// Executed instruction counts per function, the hottest first
+    fn profile(&self) -> Result<Vec<(String, u64)>>;
+    fn reset_profile(&mut self);
+    /// Record executed instruction offsets from now on, or stop recording.
+    /// Off by default like profiling.
+    fn set_recording_coverage(&mut self, enabled: bool);
+    fn is_recording_coverage(&self) -> bool;
+    /// Executed instruction offsets of each function defined in the main module
+    fn coverage(&self) -> Result<Vec<FunctionCoverage>>;
+    /// Re-resolve breakpoints against the loaded module and return ones which don't map to any code
+    fn resolve_breakpoints(&mut self, sourcemap: &dyn SourceMap) -> Vec<Breakpoint>;
     fn stack_values(&self) -> Vec<WasmValue>;
+    fn stack_entries(&self) -> Vec<StackEntry>;
     fn instructions(&self) -> Result<(&[Instruction], usize)>;
     fn step(&self, style: StepStyle) -> Result<Signal>;
     fn process(&self) -> Result<RunResult>;
+    /// Like `process`, but run through the first `skip` breakpoint hits
+    fn process_skipping(&self, skip: u64) -> Result<RunResult>;
+    /// Incremented whenever execution advances, to tell whether a command moved the program
+    fn stop_count(&self) -> usize;
 }
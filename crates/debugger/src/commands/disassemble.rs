@@ -1,7 +1,12 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::{Debugger, OutputPrinter};
+use super::completion::CompletionTree;
+use super::debugger::{Debugger, OutputPrinter, Style};
+use super::image::format_func_type;
+use super::symbol::demangle_symbol;
 use structopt::StructOpt;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::ops::RangeInclusive;
+use wasminspect_vm::Instruction;
 
 pub struct DisassembleCommand {}
 
@@ -17,6 +22,20 @@ struct Opts {
     count: Option<usize>,
     #[structopt(short, long)]
     pc: bool,
+    /// Show the bytes each instruction was decoded from
+    #[structopt(long)]
+    raw: bool,
+    /// Disassemble every function of the main module
+    #[structopt(long, conflicts_with_all = &["pc", "function-range"])]
+    all: bool,
+    /// Disassemble the functions with indices from FIRST to LAST, inclusive
+    #[structopt(
+        long,
+        number_of_values = 2,
+        value_names = &["FIRST", "LAST"],
+        conflicts_with = "pc"
+    )]
+    function_range: Vec<usize>,
 }
 
 impl<D: Debugger> Command<D> for DisassembleCommand {
@@ -25,22 +44,34 @@ impl<D: Debugger> Command<D> for DisassembleCommand {
     }
 
     fn description(&self) -> &'static str {
-        "Disassemble instructions in the current function."
+        "Disassemble instructions in the current function or the whole module."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
     }
 
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts: Opts = Opts::from_iter_safe(args)?;
+        if opts.all || !opts.function_range.is_empty() {
+            let range = match opts.function_range.as_slice() {
+                [first, last] => *first..=*last,
+                _ => 0..=usize::MAX,
+            };
+            display_module_asm(debugger, context.printer.as_ref(), range, opts.raw)?;
+            return Ok(None);
+        }
         let count = if opts.pc {
-            Some(opts.count.unwrap_or(4))
+            Some(opts.count.unwrap_or(debugger.get_opts().disassemble_count))
         } else {
             opts.count
         };
-        display_asm(debugger, context.printer.as_ref(), count, opts.pc)?;
+        display_asm(debugger, context.printer.as_ref(), count, opts.pc, opts.raw)?;
         Ok(None)
     }
 }
@@ -50,8 +81,14 @@ pub fn display_asm<D: Debugger>(
     printer: &dyn OutputPrinter,
     count: Option<usize>,
     pc_rel: bool,
+    raw: bool,
 ) -> Result<()> {
     let (insts, inst_index) = debugger.instructions()?;
+    let code = if raw {
+        Some(code_section(debugger)?)
+    } else {
+        None
+    };
     let begin = if pc_rel { inst_index } else { 0 };
     let end = if let Some(count) = count {
         begin + count
@@ -62,9 +99,92 @@ pub fn display_asm<D: Debugger>(
         if !(begin..end).contains(&index) {
             continue;
         }
-        let prefix = if index == inst_index { "->" } else { "  " };
-        let output = format!("{} 0x{:>08x}: {:?}", prefix, inst.offset, inst.kind);
-        printer.println(&output);
+        print_inst(printer, inst, code, index == inst_index);
+    }
+    Ok(())
+}
+
+/// Disassemble the functions of the main module whose indices are in
+/// `range`, each under a header with its name and type, like
+/// `wasm-objdump -d` does
+pub fn display_module_asm<D: Debugger>(
+    debugger: &D,
+    printer: &dyn OutputPrinter,
+    range: RangeInclusive<usize>,
+    raw: bool,
+) -> Result<()> {
+    let store = debugger.store()?;
+    let module_index = debugger.main_module_index()?;
+    let code = if raw {
+        Some(code_section(debugger)?)
+    } else {
+        None
+    };
+    // Only code offsets of the main module are unique, which is all this shows
+    let current = debugger
+        .instructions()
+        .ok()
+        .and_then(|(insts, index)| insts.get(index).map(|inst| inst.offset));
+    for (index, addr) in store.module_funcs(module_index).into_iter().enumerate() {
+        if !range.contains(&index) {
+            continue;
+        }
+        // Imports have no code in this module
+        let func = match store.func_global(addr).defined() {
+            Some(func) if func.module_index() == module_index => func,
+            _ => continue,
+        };
+        printer.println(&format!(
+            "{} (func[{}]): {}",
+            demangle_symbol(func.name()),
+            index,
+            format_func_type(func.ty())
+        ));
+        for inst in func.instructions() {
+            print_inst(printer, inst, code, current == Some(inst.offset));
+        }
     }
     Ok(())
 }
+
+/// Print `inst` as a line of disassembly, marked if it's the next one
+fn print_inst(printer: &dyn OutputPrinter, inst: &Instruction, code: Option<&[u8]>, next: bool) {
+    let marker = if next {
+        (Style::Marker, "->")
+    } else {
+        (Style::Plain, "  ")
+    };
+    let mut offset = format!(" 0x{:>08x}: ", inst.offset);
+    if let Some(code) = code {
+        let bytes = code
+            .get(inst.offset..inst.offset + inst.len)
+            .unwrap_or_default();
+        let hex: Vec<_> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        offset += &format!("{:<24}", hex.join(" "));
+    }
+    let kind = format!("{:?}", inst.kind);
+    let (opcode, immediates) = match kind.find(|c| c == ' ' || c == '(') {
+        Some(end) => kind.split_at(end),
+        None => (kind.as_str(), ""),
+    };
+    printer.println_styled(&[
+        marker,
+        (Style::Plain, &offset),
+        (Style::Opcode, opcode),
+        (Style::Immediate, immediates),
+    ]);
+}
+
+/// Contents of the main module's code section, which instruction offsets
+/// are relative to
+fn code_section<D: Debugger>(debugger: &D) -> Result<&[u8]> {
+    let (bytes, _) = debugger
+        .main_module_source()
+        .ok_or_else(|| anyhow!("No module loaded"))?;
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        if let wasmparser::Payload::CodeSectionStart { range, .. } = payload? {
+            return Ok(&bytes[range.start..range.end]);
+        }
+    }
+    Err(anyhow!("No code section"))
+}
@@ -0,0 +1,470 @@
+//! Evaluator for arithmetic expressions accepted by the `expression` command.
+//!
+//! ```text
+//! expr    := term (("+" | "-") term)*
+//! term    := unary (("*" | "/") unary)*
+//! unary   := "-" unary | "*" "(" type ")" unary | primary
+//! primary := literal | "global" "[" index "]" | "local" "[" index "]" | "(" expr ")"
+//! literal := integer ("i32" | "i64")? | decimal ("f32" | "f64")?
+//! type    := "i32" | "i64" | "f32" | "f64"
+//! ```
+//!
+//! Integer literals are `i32` and decimals with a fraction are `f64` unless suffixed.
+//! Both operands of a binary operator must have the same type; nothing is
+//! converted implicitly. `*(type)addr` loads a little-endian value of `type`
+//! from the linear memory at the `i32` address `addr`.
+
+use anyhow::{anyhow, Result};
+use std::convert::TryInto;
+use wasminspect_vm::{NumVal, WasmValue};
+
+/// Values an expression can refer to
+pub trait Environment {
+    fn global(&self, index: usize) -> Result<WasmValue>;
+    fn local(&self, index: usize) -> Result<WasmValue>;
+    fn load(&self, addr: usize, size: usize) -> Result<Vec<u8>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValueType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "i32" => Some(Self::I32),
+            "i64" => Some(Self::I64),
+            "f32" => Some(Self::F32),
+            "f64" => Some(Self::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I32 | Self::F32 => 4,
+            Self::I64 | Self::F64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Literal(WasmValue),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        let token = match c {
+            _ if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            _ if c.is_ascii_alphanumeric() || c == '_' || c == '.' => {
+                let start = pos;
+                while pos < chars.len()
+                    && (chars[pos].is_ascii_alphanumeric()
+                        || chars[pos] == '_'
+                        || chars[pos] == '.')
+                {
+                    pos += 1;
+                }
+                let word: String = chars[start..pos].iter().collect();
+                if c.is_ascii_digit() || c == '.' {
+                    tokens.push(Token::Literal(parse_literal(&word)?));
+                } else {
+                    tokens.push(Token::Ident(word));
+                }
+                continue;
+            }
+            _ => return Err(anyhow!("unexpected character '{}'", c)),
+        };
+        tokens.push(token);
+        pos += 1;
+    }
+    Ok(tokens)
+}
+
+fn parse_literal(word: &str) -> Result<WasmValue> {
+    let invalid = || anyhow!("invalid literal '{}'", word);
+    let text = word.replace('_', "");
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        // Hex digits include 'f', so float suffixes would be ambiguous here
+        let (digits, ty) = split_suffix(hex, &["i32", "i64"]);
+        let value = u64::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        return integer_literal(value, ty.unwrap_or(ValueType::I32)).ok_or_else(invalid);
+    }
+    let (digits, ty) = split_suffix(&text, &["i32", "i64", "f32", "f64"]);
+    let is_float = digits.contains(|c| c == '.' || c == 'e' || c == 'E');
+    match ty {
+        Some(ValueType::F32) => Ok(WasmValue::from(
+            digits.parse::<f32>().map_err(|_| invalid())?,
+        )),
+        Some(ValueType::F64) => Ok(WasmValue::from(
+            digits.parse::<f64>().map_err(|_| invalid())?,
+        )),
+        None if is_float => Ok(WasmValue::from(
+            digits.parse::<f64>().map_err(|_| invalid())?,
+        )),
+        ty => {
+            let value = digits.parse::<u64>().map_err(|_| invalid())?;
+            integer_literal(value, ty.unwrap_or(ValueType::I32)).ok_or_else(invalid)
+        }
+    }
+}
+
+fn split_suffix<'a>(text: &'a str, suffixes: &[&str]) -> (&'a str, Option<ValueType>) {
+    for suffix in suffixes {
+        if let Some(digits) = text.strip_suffix(suffix) {
+            if !digits.is_empty() {
+                return (digits, ValueType::parse(suffix));
+            }
+        }
+    }
+    (text, None)
+}
+
+/// Unsigned literals are accepted up to the full bit width, like wasm text format
+fn integer_literal(value: u64, ty: ValueType) -> Option<WasmValue> {
+    match ty {
+        ValueType::I32 => {
+            let value: u32 = value.try_into().ok()?;
+            Some(WasmValue::I32(value as i32))
+        }
+        ValueType::I64 => Some(WasmValue::I64(value as i64)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl std::fmt::Display for BinOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => write!(f, "+"),
+            Self::Sub => write!(f, "-"),
+            Self::Mul => write!(f, "*"),
+            Self::Div => write!(f, "/"),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    env: &'a dyn Environment,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(anyhow!("expected {:?} but found {:?}", expected, token)),
+            None => Err(anyhow!(
+                "expected {:?} but reached end of expression",
+                expected
+            )),
+        }
+    }
+
+    fn expr(&mut self) -> Result<WasmValue> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinOp::Add,
+                Some(Token::Minus) => BinOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = binary(op, lhs, rhs)?;
+        }
+    }
+
+    fn term(&mut self) -> Result<WasmValue> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => BinOp::Mul,
+                Some(Token::Slash) => BinOp::Div,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.unary()?;
+            lhs = binary(op, lhs, rhs)?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<WasmValue> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.pos += 1;
+                let value = self.unary()?;
+                negate(value)
+            }
+            Some(Token::Star) => {
+                self.pos += 1;
+                self.expect(Token::LParen)?;
+                let ty = match self.next() {
+                    Some(Token::Ident(name)) => {
+                        ValueType::parse(&name).ok_or_else(|| anyhow!("unknown type '{}'", name))?
+                    }
+                    token => return Err(anyhow!("expected type but found {:?}", token)),
+                };
+                self.expect(Token::RParen)?;
+                let addr = match self.unary()? {
+                    WasmValue::Num(NumVal::I32(addr)) => addr as u32 as usize,
+                    value => return Err(anyhow!("address must be i32 but got {:?}", value)),
+                };
+                self.load(ty, addr)
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<WasmValue> {
+        match self.next() {
+            Some(Token::Literal(value)) => Ok(value),
+            Some(Token::LParen) => {
+                let value = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) if name == "global" || name == "local" => {
+                self.expect(Token::LBracket)?;
+                let index = match self.next() {
+                    Some(Token::Literal(WasmValue::Num(NumVal::I32(index)))) if index >= 0 => {
+                        index as usize
+                    }
+                    token => return Err(anyhow!("expected index but found {:?}", token)),
+                };
+                self.expect(Token::RBracket)?;
+                if name == "global" {
+                    self.env.global(index)
+                } else {
+                    self.env.local(index)
+                }
+            }
+            Some(Token::Ident(name)) => Err(anyhow!("unknown identifier '{}'", name)),
+            Some(token) => Err(anyhow!("unexpected {:?}", token)),
+            None => Err(anyhow!("unexpected end of expression")),
+        }
+    }
+
+    fn load(&self, ty: ValueType, addr: usize) -> Result<WasmValue> {
+        let bytes = self.env.load(addr, ty.size())?;
+        let value = match ty {
+            ValueType::I32 => WasmValue::I32(i32::from_le_bytes(bytes[..].try_into()?)),
+            ValueType::I64 => WasmValue::I64(i64::from_le_bytes(bytes[..].try_into()?)),
+            ValueType::F32 => WasmValue::F32(u32::from_le_bytes(bytes[..].try_into()?)),
+            ValueType::F64 => WasmValue::F64(u64::from_le_bytes(bytes[..].try_into()?)),
+        };
+        Ok(value)
+    }
+}
+
+fn type_name(value: &WasmValue) -> &'static str {
+    match value {
+        WasmValue::Num(NumVal::I32(_)) => "i32",
+        WasmValue::Num(NumVal::I64(_)) => "i64",
+        WasmValue::Num(NumVal::F32(_)) => "f32",
+        WasmValue::Num(NumVal::F64(_)) => "f64",
+        WasmValue::Ref(_) => "ref",
+    }
+}
+
+macro_rules! int_op {
+    ($op:expr, $lhs:expr, $rhs:expr) => {
+        match $op {
+            BinOp::Add => Ok($lhs.wrapping_add($rhs)),
+            BinOp::Sub => Ok($lhs.wrapping_sub($rhs)),
+            BinOp::Mul => Ok($lhs.wrapping_mul($rhs)),
+            BinOp::Div if $rhs == 0 => Err(anyhow!("integer divide by zero")),
+            BinOp::Div => $lhs
+                .checked_div($rhs)
+                .ok_or_else(|| anyhow!("integer overflow")),
+        }
+    };
+}
+
+macro_rules! float_op {
+    ($op:expr, $lhs:expr, $rhs:expr) => {
+        match $op {
+            BinOp::Add => $lhs + $rhs,
+            BinOp::Sub => $lhs - $rhs,
+            BinOp::Mul => $lhs * $rhs,
+            BinOp::Div => $lhs / $rhs,
+        }
+    };
+}
+
+fn binary(op: BinOp, lhs: WasmValue, rhs: WasmValue) -> Result<WasmValue> {
+    use NumVal::*;
+    match (lhs, rhs) {
+        (WasmValue::Num(I32(l)), WasmValue::Num(I32(r))) => Ok(WasmValue::I32(int_op!(op, l, r)?)),
+        (WasmValue::Num(I64(l)), WasmValue::Num(I64(r))) => Ok(WasmValue::I64(int_op!(op, l, r)?)),
+        (WasmValue::Num(F32(l)), WasmValue::Num(F32(r))) => {
+            Ok(WasmValue::from(float_op!(op, l.to_float(), r.to_float())))
+        }
+        (WasmValue::Num(F64(l)), WasmValue::Num(F64(r))) => {
+            Ok(WasmValue::from(float_op!(op, l.to_float(), r.to_float())))
+        }
+        _ => Err(anyhow!(
+            "type mismatch: {} {} {}",
+            type_name(&lhs),
+            op,
+            type_name(&rhs)
+        )),
+    }
+}
+
+fn negate(value: WasmValue) -> Result<WasmValue> {
+    use NumVal::*;
+    match value {
+        WasmValue::Num(I32(v)) => Ok(WasmValue::I32(v.wrapping_neg())),
+        WasmValue::Num(I64(v)) => Ok(WasmValue::I64(v.wrapping_neg())),
+        WasmValue::Num(F32(v)) => Ok(WasmValue::from(-v.to_float())),
+        WasmValue::Num(F64(v)) => Ok(WasmValue::from(-v.to_float())),
+        WasmValue::Ref(_) => Err(anyhow!("can't negate a reference value")),
+    }
+}
+
+pub fn evaluate(source: &str, env: &dyn Environment) -> Result<WasmValue> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        env,
+    };
+    let value = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(anyhow!("unexpected {:?} after expression", token));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestEnvironment {
+        globals: Vec<WasmValue>,
+        locals: Vec<WasmValue>,
+        memory: Vec<u8>,
+    }
+
+    impl Environment for TestEnvironment {
+        fn global(&self, index: usize) -> Result<WasmValue> {
+            self.globals
+                .get(index)
+                .cloned()
+                .ok_or_else(|| anyhow!("no global {}", index))
+        }
+        fn local(&self, index: usize) -> Result<WasmValue> {
+            self.locals
+                .get(index)
+                .cloned()
+                .ok_or_else(|| anyhow!("no local {}", index))
+        }
+        fn load(&self, addr: usize, size: usize) -> Result<Vec<u8>> {
+            self.memory
+                .get(addr..addr + size)
+                .map(|bytes| bytes.to_vec())
+                .ok_or_else(|| anyhow!("out of bounds"))
+        }
+    }
+
+    fn env() -> TestEnvironment {
+        let mut memory = vec![0; 32];
+        memory[16..20].copy_from_slice(&42i32.to_le_bytes());
+        memory[24..32].copy_from_slice(&1.5f64.to_le_bytes());
+        TestEnvironment {
+            globals: vec![WasmValue::I32(16), WasmValue::from(2.0f32)],
+            locals: vec![WasmValue::I64(10)],
+            memory,
+        }
+    }
+
+    fn eval(source: &str) -> Result<WasmValue> {
+        evaluate(source, &env())
+    }
+
+    #[test]
+    fn arithmetic_precedence() {
+        assert_eq!(eval("1 + 2 * 3").unwrap(), WasmValue::I32(7));
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), WasmValue::I32(9));
+        assert_eq!(eval("10 - 4 - 3").unwrap(), WasmValue::I32(3));
+        assert_eq!(eval("-7 / 2").unwrap(), WasmValue::I32(-3));
+        assert_eq!(eval("0xffffffff").unwrap(), WasmValue::I32(-1));
+        assert_eq!(eval("local[0] * 3i64").unwrap(), WasmValue::I64(30));
+        assert_eq!(eval("0.5 + 1.5").unwrap(), WasmValue::from(2.0f64));
+        assert_eq!(eval("global[1] * 1.5f32").unwrap(), WasmValue::from(3.0f32));
+    }
+
+    #[test]
+    fn memory_dereference() {
+        assert_eq!(eval("*(i32)0x10").unwrap(), WasmValue::I32(42));
+        assert_eq!(eval("*(i32)global[0] + 1").unwrap(), WasmValue::I32(43));
+        assert_eq!(
+            eval("*(f64)(global[0] + 8)").unwrap(),
+            WasmValue::from(1.5f64)
+        );
+        assert!(eval("*(i32)30").is_err());
+        assert!(eval("*(i32)local[0]").is_err());
+    }
+
+    #[test]
+    fn type_errors_are_reported() {
+        assert_eq!(
+            eval("global[1] + 1").unwrap_err().to_string(),
+            "type mismatch: f32 + i32"
+        );
+        assert_eq!(
+            eval("1 / 0").unwrap_err().to_string(),
+            "integer divide by zero"
+        );
+        assert!(eval("0x100000000").is_err());
+        assert!(eval("1 +").is_err());
+        assert!(eval("foo").is_err());
+    }
+}
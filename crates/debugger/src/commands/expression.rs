@@ -1,6 +1,8 @@
-use crate::dwarf::{FrameBase, WasmLoc};
 use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
 use super::debugger::Debugger;
+use super::evaluator::{evaluate, Environment};
+use crate::dwarf::{FrameBase, WasmLoc};
 use anyhow::{anyhow, Context, Result};
 
 pub struct ExpressionCommand {}
@@ -14,8 +16,62 @@ impl ExpressionCommand {
 use structopt::StructOpt;
 #[derive(StructOpt)]
 struct Opts {
-    #[structopt(name = "SYMBOL")]
-    symbol: String,
+    /// A variable name, or an arithmetic expression over globals, locals, literals and memory
+    #[structopt(name = "EXPR", required = true)]
+    expr: Vec<String>,
+}
+
+struct DebuggerEnvironment<'a, D: Debugger> {
+    debugger: &'a D,
+}
+
+impl<'a, D: Debugger> Environment for DebuggerEnvironment<'a, D> {
+    fn global(&self, index: usize) -> Result<wasminspect_vm::WasmValue> {
+        use wasminspect_vm::*;
+        let store: &Store = self.debugger.store()?;
+        let mod_index = match self.debugger.current_frame() {
+            Some(frame) => frame.module_index,
+            None => return Err(anyhow!("function frame not found")),
+        };
+        let global = store
+            .global(GlobalAddr::new_unsafe(mod_index, index))
+            .ok_or_else(|| anyhow!("global {} not found", index))?;
+        let value = global.borrow().value();
+        Ok(value)
+    }
+
+    fn local(&self, index: usize) -> Result<wasminspect_vm::WasmValue> {
+        self.debugger
+            .locals()
+            .get(index)
+            .cloned()
+            .with_context(|| format!("local {} not found", index))
+    }
+
+    fn load(&self, addr: usize, size: usize) -> Result<Vec<u8>> {
+        let memory = self.debugger.memory()?;
+        memory
+            .get(addr..addr + size)
+            .map(|bytes| bytes.to_vec())
+            .with_context(|| {
+                format!(
+                    "memory access out of bounds: 0x{:x} + {} > 0x{:x}",
+                    addr,
+                    size,
+                    memory.len()
+                )
+            })
+    }
+}
+
+fn is_variable_name(expr: &str) -> bool {
+    let mut chars = expr.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
 }
 
 impl<D: Debugger> Command<D> for ExpressionCommand {
@@ -24,16 +80,26 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
     }
 
     fn description(&self) -> &'static str {
-        "Evaluate an expression on the process (only support variable name now)."
+        "Evaluate an expression on the process."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
     }
 
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
+        let expr = opts.expr.join(" ");
+        if !is_variable_name(&expr) {
+            let value = evaluate(&expr, &DebuggerEnvironment { debugger })?;
+            context.printer.println(&format!("{:?}", value));
+            return Ok(None);
+        }
         let (insts, next_index) = debugger.instructions()?;
         let current_index = if next_index == 0 { 0 } else { next_index - 1 };
         let current_inst = insts[current_index].clone();
@@ -49,6 +115,7 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
                 let offset = match loc {
                     WasmLoc::Global(idx) => store
                         .global(GlobalAddr::new_unsafe(mod_index, idx as usize))
+                        .with_context(|| "failed to get base global".to_string())?
                         .borrow()
                         .value(),
                     WasmLoc::Local(idx) => *locals
@@ -86,7 +153,7 @@ impl<D: Debugger> Command<D> for ExpressionCommand {
             current_inst.offset,
             frame_base,
             &debugger.memory()?,
-            opts.symbol,
+            expr,
         )?;
         Ok(None)
     }
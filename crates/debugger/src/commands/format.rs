@@ -0,0 +1,155 @@
+use anyhow::{anyhow, Result};
+use wasminspect_vm::{NumVal, RefType, RefVal, WasmValue};
+
+/// How `format_value_as` renders numbers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Radix {
+    /// Signed decimal for integers, the value itself for floats
+    Dec,
+    /// The bits in hex, zero padded to the width of the type
+    Hex,
+    /// The bits in binary, zero padded to the width of the type
+    Bin,
+    /// The bits read as a float of the same width
+    Float,
+    /// The little-endian bytes as ASCII, `.` for unprintable ones
+    Char,
+}
+
+impl Radix {
+    /// Names accepted by `FromStr`
+    pub const NAMES: &'static [&'static str] = &["dec", "hex", "bin", "float", "char"];
+}
+
+impl std::str::FromStr for Radix {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dec" => Ok(Self::Dec),
+            "hex" => Ok(Self::Hex),
+            "bin" => Ok(Self::Bin),
+            "float" => Ok(Self::Float),
+            "char" => Ok(Self::Char),
+            _ => Err(anyhow!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/// `value` rendered in `radix`. References have no bits to show, so they
+/// are rendered as they are.
+pub fn format_value_as(value: &WasmValue, radix: Radix) -> String {
+    let (bits, width) = match value {
+        WasmValue::Num(NumVal::I32(v)) => (*v as u32 as u64, 32),
+        WasmValue::Num(NumVal::I64(v)) => (*v as u64, 64),
+        WasmValue::Num(NumVal::F32(v)) => (v.to_bits() as u64, 32),
+        WasmValue::Num(NumVal::F64(v)) => (v.to_bits(), 64),
+        WasmValue::Ref(_) => return format!("{:?}", value),
+    };
+    match (radix, value) {
+        (Radix::Dec, WasmValue::Num(NumVal::F32(v))) => v.to_float().to_string(),
+        (Radix::Dec, WasmValue::Num(NumVal::F64(v))) => v.to_float().to_string(),
+        (Radix::Dec, _) if width == 32 => (bits as u32 as i32).to_string(),
+        (Radix::Dec, _) => (bits as i64).to_string(),
+        (Radix::Hex, _) => format!("0x{:0width$x}", bits, width = width / 4),
+        (Radix::Bin, _) => format!("0b{:0width$b}", bits, width = width),
+        (Radix::Float, _) if width == 32 => f32::from_bits(bits as u32).to_string(),
+        (Radix::Float, _) => f64::from_bits(bits).to_string(),
+        (Radix::Char, _) => bytes_as_chars(&bits.to_le_bytes()[..width / 8]),
+    }
+}
+
+/// The type of `value` and the value itself, with numbers rendered in
+/// `radix`. This is how values are shown wherever their type goes along.
+pub fn typed_value(value: &WasmValue, radix: Radix) -> (&'static str, String) {
+    let ty = match value {
+        WasmValue::Num(NumVal::I32(_)) => "i32",
+        WasmValue::Num(NumVal::I64(_)) => "i64",
+        WasmValue::Num(NumVal::F32(_)) => "f32",
+        WasmValue::Num(NumVal::F64(_)) => "f64",
+        WasmValue::Ref(RefVal::NullRef(RefType::FuncRef)) | WasmValue::Ref(RefVal::FuncRef(_)) => {
+            "funcref"
+        }
+        WasmValue::Ref(RefVal::NullRef(RefType::ExternRef))
+        | WasmValue::Ref(RefVal::ExternRef(_)) => "externref",
+    };
+    let text = match value {
+        WasmValue::Num(_) => format_value_as(value, radix),
+        WasmValue::Ref(RefVal::NullRef(_)) => "null".to_string(),
+        WasmValue::Ref(RefVal::FuncRef(addr)) => format!("{:?}", addr),
+        WasmValue::Ref(RefVal::ExternRef(v)) => v.to_string(),
+    };
+    (ty, text)
+}
+
+/// `value` in `radix`, or as debug output without one
+pub fn show_value(value: &WasmValue, radix: Option<Radix>) -> String {
+    match radix {
+        Some(radix) => format_value_as(value, radix),
+        None => format!("{:?}", value),
+    }
+}
+
+/// Printable ASCII bytes as they are and others as `.`, like hex dumps do
+pub fn bytes_as_chars(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if (0x20..0x7f).contains(&b) {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_integers_in_each_radix() {
+        let value = WasmValue::I32(0xdeadbeef_u32 as i32);
+        assert_eq!(format_value_as(&value, Radix::Dec), "-559038737");
+        assert_eq!(format_value_as(&value, Radix::Hex), "0xdeadbeef");
+        assert_eq!(
+            format_value_as(&WasmValue::I64(255), Radix::Hex),
+            "0x00000000000000ff"
+        );
+        assert_eq!(
+            format_value_as(&WasmValue::I32(5), Radix::Bin),
+            format!("0b{}101", "0".repeat(29))
+        );
+        assert_eq!(
+            format_value_as(&WasmValue::I32(0x4c4c_4548), Radix::Char),
+            "HELL"
+        );
+        assert_eq!(
+            format_value_as(&WasmValue::I32(1.5f32.to_bits() as i32), Radix::Float),
+            "1.5"
+        );
+    }
+
+    #[test]
+    fn formats_typed_values() {
+        let value = WasmValue::F32(1.5f32.to_bits());
+        assert_eq!(typed_value(&value, Radix::Dec), ("f32", "1.5".to_string()));
+        assert_eq!(
+            typed_value(&value, Radix::Hex),
+            ("f32", "0x3fc00000".to_string())
+        );
+        let value = WasmValue::Ref(RefVal::NullRef(RefType::ExternRef));
+        assert_eq!(
+            typed_value(&value, Radix::Hex),
+            ("externref", "null".to_string())
+        );
+    }
+
+    #[test]
+    fn formats_float_bits() {
+        let value = WasmValue::F64(2.0f64.to_bits());
+        assert_eq!(format_value_as(&value, Radix::Dec), "2");
+        assert_eq!(format_value_as(&value, Radix::Hex), "0x4000000000000000");
+        assert_eq!(format_value_as(&value, Radix::Char), ".......@");
+    }
+}
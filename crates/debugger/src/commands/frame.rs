@@ -1,5 +1,7 @@
 use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
 use super::debugger::Debugger;
+use crate::trace::format_value;
 use anyhow::Result;
 
 use structopt::StructOpt;
@@ -27,10 +29,14 @@ impl<D: Debugger> Command<D> for FrameCommand {
         "Commands for selecting current stack frame."
     }
 
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
@@ -40,8 +46,27 @@ impl<D: Debugger> Command<D> for FrameCommand {
                 let current_index = if next_index == 0 { 0 } else { next_index - 1 };
                 let current_inst = insts[current_index].clone();
                 let variable_names = context.subroutine.variable_name_list(current_inst.offset)?;
-                for variable in variable_names {
-                    let output = format!("{}: {}", variable.name, variable.type_name);
+                if !variable_names.is_empty() {
+                    for variable in variable_names {
+                        let output = format!("{}: {}", variable.name, variable.type_name);
+                        context.printer.println(&output);
+                    }
+                    return Ok(None);
+                }
+                // Without DWARF, the name section may still name the locals
+                let func_index = match debugger.raw_frames().last() {
+                    Some(frame) => frame.func_index as u32,
+                    None => return Ok(None),
+                };
+                if !context.names.has_local_names(func_index) {
+                    return Ok(None);
+                }
+                for (index, value) in debugger.locals().iter().enumerate() {
+                    let output = format!(
+                        "{}: {}",
+                        context.names.local_name(func_index, index as u32),
+                        format_value(value)
+                    );
                     context.printer.println(&output);
                 }
                 Ok(None)
@@ -1,6 +1,10 @@
 use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
 use super::debugger::Debugger;
+use super::format::{show_value, Radix};
+use crate::trace::format_value;
 use anyhow::{anyhow, Result};
+use serde_json::json;
 
 use structopt::StructOpt;
 
@@ -18,6 +22,9 @@ enum Opts {
     Read {
         #[structopt(name = "INDEX")]
         index: usize,
+        /// Show numbers in another radix
+        #[structopt(short, long, possible_values = Radix::NAMES)]
+        format: Option<Radix>,
     },
 }
 
@@ -30,24 +37,32 @@ impl<D: Debugger> Command<D> for GlobalCommand {
         "Commands for operating globals."
     }
 
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         use wasminspect_vm::*;
         match opts {
-            Opts::Read { index } => {
+            Opts::Read { index, format } => {
                 let store: &Store = debugger.store()?;
                 let mod_index = match debugger.current_frame() {
                     Some(frame) => frame.module_index,
                     None => return Err(anyhow!("function frame not found")),
                 };
-                let global = store.global(GlobalAddr::new_unsafe(mod_index, index));
-                let output = format!("{:?}", global.borrow().value());
-                context.printer.println(&output);
+                let global = store
+                    .global(GlobalAddr::new_unsafe(mod_index, index))
+                    .ok_or_else(|| anyhow!("global {} not found", index))?;
+                let value = global.borrow().value();
+                let record = json!({ "index": index, "value": format_value(&value) });
+                let text = show_value(&value, format);
+                context.printer.print_record(&text, &record);
                 Ok(None)
             }
         }
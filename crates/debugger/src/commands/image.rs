@@ -0,0 +1,348 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
+use super::debugger::Debugger;
+use super::symbol::demangle_symbol;
+use anyhow::{anyhow, Result};
+use wasmparser::{
+    ExternalKind, FuncType, GlobalType, ImportSectionEntryType, MemoryType, Payload, TableType,
+    Type, TypeDef,
+};
+
+use structopt::StructOpt;
+
+pub struct ImageCommand {}
+
+impl ImageCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// List imports and exports of the main module
+    #[structopt(name = "dump")]
+    Dump,
+    /// Find the function containing a code offset, or the code offsets of a function
+    #[structopt(name = "lookup")]
+    Lookup {
+        /// Code offset to look up, in hex with a 0x prefix or decimal
+        #[structopt(short, long)]
+        address: Option<String>,
+        /// Function name to look up
+        #[structopt(short, long)]
+        name: Option<String>,
+    },
+}
+
+impl<D: Debugger> Command<D> for ImageCommand {
+    fn name(&self) -> &'static str {
+        "image"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for inspecting the loaded module."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Dump => {
+                let (bytes, _) = debugger
+                    .main_module_source()
+                    .ok_or_else(|| anyhow!("No module loaded"))?;
+                let interface = ModuleInterface::parse(bytes)?;
+                context.printer.println("Imports:");
+                for import in &interface.imports {
+                    context.printer.println(&format!("  {}", import));
+                }
+                context.printer.println("Exports:");
+                for export in &interface.exports {
+                    context.printer.println(&format!("  {}", export));
+                }
+            }
+            Opts::Lookup {
+                address: Some(address),
+                name: None,
+            } => {
+                let address = if address.starts_with("0x") {
+                    let raw = address.trim_start_matches("0x");
+                    usize::from_str_radix(raw, 16)?
+                } else {
+                    address.parse::<usize>()?
+                };
+                let symbol = function_symbols(debugger)?
+                    .into_iter()
+                    .find(|symbol| symbol.range.contains(&address))
+                    .ok_or_else(|| anyhow!("No function contains 0x{:x}", address))?;
+                let mut output = format!(
+                    "0x{:x}: {} (func[{}]) + 0x{:x}",
+                    address,
+                    demangle_symbol(&symbol.name),
+                    symbol.index,
+                    address - symbol.range.start
+                );
+                if let Some(line_info) = context.sourcemap.find_line_info(address) {
+                    output += &format!(" at {}", line_info);
+                }
+                context.printer.println(&output);
+            }
+            Opts::Lookup {
+                address: None,
+                name: Some(name),
+            } => {
+                let symbols: Vec<_> = function_symbols(debugger)?
+                    .into_iter()
+                    .filter(|symbol| symbol.name == name || demangle_symbol(&symbol.name) == name)
+                    .collect();
+                if symbols.is_empty() {
+                    return Err(anyhow!("No function named '{}'", name));
+                }
+                for symbol in symbols {
+                    let mut output = format!(
+                        "{} (func[{}]): 0x{:x}..0x{:x}",
+                        demangle_symbol(&symbol.name),
+                        symbol.index,
+                        symbol.range.start,
+                        symbol.range.end
+                    );
+                    if let Some(line_info) = context.sourcemap.find_line_info(symbol.range.start) {
+                        output += &format!(" at {}", line_info);
+                    }
+                    context.printer.println(&output);
+                }
+            }
+            Opts::Lookup { .. } => {
+                return Err(anyhow!("Specify exactly one of --address or --name"));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A function defined in the main module and the code it spans
+struct FunctionSymbol {
+    /// Index in the module's function index space, imports included
+    index: usize,
+    name: String,
+    /// From the first instruction up to and excluding the byte after `end`
+    range: std::ops::Range<usize>,
+}
+
+fn function_symbols<D: Debugger>(debugger: &D) -> Result<Vec<FunctionSymbol>> {
+    let store = debugger.store()?;
+    let module_index = debugger.main_module_index()?;
+    let mut symbols = Vec::new();
+    for (index, addr) in store.module_funcs(module_index).into_iter().enumerate() {
+        // Imports have no code in this module, even those defined by another one
+        let func = match store.func_global(addr).defined() {
+            Some(func) if func.module_index() == module_index => func,
+            _ => continue,
+        };
+        let insts = func.instructions();
+        if let (Some(first), Some(last)) = (insts.first(), insts.last()) {
+            symbols.push(FunctionSymbol {
+                index,
+                name: func.name().clone(),
+                range: first.offset..last.offset + 1,
+            });
+        }
+    }
+    Ok(symbols)
+}
+
+/// Imports and exports of a module, each rendered as a line of `image dump`
+struct ModuleInterface {
+    imports: Vec<String>,
+    exports: Vec<String>,
+}
+
+impl ModuleInterface {
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut types = Vec::new();
+        // Index spaces start with the imported entities, followed by defined ones
+        let mut funcs = Vec::new();
+        let mut globals = Vec::new();
+        let mut memories = Vec::new();
+        let mut tables = Vec::new();
+        let mut imports = Vec::new();
+        let mut exports = Vec::new();
+
+        for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+            match payload? {
+                Payload::TypeSection(section) => {
+                    for entry in section {
+                        match entry? {
+                            TypeDef::Func(ty) => types.push(Some(ty)),
+                            _ => types.push(None),
+                        }
+                    }
+                }
+                Payload::ImportSection(section) => {
+                    for entry in section {
+                        let entry = entry?;
+                        let name = format!("{}.{}", entry.module, entry.field.unwrap_or(""));
+                        let description = match entry.ty {
+                            ImportSectionEntryType::Function(index) => {
+                                funcs.push(index);
+                                format!("func {}: {}", name, signature(&types, index))
+                            }
+                            ImportSectionEntryType::Global(ty) => {
+                                globals.push(ty);
+                                format!("global {}: {}", name, global_type(&ty))
+                            }
+                            ImportSectionEntryType::Memory(ty) => {
+                                memories.push(ty);
+                                format!("memory {}: {}", name, memory_type(&ty))
+                            }
+                            ImportSectionEntryType::Table(ty) => {
+                                tables.push(ty);
+                                format!("table {}: {}", name, table_type(&ty))
+                            }
+                            other => format!("{:?} {}", other, name),
+                        };
+                        imports.push(description);
+                    }
+                }
+                Payload::FunctionSection(section) => {
+                    for entry in section {
+                        funcs.push(entry?);
+                    }
+                }
+                Payload::GlobalSection(section) => {
+                    for entry in section {
+                        globals.push(entry?.ty);
+                    }
+                }
+                Payload::MemorySection(section) => {
+                    for entry in section {
+                        memories.push(entry?);
+                    }
+                }
+                Payload::TableSection(section) => {
+                    for entry in section {
+                        tables.push(entry?);
+                    }
+                }
+                Payload::ExportSection(section) => {
+                    for entry in section {
+                        let entry = entry?;
+                        let index = entry.index as usize;
+                        let name = entry.field;
+                        let description = match entry.kind {
+                            ExternalKind::Function => match funcs.get(index) {
+                                Some(ty) => format!("func {}: {}", name, signature(&types, *ty)),
+                                None => format!("func {}", name),
+                            },
+                            ExternalKind::Global => match globals.get(index) {
+                                Some(ty) => format!("global {}: {}", name, global_type(ty)),
+                                None => format!("global {}", name),
+                            },
+                            ExternalKind::Memory => match memories.get(index) {
+                                Some(ty) => format!("memory {}: {}", name, memory_type(ty)),
+                                None => format!("memory {}", name),
+                            },
+                            ExternalKind::Table => match tables.get(index) {
+                                Some(ty) => format!("table {}: {}", name, table_type(ty)),
+                                None => format!("table {}", name),
+                            },
+                            other => format!("{:?} {}", other, name),
+                        };
+                        exports.push(description);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self { imports, exports })
+    }
+}
+
+fn value_type(ty: Type) -> String {
+    // `I32` -> `i32`, `FuncRef` -> `funcref` and so on
+    format!("{:?}", ty).to_lowercase()
+}
+
+fn signature(types: &[Option<FuncType>], index: u32) -> String {
+    match types.get(index as usize) {
+        Some(Some(ty)) => format_func_type(ty),
+        _ => format!("<invalid type {}>", index),
+    }
+}
+
+/// Renders a function type like `(i32, i32) -> i32`
+pub fn format_func_type(ty: &FuncType) -> String {
+    let params: Vec<_> = ty.params.iter().map(|ty| value_type(*ty)).collect();
+    let returns: Vec<_> = ty.returns.iter().map(|ty| value_type(*ty)).collect();
+    let returns = if returns.len() == 1 {
+        returns[0].clone()
+    } else {
+        format!("({})", returns.join(", "))
+    };
+    format!("({}) -> {}", params.join(", "), returns)
+}
+
+fn global_type(ty: &GlobalType) -> String {
+    if ty.mutable {
+        format!("mut {}", value_type(ty.content_type))
+    } else {
+        value_type(ty.content_type)
+    }
+}
+
+fn limits(initial: u64, maximum: Option<u64>) -> String {
+    match maximum {
+        Some(maximum) => format!("{}..{}", initial, maximum),
+        None => format!("{}..", initial),
+    }
+}
+
+fn memory_type(ty: &MemoryType) -> String {
+    format!("{} pages", limits(ty.initial, ty.maximum))
+}
+
+fn table_type(ty: &TableType) -> String {
+    format!(
+        "{} {}",
+        value_type(ty.element_type),
+        limits(ty.initial as u64, ty.maximum.map(u64::from))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_module_interface() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            // (type (func (param i32 i32) (result i32)))
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f,
+            // (import "env" "f" (func (type 0)))
+            0x02, 0x09, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00,
+            // (memory 1 2)
+            0x05, 0x04, 0x01, 0x01, 0x01, 0x02,
+            // (export "add" (func 1)) (export "mem" (memory 0))
+            0x07, 0x0d, 0x02, 0x03, 0x61, 0x64, 0x64, 0x00, 0x01, 0x03, 0x6d, 0x65, 0x6d, 0x02, 0x00,
+            0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b,
+        ];
+        let interface = ModuleInterface::parse(&bytes).unwrap();
+        assert_eq!(interface.imports, vec!["func env.f: (i32, i32) -> i32"]);
+        assert_eq!(
+            interface.exports,
+            vec!["func add: (i32, i32) -> i32", "memory mem: 1..2 pages"]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::{Debugger, OutputPrinter};
+use super::debugger::{Debugger, OutputPrinter, Style};
 use super::sourcemap::{ColumnType, LineInfo, SourceMap};
 use anyhow::{anyhow, Result};
 
@@ -23,7 +23,7 @@ impl<D: Debugger> Command<D> for ListCommand {
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         _args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
@@ -34,7 +34,10 @@ impl<D: Debugger> Command<D> for ListCommand {
 
 pub fn next_line_info<D: Debugger>(debugger: &D, sourcemap: &dyn SourceMap) -> Result<LineInfo> {
     let (insts, next_index) = debugger.instructions()?;
-    match sourcemap.find_line_info(insts[next_index].offset) {
+    let inst = insts
+        .get(next_index)
+        .ok_or_else(|| anyhow!("No instruction left to run"))?;
+    match sourcemap.find_line_info(inst.offset) {
         Some(info) => Ok(info),
         None => Err(anyhow!("Source info not found")),
     }
@@ -64,13 +67,13 @@ pub fn display_source(line_info: LineInfo, printer: &dyn OutputPrinter) -> Resul
         if !(should_display.unwrap_or(true)) {
             continue;
         }
-        let out = if Some(index as u64) == line_info.line {
-            let mut out = format!("-> {: <4} ", index);
+        if Some(index as u64) == line_info.line {
+            let mut out = format!(" {: <4} ", index);
             match line_info.column {
                 ColumnType::Column(col) => {
                     for (col_index, col_char) in line.chars().enumerate() {
                         if (col_index + 1) as u64 == col {
-                            out = format!("{}\x1B[4m{}\x1B[0m", out, col_char);
+                            out = format!("{}\x1B[4m{}\x1B[24m", out, col_char);
                         } else {
                             out = format!("{}{}", out, col_char);
                         }
@@ -80,11 +83,11 @@ pub fn display_source(line_info: LineInfo, printer: &dyn OutputPrinter) -> Resul
                     out = format!("{}{}", out, line);
                 }
             }
-            out
+            printer.println_styled(&[(Style::Marker, "->"), (Style::CurrentLine, &out)]);
         } else {
-            format!("   {: <4} {}", index, line)
-        };
-        printer.println(&out);
+            let out = format!("   {: <4} {}", index, line);
+            printer.println_styled(&[(Style::ContextLine, &out)]);
+        }
     }
     Ok(())
 }
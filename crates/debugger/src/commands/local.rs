@@ -1,6 +1,10 @@
 use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
 use super::debugger::Debugger;
+use super::format::{show_value, Radix};
+use crate::trace::format_value;
 use anyhow::Result;
+use serde_json::json;
 
 use structopt::StructOpt;
 
@@ -18,6 +22,9 @@ enum Opts {
     Read {
         #[structopt(name = "INDEX")]
         index: Option<usize>,
+        /// Show numbers in another radix
+        #[structopt(short, long, possible_values = Radix::NAMES)]
+        format: Option<Radix>,
     },
 }
 
@@ -30,23 +37,48 @@ impl<D: Debugger> Command<D> for LocalCommand {
         "Commands for operating locals."
     }
 
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
-            Opts::Read { index: None } => {
-                for (index, value) in debugger.locals().iter().enumerate() {
-                    let output = format!("{: <3}: {:?}", index, value);
-                    context.printer.println(&output);
-                }
+            Opts::Read {
+                index: None,
+                format,
+            } => {
+                let locals = debugger.locals();
+                let text = locals
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| format!("{: <3}: {}", index, show_value(value, format)))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let record = json!({
+                    "locals": locals
+                        .iter()
+                        .enumerate()
+                        .map(|(index, value)| {
+                            json!({ "index": index, "value": format_value(value) })
+                        })
+                        .collect::<Vec<_>>(),
+                });
+                context.printer.print_record(&text, &record);
             }
-            Opts::Read { index: Some(index) } => {
-                let output = format!("{:?}", debugger.locals()[index]);
-                context.printer.println(&output);
+            Opts::Read {
+                index: Some(index),
+                format,
+            } => {
+                let value = debugger.locals()[index];
+                let record = json!({ "index": index, "value": format_value(&value) });
+                let text = show_value(&value, format);
+                context.printer.print_record(&text, &record);
             }
         }
         Ok(None)
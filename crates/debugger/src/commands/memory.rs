@@ -1,6 +1,11 @@
 use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
 use super::debugger::Debugger;
+use super::format::bytes_as_chars;
+use crate::dwarf::WasmLoc;
 use anyhow::{anyhow, Result};
+use serde_json::json;
+use wasminspect_vm::{GlobalAddr, HostMemory, MemoryAddr, NumVal, WasmValue, WASM_PAGE_SIZE};
 
 use structopt::StructOpt;
 
@@ -21,8 +26,37 @@ enum Opts {
         #[structopt(short, long, default_value = "32")]
         count: u32,
     },
+    /// Print the addresses where PATTERN occurs in the main module's memory
+    #[structopt(name = "find")]
+    Find {
+        /// Bytes in hex with a 0x prefix, like 0xcafebabe, or else a string
+        #[structopt(name = "PATTERN")]
+        pattern: String,
+        /// Stop after this many matches
+        #[structopt(short, long)]
+        limit: Option<usize>,
+    },
     #[structopt(name = "enable-watch")]
     EnableWatch,
+    /// Show data segments, the stack and the heap of the main module's memory
+    #[structopt(name = "map")]
+    Map,
+    /// Grow the main module's memory by PAGES pages, like `memory.grow`
+    #[structopt(name = "grow")]
+    Grow {
+        #[structopt(name = "PAGES")]
+        pages: u32,
+    },
+}
+
+/// Globals exported by wasm-ld which describe the memory layout
+const LAYOUT_SYMBOLS: [&str; 3] = ["__global_base", "__data_end", "__heap_base"];
+
+/// An address range, or a single address if `end` is `None`
+struct Region {
+    start: usize,
+    end: Option<usize>,
+    label: String,
 }
 
 impl<D: Debugger> Command<D> for MemoryCommand {
@@ -33,10 +67,14 @@ impl<D: Debugger> Command<D> for MemoryCommand {
     fn description(&self) -> &'static str {
         "Commands for operating on memory."
     }
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
@@ -60,19 +98,47 @@ impl<D: Debugger> Command<D> for MemoryCommand {
                         memory.len()
                     ));
                 }
+                let mut lines = Vec::new();
                 for (offset, bytes) in memory[begin..end].chunks(chunk_size).enumerate() {
                     let bytes_str = bytes
                         .iter()
                         .map(|b| format!("{:>02x}", b))
                         .collect::<Vec<String>>();
-                    let output = format!(
+                    lines.push(format!(
                         "0x{:>08x}: {} {}",
                         begin + offset * chunk_size,
                         bytes_str.join(" "),
-                        dump_memory_as_str(bytes)
-                    );
-                    context.printer.println(&output);
+                        bytes_as_chars(bytes)
+                    ));
+                }
+                let hex: String = memory[begin..end]
+                    .iter()
+                    .map(|b| format!("{:>02x}", b))
+                    .collect();
+                let record = json!({ "address": begin, "bytes": hex });
+                context.printer.print_record(&lines.join("\n"), &record);
+                Ok(None)
+            }
+            Opts::Find { pattern, limit } => {
+                let pattern = parse_pattern(&pattern)?;
+                let store = debugger.store()?;
+                let module_index = debugger.main_module_index()?;
+                if store.memory_count(module_index) == 0 {
+                    return Err(anyhow!("The main module has no memory"));
                 }
+                let memory = store.memory(MemoryAddr::new_unsafe(module_index, 0));
+                let matches = find_pattern(&memory.borrow(), &pattern, limit)?;
+                let text = if matches.is_empty() {
+                    "Pattern not found".to_string()
+                } else {
+                    matches
+                        .iter()
+                        .map(|addr| format!("0x{:>08x}", addr))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                let record = json!({ "addresses": matches });
+                context.printer.print_record(&text, &record);
                 Ok(None)
             }
             Opts::EnableWatch => {
@@ -81,22 +147,213 @@ impl<D: Debugger> Command<D> for MemoryCommand {
                 debugger.set_opts(opts);
                 Ok(None)
             }
+            Opts::Map => {
+                for region in memory_map(debugger, context)? {
+                    let output = match region.end {
+                        Some(end) => {
+                            format!("0x{:>08x}-0x{:>08x} {}", region.start, end, region.label)
+                        }
+                        None => format!("0x{:>08x}{:12}{}", region.start, "", region.label),
+                    };
+                    context.printer.println(&output);
+                }
+                Ok(None)
+            }
+            Opts::Grow { pages } => {
+                let store = debugger.store()?;
+                let module_index = debugger.main_module_index()?;
+                if store.memory_count(module_index) == 0 {
+                    return Err(anyhow!("The main module has no memory"));
+                }
+                let memory = store.memory(MemoryAddr::new_unsafe(module_index, 0));
+                let before = memory.borrow().page_count();
+                memory
+                    .borrow_mut()
+                    .grow(pages as usize)
+                    .map_err(|err| anyhow!("Failed to grow memory: {}", err))?;
+                context.printer.println(&format!(
+                    "Grew memory from {} to {} pages",
+                    before,
+                    memory.borrow().page_count()
+                ));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// The bytes `pattern` stands for: hex digits after a `0x` prefix, in the
+/// order they are written, or else the string itself
+fn parse_pattern(pattern: &str) -> Result<Vec<u8>> {
+    let bytes = match pattern.strip_prefix("0x") {
+        Some(hex) => {
+            // Digits are sliced out by byte offsets below
+            if !hex.is_ascii() {
+                return Err(anyhow!("invalid hex pattern '{}'", pattern));
+            }
+            if hex.len() % 2 != 0 {
+                return Err(anyhow!("odd number of hex digits in '{}'", pattern));
+            }
+            (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| anyhow!("invalid hex pattern '{}'", pattern))?
         }
+        None => pattern.as_bytes().to_vec(),
+    };
+    if bytes.is_empty() {
+        return Err(anyhow!("empty pattern"));
     }
+    Ok(bytes)
 }
 
-use std::str;
-fn dump_memory_as_str(bytes: &[u8]) -> String {
-    let mut v = Vec::new();
-    for byte in bytes.iter() {
-        let byte = *byte;
-        let byte = if byte > 0x1f && byte < 0x7f {
-            let byte = vec![byte];
-            str::from_utf8(&byte).unwrap_or(".").to_string()
-        } else {
-            ".".to_string()
+/// Addresses where `pattern` starts in `memory`, at most `limit` of them.
+/// Memory is scanned a page at a time, each page extended by the length of
+/// the pattern so that matches crossing into the next page are found too.
+fn find_pattern(memory: &HostMemory, pattern: &[u8], limit: Option<usize>) -> Result<Vec<usize>> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let len = memory.data_len();
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start + pattern.len() <= len && matches.len() < limit {
+        let end = (start + WASM_PAGE_SIZE + pattern.len() - 1).min(len);
+        let chunk = memory
+            .bytes(start, end - start)
+            .map_err(|err| anyhow!("{}", err))?;
+        let found = chunk
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| *window == pattern)
+            .map(|(offset, _)| start + offset);
+        matches.extend(found.take(limit - matches.len()));
+        start += WASM_PAGE_SIZE;
+    }
+    Ok(matches)
+}
+
+/// Regions of the main module's memory sorted by address. Enclosing ranges
+/// come before the ranges and addresses they contain.
+fn memory_map<D: Debugger>(debugger: &D, context: &CommandContext) -> Result<Vec<Region>> {
+    let store = debugger.store()?;
+    let module_index = debugger.main_module_index()?;
+    if store.memory_count(module_index) == 0 {
+        return Err(anyhow!("The main module has no memory"));
+    }
+    let module = store
+        .module(module_index)
+        .defined()
+        .ok_or_else(|| anyhow!("The main module is not a wasm module"))?;
+    let memory_size = store
+        .memory(MemoryAddr::new_unsafe(module_index, 0))
+        .borrow()
+        .data_len();
+    let read_global = |addr: GlobalAddr| match store.global(addr)?.borrow().value() {
+        WasmValue::Num(NumVal::I32(v)) => Some(v as u32 as usize),
+        _ => None,
+    };
+
+    let mut regions = vec![Region {
+        start: 0,
+        end: Some(memory_size),
+        label: format!("memory ({} pages)", memory_size / WASM_PAGE_SIZE),
+    }];
+    for segment in module.active_data_segments() {
+        if segment.memory_index != 0 || segment.len == 0 {
+            continue;
+        }
+        regions.push(Region {
+            start: segment.offset,
+            end: Some(segment.offset + segment.len),
+            label: format!("data[{}] ({} bytes)", segment.segment_index, segment.len),
+        });
+    }
+
+    let mut symbols = std::collections::HashMap::new();
+    for name in LAYOUT_SYMBOLS.iter() {
+        let addr = module.exported_global(name).ok().flatten();
+        if let Some(value) = addr.and_then(read_global) {
+            symbols.insert(*name, value);
+            regions.push(Region {
+                start: value,
+                end: None,
+                label: name.to_string(),
+            });
+        }
+    }
+    if let Some(heap_base) = symbols.get("__heap_base").copied() {
+        if let Some(data_end) = symbols.get("__data_end").copied() {
+            // wasm-ld places the stack between the data and the heap unless
+            // it's linked with --stack-first
+            if data_end < heap_base {
+                regions.push(Region {
+                    start: data_end,
+                    end: Some(heap_base),
+                    label: "stack".to_string(),
+                });
+            }
+        }
+        if heap_base < memory_size {
+            regions.push(Region {
+                start: heap_base,
+                end: Some(memory_size),
+                label: "heap".to_string(),
+            });
+        }
+    }
+
+    // DWARF frame bases name the global used as the stack pointer
+    if let (Ok((insts, next_index)), Some(frame)) =
+        (debugger.instructions(), debugger.current_frame())
+    {
+        let current_index = if next_index == 0 { 0 } else { next_index - 1 };
+        let frame_base = match insts.get(current_index) {
+            Some(inst) => context.subroutine.get_frame_base(inst.offset)?,
+            None => None,
         };
-        v.push(byte)
+        if let Some(WasmLoc::Global(index)) = frame_base {
+            let addr = GlobalAddr::new_unsafe(frame.module_index, index as usize);
+            if let Some(value) = read_global(addr) {
+                regions.push(Region {
+                    start: value,
+                    end: None,
+                    label: format!("stack pointer (global {})", index),
+                });
+            }
+        }
+    }
+
+    regions.sort_by_key(|region| (region.start, std::cmp::Reverse(region.end)));
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex_and_string_patterns() {
+        assert_eq!(parse_pattern("0xcafe").unwrap(), vec![0xca, 0xfe]);
+        assert_eq!(parse_pattern("abc").unwrap(), b"abc".to_vec());
+        assert!(parse_pattern("0xcaf").is_err());
+        assert!(parse_pattern("0xzz").is_err());
+        assert!(parse_pattern("0xaéb").is_err());
+    }
+
+    #[test]
+    fn find_pattern_across_pages() {
+        let mut memory = HostMemory::new(2, None);
+        memory.store(16, b"magic").unwrap();
+        memory.store(WASM_PAGE_SIZE - 2, b"magic").unwrap();
+        memory.store(2 * WASM_PAGE_SIZE - 5, b"magic").unwrap();
+        assert_eq!(
+            find_pattern(&memory, b"magic", None).unwrap(),
+            vec![16, WASM_PAGE_SIZE - 2, 2 * WASM_PAGE_SIZE - 5]
+        );
+        assert_eq!(
+            find_pattern(&memory, b"magic", Some(2)).unwrap(),
+            vec![16, WASM_PAGE_SIZE - 2]
+        );
+        assert!(find_pattern(&memory, b"absent", None).unwrap().is_empty());
     }
-    v.join("")
 }
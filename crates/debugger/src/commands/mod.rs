@@ -1,21 +1,34 @@
 pub mod command;
+pub mod completion;
 pub mod debugger;
+pub mod evaluator;
+pub mod format;
+pub mod names;
 pub mod sourcemap;
 pub mod subroutine;
 pub mod symbol;
 
 // commands
+pub mod alias;
 pub mod backtrace;
 pub mod breakpoint;
+pub mod coverage;
 pub mod disassemble;
 pub mod expression;
 pub mod frame;
 pub mod global;
+pub mod image;
 pub mod list;
 pub mod local;
 pub mod memory;
 pub mod process;
+pub mod profile;
 pub mod run;
+pub mod segment;
+pub mod session;
 pub mod settings;
 pub mod stack;
+pub mod table;
 pub mod thread;
+pub mod until;
+pub mod r#where;
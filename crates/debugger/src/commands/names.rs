@@ -0,0 +1,90 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use wasmparser::{Name, NameSectionReader, Payload};
+
+/// Local names from the `name` custom section, which many modules carry
+/// without any DWARF. Function names from it are already the names of the
+/// function instances.
+#[derive(Default)]
+pub struct NameMap {
+    /// Local names by function index, then by local index
+    locals: HashMap<u32, HashMap<u32, String>>,
+}
+
+impl NameMap {
+    /// Names of `bytes`, empty if the module has no name section
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut names = Self::default();
+        for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+            match payload? {
+                Payload::CustomSection {
+                    name: "name",
+                    data,
+                    data_offset,
+                    ..
+                } => names.read_section(NameSectionReader::new(data, data_offset)?)?,
+                Payload::End => break,
+                _ => {}
+            }
+        }
+        Ok(names)
+    }
+
+    fn read_section(&mut self, mut reader: NameSectionReader) -> Result<()> {
+        while !reader.eof() {
+            match reader.read()? {
+                Name::Local(names) => {
+                    let mut funcs = names.get_indirect_map()?;
+                    for _ in 0..funcs.get_indirect_count() {
+                        let func = funcs.read()?;
+                        let locals = self.locals.entry(func.indirect_index).or_default();
+                        let mut map = func.get_map()?;
+                        for _ in 0..map.get_count() {
+                            let naming = map.read()?;
+                            locals.insert(naming.index, String::from(naming.name));
+                        }
+                    }
+                }
+                _ => continue,
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of local `index` of function `func`, or `local[index]` without one
+    pub fn local_name(&self, func: u32, index: u32) -> String {
+        match self.locals.get(&func).and_then(|locals| locals.get(&index)) {
+            Some(name) => name.clone(),
+            None => format!("local[{}]", index),
+        }
+    }
+
+    /// Whether function `func` has any named locals
+    pub fn has_local_names(&self, func: u32) -> bool {
+        self.locals
+            .get(&func)
+            .map_or(false, |locals| !locals.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_local_names() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            // name section: func 0 is "f", its local 1 is "x"
+            0x00, 0x13, 0x04, 0x6e, 0x61, 0x6d, 0x65,
+            0x01, 0x04, 0x01, 0x00, 0x01, 0x66,
+            0x02, 0x06, 0x01, 0x00, 0x01, 0x01, 0x01, 0x78,
+        ];
+        let names = NameMap::parse(&bytes).unwrap();
+        assert_eq!(names.local_name(0, 1), "x");
+        assert_eq!(names.local_name(0, 0), "local[0]");
+        assert!(names.has_local_names(0));
+        assert!(!names.has_local_names(1));
+    }
+}
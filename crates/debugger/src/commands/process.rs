@@ -1,8 +1,11 @@
 use crate::RunResult;
 
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::Debugger;
-use anyhow::Result;
+use super::completion::CompletionTree;
+use super::debugger::{describe_error, Debugger};
+use super::thread::display_backtrace;
+use anyhow::{anyhow, Result};
+use std::time::Duration;
 
 use structopt::StructOpt;
 
@@ -16,17 +19,38 @@ impl ProcessCommand {
 
 #[derive(StructOpt)]
 enum Opts {
+    /// Continue until the COUNT-th breakpoint hit
     #[structopt(name = "continue")]
-    Continue,
+    Continue {
+        #[structopt(name = "COUNT", default_value = "1")]
+        count: u64,
+    },
 
     /// Start WASI entry point
     #[structopt(name = "launch")]
     Launch {
+        /// Break into the debugger when the process runs longer than this (e.g. `500ms`, `2s`, `1m`)
+        #[structopt(long, parse(try_from_str = parse_duration))]
+        timeout: Option<Duration>,
         #[structopt(name = "ARGS", last = true)]
         args: Vec<String>,
     },
 }
 
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => s.split_at(pos),
+        None => (s, "s"),
+    };
+    let value = value.parse::<u64>()?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        _ => Err(anyhow!("invalid duration unit '{}'", unit)),
+    }
+}
+
 impl<D: Debugger> Command<D> for ProcessCommand {
     fn name(&self) -> &'static str {
         "process"
@@ -36,24 +60,36 @@ impl<D: Debugger> Command<D> for ProcessCommand {
         "Commands for interacting with processes."
     }
 
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
-            Opts::Continue => match debugger.process()? {
+            Opts::Continue { count } => match debugger.process_skipping(count.saturating_sub(1))? {
                 RunResult::Finish(result) => {
                     return Ok(Some(CommandResult::ProcessFinish(result)));
                 }
-                RunResult::Breakpoint => {
+                // Only launching sets a timeout
+                RunResult::Breakpoint | RunResult::Timeout => {
                     context.printer.println("Hit breakpoint");
                 }
+                RunResult::Trap(err) => {
+                    let output = describe_error(&err, context);
+                    context
+                        .printer
+                        .eprintln(&format!("Stopped at trap: {}", output));
+                    display_backtrace(debugger, context.printer.as_ref());
+                }
             },
-            Opts::Launch { args } => {
-                return self.start_debugger(debugger, context, args);
+            Opts::Launch { timeout, args } => {
+                return self.start_debugger(debugger, context, args, timeout);
             }
         }
         Ok(None)
@@ -65,6 +101,7 @@ impl ProcessCommand {
         debugger: &mut D,
         context: &CommandContext,
         wasi_args: Vec<String>,
+        timeout: Option<Duration>,
     ) -> Result<Option<CommandResult>> {
         use std::io::Write;
         if debugger.is_running() {
@@ -79,19 +116,31 @@ impl ProcessCommand {
         }
         debugger.instantiate(std::collections::HashMap::new(), &wasi_args)?;
 
-        match debugger.run(None, vec![]) {
+        debugger.set_timeout(timeout);
+        let result = debugger.run(None, vec![]);
+        debugger.set_timeout(None);
+        match result {
             Ok(RunResult::Finish(values)) => {
                 let output = format!("{:?}", values);
                 context.printer.println(&output);
                 return Ok(Some(CommandResult::ProcessFinish(values)));
             }
-            Ok(RunResult::Breakpoint) => {
-                context.printer.println("Hit breakpoint");
+            Ok(RunResult::Breakpoint) => context.printer.println("Hit breakpoint"),
+            Ok(RunResult::Timeout) => {
+                // Only launching sets a timeout, so it's always there
+                let timeout = timeout.unwrap_or_default();
+                let output = format!("Process timed out after {:?}", timeout);
+                context.printer.println(&output);
+                display_backtrace(debugger, context.printer.as_ref());
             }
-            Err(msg) => {
-                let output = format!("{}", msg);
-                context.printer.eprintln(&output);
+            Ok(RunResult::Trap(err)) => {
+                let output = describe_error(&err, context);
+                context
+                    .printer
+                    .eprintln(&format!("Stopped at trap: {}", output));
+                display_backtrace(debugger, context.printer.as_ref());
             }
+            Err(err) => return Err(err),
         }
         Ok(None)
     }
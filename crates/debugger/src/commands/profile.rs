@@ -0,0 +1,73 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
+use super::debugger::Debugger;
+use super::symbol::demangle_symbol;
+use anyhow::Result;
+
+use structopt::StructOpt;
+
+pub struct ProfileCommand {}
+
+impl ProfileCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Start counting executed instructions
+    #[structopt(long, conflicts_with = "stop")]
+    start: bool,
+    /// Stop counting, keeping the counts so far
+    #[structopt(long)]
+    stop: bool,
+    /// Clear the collected counts
+    #[structopt(long)]
+    reset: bool,
+}
+
+impl<D: Debugger> Command<D> for ProfileCommand {
+    fn name(&self) -> &'static str {
+        "profile"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show executed instruction counts per function."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        if opts.reset {
+            debugger.reset_profile();
+        }
+        if opts.start || opts.stop {
+            debugger.set_profiling(opts.start);
+        }
+        if opts.reset || opts.start || opts.stop {
+            return Ok(None);
+        }
+        let profile = debugger.profile()?;
+        if profile.is_empty() && !debugger.is_profiling() {
+            context
+                .printer
+                .println("Profiling is off, start it with `profile --start`");
+        } else if profile.is_empty() {
+            context.printer.println("No instructions executed yet");
+        }
+        for (name, count) in profile {
+            let output = format!("{:>10} {}", count, demangle_symbol(&name));
+            context.printer.println(&output);
+        }
+        Ok(None)
+    }
+}
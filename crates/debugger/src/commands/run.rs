@@ -1,5 +1,7 @@
 use super::command::AliasCommand;
+use super::completion::CompletionTree;
 use anyhow::Result;
+use structopt::StructOpt;
 
 pub struct RunCommand {}
 
@@ -9,12 +11,33 @@ impl RunCommand {
     }
 }
 
+#[derive(StructOpt)]
+struct Opts {
+    /// Break into the debugger when the process runs longer than this (e.g. `500ms`, `2s`, `1m`)
+    #[structopt(long)]
+    timeout: Option<String>,
+    #[structopt(name = "ARGS", last = true)]
+    args: Vec<String>,
+}
+
 impl AliasCommand for RunCommand {
     fn name(&self) -> &'static str {
         "run"
     }
 
-    fn run(&self, _args: Vec<&str>) -> Result<String> {
-        Ok("process launch".to_string())
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(&self, args: Vec<&str>) -> Result<String> {
+        let opts = Opts::from_iter_safe(args)?;
+        let mut line = "process launch".to_string();
+        if let Some(timeout) = opts.timeout {
+            line += &format!(" --timeout {}", shell_words::quote(&timeout));
+        }
+        if !opts.args.is_empty() {
+            line += &format!(" -- {}", shell_words::join(&opts.args));
+        }
+        Ok(line)
     }
 }
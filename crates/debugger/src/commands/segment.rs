@@ -0,0 +1,230 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
+use super::debugger::Debugger;
+use anyhow::{anyhow, Result};
+use wasminspect_vm::{DataAddr, ElemAddr};
+use wasmparser::{DataKind, ElementItem, ElementKind, InitExpr, Operator, Payload};
+
+use structopt::StructOpt;
+
+pub struct SegmentCommand {}
+
+impl SegmentCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// List data and element segments of the main module
+    #[structopt(name = "list")]
+    List,
+}
+
+enum SegmentKind {
+    Active { index: u32, offset: String },
+    Passive,
+    Declared,
+}
+
+struct DataSegment {
+    kind: SegmentKind,
+    len: usize,
+}
+
+struct ElementSegment {
+    kind: SegmentKind,
+    ty: String,
+    entries: Vec<String>,
+}
+
+impl<D: Debugger> Command<D> for SegmentCommand {
+    fn name(&self) -> &'static str {
+        "segment"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for inspecting data and element segments."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::List => {
+                let (bytes, _) = debugger
+                    .main_module_source()
+                    .ok_or_else(|| anyhow!("No module loaded"))?;
+                let (data_segments, elem_segments) = parse_segments(bytes)?;
+                // Active and declared segments are always dropped on instantiation, so only
+                // passive ones emptied at runtime by `data.drop`/`elem.drop` are reported
+                let instance = match (debugger.store(), debugger.main_module_index()) {
+                    (Ok(store), Ok(module_index)) => Some((store, module_index)),
+                    _ => None,
+                };
+
+                context.printer.println("Data segments:");
+                for (index, segment) in data_segments.iter().enumerate() {
+                    let is_passive = matches!(segment.kind, SegmentKind::Passive);
+                    let is_dropped = instance.map_or(false, |(store, module_index)| {
+                        let data = store.data(DataAddr::new_unsafe(module_index, index));
+                        let is_empty = data.borrow().raw().is_empty();
+                        is_passive && segment.len > 0 && is_empty
+                    });
+                    let output = format!(
+                        "  {}: {} length {}{}",
+                        index,
+                        format_kind(&segment.kind, "memory"),
+                        segment.len,
+                        if is_dropped { " (dropped)" } else { "" }
+                    );
+                    context.printer.println(&output);
+                }
+
+                context.printer.println("Element segments:");
+                for (index, segment) in elem_segments.iter().enumerate() {
+                    let is_passive = matches!(segment.kind, SegmentKind::Passive);
+                    let is_dropped = instance.map_or(false, |(store, module_index)| {
+                        let elem = store.elem(ElemAddr::new_unsafe(module_index, index));
+                        let is_empty = elem.borrow().is_empty();
+                        is_passive && !segment.entries.is_empty() && is_empty
+                    });
+                    let output = format!(
+                        "  {}: {} {} [{}]{}",
+                        index,
+                        format_kind(&segment.kind, "table"),
+                        segment.ty,
+                        segment.entries.join(", "),
+                        if is_dropped { " (dropped)" } else { "" }
+                    );
+                    context.printer.println(&output);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn format_kind(kind: &SegmentKind, target: &str) -> String {
+    match kind {
+        SegmentKind::Active { index, offset } => {
+            format!("active {} {} offset ({})", target, index, offset)
+        }
+        SegmentKind::Passive => "passive".to_string(),
+        SegmentKind::Declared => "declared".to_string(),
+    }
+}
+
+fn format_init_expr(expr: &InitExpr) -> Result<String> {
+    let mut operators = Vec::new();
+    for op in expr.get_operators_reader() {
+        let text = match op? {
+            Operator::End => continue,
+            Operator::I32Const { value } => format!("i32.const {}", value),
+            Operator::I64Const { value } => format!("i64.const {}", value),
+            Operator::GlobalGet { global_index } => format!("global.get {}", global_index),
+            Operator::RefNull { ty } => format!("ref.null {:?}", ty).to_lowercase(),
+            Operator::RefFunc { function_index } => format!("ref.func {}", function_index),
+            other => format!("{:?}", other),
+        };
+        operators.push(text);
+    }
+    Ok(operators.join(" "))
+}
+
+fn parse_segments(bytes: &[u8]) -> Result<(Vec<DataSegment>, Vec<ElementSegment>)> {
+    let mut data_segments = Vec::new();
+    let mut elem_segments = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+        match payload? {
+            Payload::DataSection(section) => {
+                for entry in section {
+                    let entry = entry?;
+                    let kind = match entry.kind {
+                        DataKind::Active {
+                            memory_index,
+                            init_expr,
+                        } => SegmentKind::Active {
+                            index: memory_index,
+                            offset: format_init_expr(&init_expr)?,
+                        },
+                        DataKind::Passive => SegmentKind::Passive,
+                    };
+                    data_segments.push(DataSegment {
+                        kind,
+                        len: entry.data.len(),
+                    });
+                }
+            }
+            Payload::ElementSection(section) => {
+                for entry in section {
+                    let entry = entry?;
+                    let kind = match entry.kind {
+                        ElementKind::Active {
+                            table_index,
+                            init_expr,
+                        } => SegmentKind::Active {
+                            index: table_index,
+                            offset: format_init_expr(&init_expr)?,
+                        },
+                        ElementKind::Passive => SegmentKind::Passive,
+                        ElementKind::Declared => SegmentKind::Declared,
+                    };
+                    let mut entries = Vec::new();
+                    for item in entry.items.get_items_reader()? {
+                        entries.push(match item? {
+                            ElementItem::Func(index) => format!("func {}", index),
+                            ElementItem::Expr(expr) => format_init_expr(&expr)?,
+                        });
+                    }
+                    elem_segments.push(ElementSegment {
+                        kind,
+                        ty: format!("{:?}", entry.ty).to_lowercase(),
+                        entries,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((data_segments, elem_segments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_data_segments() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            // (memory 1)
+            0x05, 0x03, 0x01, 0x00, 0x01,
+            // (data (i32.const 16) "hi") (data "abc")
+            0x0b, 0x0d, 0x02,
+            0x00, 0x41, 0x10, 0x0b, 0x02, 0x68, 0x69,
+            0x01, 0x03, 0x61, 0x62, 0x63,
+        ];
+        let (data, elems) = parse_segments(&bytes).unwrap();
+        assert!(elems.is_empty());
+        assert_eq!(
+            data.iter()
+                .map(|segment| (format_kind(&segment.kind, "memory"), segment.len))
+                .collect::<Vec<_>>(),
+            vec![
+                ("active memory 0 offset (i32.const 16)".to_string(), 2),
+                ("passive".to_string(), 3),
+            ]
+        );
+    }
+}
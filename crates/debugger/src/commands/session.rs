@@ -0,0 +1,135 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
+use super::debugger::{Breakpoint, Debugger, DebuggerOpts};
+use crate::reload_main_module;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use structopt::StructOpt;
+
+pub struct SessionCommand {}
+
+impl SessionCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// Save the loaded module, breakpoints and settings to a file
+    #[structopt(name = "save")]
+    Save { path: String },
+    /// Restore a session saved by `session save`, loading and instantiating
+    /// its module
+    #[structopt(name = "load")]
+    Load { path: String },
+}
+
+const SESSION_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SavedModule {
+    name: String,
+    /// base64 encoded module binary, so that the session doesn't depend on the module path
+    bytes: String,
+}
+
+/// Everything needed to resume debugging except live execution state
+#[derive(Serialize, Deserialize)]
+struct Session {
+    version: u32,
+    module: Option<SavedModule>,
+    breakpoints: Vec<Breakpoint>,
+    opts: DebuggerOpts,
+    directory_map: Vec<(String, String)>,
+}
+
+impl<D: Debugger> Command<D> for SessionCommand {
+    fn name(&self) -> &'static str {
+        "session"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for saving and restoring debugger sessions."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        match opts {
+            Opts::Save { path } => {
+                let session = Session {
+                    version: SESSION_FORMAT_VERSION,
+                    module: debugger
+                        .main_module_source()
+                        .map(|(bytes, name)| SavedModule {
+                            name: name.to_string(),
+                            bytes: base64::encode(bytes),
+                        }),
+                    breakpoints: debugger.breakpoints(),
+                    opts: debugger.get_opts(),
+                    directory_map: context.sourcemap.directory_map(),
+                };
+                let file = std::fs::File::create(&path)
+                    .with_context(|| format!("failed to create {}", path))?;
+                serde_json::to_writer(file, &session)?;
+                context
+                    .printer
+                    .println(&format!("Session saved to {}", path));
+            }
+            Opts::Load { path } => {
+                let file = std::fs::File::open(&path)
+                    .with_context(|| format!("failed to open {}", path))?;
+                let session: Session = serde_json::from_reader(std::io::BufReader::new(file))?;
+                if session.version != SESSION_FORMAT_VERSION {
+                    return Err(anyhow!(
+                        "unsupported session format version {}",
+                        session.version
+                    ));
+                }
+                // Drop the current breakpoints first so that reloading doesn't
+                // report them as unresolved
+                for breakpoint in debugger.breakpoints() {
+                    debugger.delete_breakpoint(breakpoint);
+                }
+                if let Some(module) = session.module {
+                    let bytes = base64::decode(&module.bytes)?;
+                    reload_main_module(debugger, context, &bytes, module.name)?;
+                    // A module whose imports can't be satisfied here can still
+                    // have its breakpoints restored and be launched later
+                    if let Err(err) = debugger.instantiate(HashMap::new(), &[]) {
+                        let output = format!("Failed to instantiate the module: {}", err);
+                        context.printer.eprintln(&output);
+                    }
+                }
+                // Directories are mapped on the DWARF info just loaded
+                for (from, to) in session.directory_map {
+                    context.sourcemap.set_directory_map(from, to);
+                }
+                for breakpoint in session.breakpoints {
+                    debugger.set_breakpoint(breakpoint);
+                }
+                for breakpoint in debugger.resolve_breakpoints(&*context.sourcemap) {
+                    let output = format!("Breakpoint {} doesn't map to any code", breakpoint);
+                    context.printer.eprintln(&output);
+                }
+                context.printer.set_color(session.opts.color);
+                debugger.set_opts(session.opts);
+                context
+                    .printer
+                    .println(&format!("Session loaded from {}", path));
+            }
+        }
+        Ok(None)
+    }
+}
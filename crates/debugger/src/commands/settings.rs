@@ -1,6 +1,8 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::Debugger;
-use anyhow::Result;
+use super::completion::CompletionTree;
+use super::debugger::{Debugger, DebuggerOpts, StopView};
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
 
 use structopt::StructOpt;
 
@@ -14,14 +16,173 @@ impl SettingsCommand {
 
 #[derive(StructOpt)]
 enum Opts {
+    /// List every setting with its current value
+    #[structopt(name = "list")]
+    List,
+    /// Show the current value of a setting
+    #[structopt(name = "get")]
+    Get { key: String },
+    /// Change a setting
     #[structopt(name = "set")]
     Set {
         key: String,
         operand1: String,
-        operand2: String,
+        operand2: Option<String>,
     },
 }
 
+/// Every key accepted by `settings`
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Setting {
+    BreakOnTrap,
+    DisassembleCount,
+    Color,
+    StopDisplay,
+    WatchMemory,
+    PoisonLocals,
+    DirectoryMap,
+}
+
+impl Setting {
+    const ALL: [Setting; 7] = [
+        Setting::BreakOnTrap,
+        Setting::DisassembleCount,
+        Setting::Color,
+        Setting::StopDisplay,
+        Setting::WatchMemory,
+        Setting::PoisonLocals,
+        Setting::DirectoryMap,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Setting::BreakOnTrap => "break-on-trap",
+            Setting::DisassembleCount => "disassemble-count",
+            Setting::Color => "color",
+            Setting::StopDisplay => "stop-display",
+            Setting::WatchMemory => "watch-memory",
+            Setting::PoisonLocals => "poison-locals",
+            Setting::DirectoryMap => "directory.map",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Setting::BreakOnTrap => "Stop at the trapping instruction instead of aborting the run",
+            Setting::DisassembleCount => {
+                "Number of instructions shown after stepping or by `disassemble --pc`"
+            }
+            Setting::Color => "Highlight disassembly and source listings on a terminal",
+            Setting::StopDisplay => {
+                "Views printed after each step or stop, in order, from `asm,source,locals`"
+            }
+            Setting::WatchMemory => "Watch memory stores, same as `memory enable-watch`",
+            Setting::PoisonLocals => "Stop when a local is read before it was ever written",
+            Setting::DirectoryMap => "Source directory remapping, set with `<from> <to>`",
+        }
+    }
+
+    fn get(self, opts: &DebuggerOpts, context: &CommandContext) -> String {
+        match self {
+            Setting::BreakOnTrap => opts.break_on_trap.to_string(),
+            Setting::DisassembleCount => opts.disassemble_count.to_string(),
+            Setting::Color => opts.color.to_string(),
+            Setting::StopDisplay => opts
+                .stop_display
+                .iter()
+                .map(|view| view.name())
+                .collect::<Vec<_>>()
+                .join(","),
+            Setting::WatchMemory => opts.watch_memory.to_string(),
+            Setting::PoisonLocals => opts.poison_locals.to_string(),
+            Setting::DirectoryMap => context
+                .sourcemap
+                .directory_map()
+                .iter()
+                .map(|(from, to)| format!("{} -> {}", from, to))
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    fn set(
+        self,
+        opts: &mut DebuggerOpts,
+        context: &CommandContext,
+        operand1: String,
+        operand2: Option<String>,
+    ) -> Result<()> {
+        if self != Setting::DirectoryMap && operand2.is_some() {
+            return Err(anyhow!("{} takes a single value", self.name()));
+        }
+        match self {
+            Setting::BreakOnTrap => opts.break_on_trap = parse_value(self, &operand1)?,
+            Setting::DisassembleCount => opts.disassemble_count = parse_value(self, &operand1)?,
+            Setting::Color => {
+                opts.color = parse_value(self, &operand1)?;
+                context.printer.set_color(opts.color);
+            }
+            Setting::StopDisplay => opts.stop_display = parse_stop_views(&operand1)?,
+            Setting::WatchMemory => opts.watch_memory = parse_value(self, &operand1)?,
+            Setting::PoisonLocals => opts.poison_locals = parse_value(self, &operand1)?,
+            Setting::DirectoryMap => {
+                let operand2 =
+                    operand2.ok_or_else(|| anyhow!("{} takes two paths", self.name()))?;
+                context.sourcemap.set_directory_map(operand1, operand2);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Setting {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .find(|setting| setting.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                anyhow!(
+                    "'{}' is not a valid key, use `settings list` to see the available ones",
+                    s
+                )
+            })
+    }
+}
+
+fn parse_value<T: FromStr>(setting: Setting, value: &str) -> Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|err| anyhow!("invalid value '{}' for {}: {}", value, setting.name(), err))
+}
+
+/// Parse a comma separated list of views, an empty one disables the display
+fn parse_stop_views(value: &str) -> Result<Vec<StopView>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            StopView::ALL
+                .iter()
+                .find(|view| view.name() == name)
+                .copied()
+                .ok_or_else(|| {
+                    anyhow!(
+                        "invalid value '{}' for stop-display: unknown view '{}'",
+                        value,
+                        name
+                    )
+                })
+        })
+        .collect()
+}
+
 impl<D: Debugger> Command<D> for SettingsCommand {
     fn name(&self) -> &'static str {
         "settings"
@@ -31,28 +192,88 @@ impl<D: Debugger> Command<D> for SettingsCommand {
         "Commands for setting environment"
     }
 
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
-        _debugger: &mut D,
-        context: &CommandContext,
+        debugger: &mut D,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args)?;
         match opts {
+            Opts::List => {
+                let opts = debugger.get_opts();
+                for setting in Setting::ALL.iter() {
+                    let output = format!(
+                        "{} = {} -- {}",
+                        setting.name(),
+                        setting.get(&opts, context),
+                        setting.description()
+                    );
+                    context.printer.println(&output);
+                }
+            }
+            Opts::Get { key } => {
+                let setting: Setting = key.parse()?;
+                let output = format!(
+                    "{} = {}",
+                    setting.name(),
+                    setting.get(&debugger.get_opts(), context)
+                );
+                context.printer.println(&output);
+            }
             Opts::Set {
                 key,
                 operand1,
                 operand2,
-            } => match key.as_str() {
-                "directory.map" => {
-                    context.sourcemap.set_directory_map(operand1, operand2);
-                }
-                _ => {
-                    let output = format!("'{}' is not valid key", key);
-                    context.printer.eprintln(&output);
-                }
-            },
+            } => {
+                let setting: Setting = key.parse()?;
+                let mut opts = debugger.get_opts();
+                setting.set(&mut opts, context, operand1, operand2)?;
+                debugger.set_opts(opts);
+            }
         }
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setting_names_round_trip() {
+        for setting in Setting::ALL.iter() {
+            assert_eq!(setting.name().parse::<Setting>().unwrap(), *setting);
+        }
+        let err = "break-on-traps".parse::<Setting>().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("'break-on-traps' is not a valid key"));
+    }
+
+    #[test]
+    fn stop_views_keep_their_order() {
+        assert_eq!(
+            parse_stop_views("locals, asm").unwrap(),
+            vec![StopView::Locals, StopView::Asm]
+        );
+        assert!(parse_stop_views("").unwrap().is_empty());
+        assert!(parse_stop_views("asm,regs").is_err());
+    }
+
+    #[test]
+    fn parse_value_rejects_mistyped_values() {
+        assert_eq!(
+            parse_value::<usize>(Setting::DisassembleCount, "8").unwrap(),
+            8
+        );
+        let err = parse_value::<bool>(Setting::BreakOnTrap, "yes").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("invalid value 'yes' for break-on-trap"));
+    }
+}
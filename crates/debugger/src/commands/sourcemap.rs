@@ -20,9 +20,25 @@ pub struct LineInfo {
     pub column: ColumnType,
 }
 
+impl std::fmt::Display for LineInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.filepath,
+            self.line
+                .map(|l| format!("{}", l))
+                .unwrap_or_else(|| "".to_string()),
+            Into::<u64>::into(self.column)
+        )
+    }
+}
+
 pub trait SourceMap {
     fn find_line_info(&self, offset: usize) -> Option<LineInfo>;
+    fn find_code_offset(&self, filepath: &str, line: u64) -> Option<usize>;
     fn set_directory_map(&self, from: String, to: String);
+    fn directory_map(&self) -> Vec<(String, String)>;
 }
 
 pub struct EmptySourceMap {}
@@ -36,5 +52,11 @@ impl SourceMap for EmptySourceMap {
     fn find_line_info(&self, _: usize) -> Option<LineInfo> {
         None
     }
+    fn find_code_offset(&self, _: &str, _: u64) -> Option<usize> {
+        None
+    }
     fn set_directory_map(&self, _: String, _: String) {}
+    fn directory_map(&self) -> Vec<(String, String)> {
+        vec![]
+    }
 }
@@ -1,5 +1,6 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::Debugger;
+use super::debugger::{Debugger, StackEntry};
+use super::format::{typed_value, Radix};
 use anyhow::Result;
 
 pub struct StackCommand {}
@@ -22,13 +23,77 @@ impl<D: Debugger> Command<D> for StackCommand {
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         _args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
-        for (index, value) in debugger.stack_values().iter().enumerate() {
-            let output = format!("{}: {:?}", index, value);
-            context.printer.println(&output);
+        for line in format_stack(&debugger.stack_entries()) {
+            context.printer.println(&line);
         }
         Ok(None)
     }
 }
+
+/// Formats entries top-down, indenting values and labels by their nesting level
+/// inside the enclosing frame
+fn format_stack(entries: &[StackEntry]) -> Vec<String> {
+    let mut depth = 0;
+    let mut lines = Vec::new();
+    for entry in entries {
+        let (entry_depth, description) = match entry {
+            StackEntry::Value(value) => {
+                let (ty, value) = typed_value(value, Radix::Dec);
+                (depth, format!("{} {}", ty, value))
+            }
+            StackEntry::Label { kind, arity } => {
+                depth += 1;
+                (depth - 1, format!("label {} (arity {})", kind, arity))
+            }
+            StackEntry::Frame { name } => {
+                depth = 1;
+                (0, format!("frame {}", name))
+            }
+        };
+        lines.push(format!("{}{}", "  ".repeat(entry_depth), description));
+    }
+    lines
+        .into_iter()
+        .rev()
+        .enumerate()
+        .map(|(index, line)| format!("{}: {}", index, line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasminspect_vm::WasmValue;
+
+    #[test]
+    fn format_nested_stack() {
+        let entries = vec![
+            StackEntry::Frame {
+                name: "main".to_string(),
+            },
+            StackEntry::Label {
+                kind: "return",
+                arity: 1,
+            },
+            StackEntry::Value(WasmValue::I32(1)),
+            StackEntry::Label {
+                kind: "block",
+                arity: 0,
+            },
+            StackEntry::Value(WasmValue::from(1.5f64)),
+        ];
+        assert_eq!(
+            format_stack(&entries),
+            vec![
+                "0:       f64 1.5",
+                "1:     label block (arity 0)",
+                "2:     i32 1",
+                "3:   label return (arity 1)",
+                "4: frame main",
+            ]
+        );
+    }
+}
@@ -9,6 +9,8 @@ pub struct Variable {
 pub trait SubroutineMap {
     fn variable_name_list(&self, code_offset: usize) -> Result<Vec<Variable>>;
     fn get_frame_base(&self, code_offset: usize) -> Result<Option<WasmLoc>>;
+    /// Source name of the function whose code contains `code_offset`
+    fn function_name(&self, code_offset: usize) -> Option<String>;
     fn display_variable(
         &self,
         code_offset: usize,
@@ -32,6 +34,9 @@ impl SubroutineMap for EmptySubroutineMap {
     fn get_frame_base(&self, _: usize) -> Result<Option<WasmLoc>> {
         Ok(Some(WasmLoc::Global(0)))
     }
+    fn function_name(&self, _: usize) -> Option<String> {
+        None
+    }
     fn display_variable(&self, _: usize, _: FrameBase, _: &[u8], _: String) -> Result<()> {
         Ok(())
     }
@@ -21,3 +21,54 @@ fn demangle_swift_symbol(symbol: &str) -> &str {
 fn demangle_swift_symbol(symbol: &str) -> &str {
     symbol
 }
+
+/// Parts of the names of the Rust panic machinery, mangled or not
+const RUST_PANIC_PATTERNS: &[&str] = &[
+    "panicking",
+    "rust_begin_unwind",
+    "rust_panic",
+    "__rust_start_panic",
+];
+
+/// Prefixes of the Rust standard library crates, demangled and mangled
+const RUST_STD_PREFIXES: &[&str] = &[
+    "core::",
+    "std::",
+    "alloc::",
+    "_ZN4core",
+    "_ZN3std",
+    "_ZN5alloc",
+];
+
+/// Whether `symbol` is part of the Rust panic machinery, which ends in
+/// `unreachable` when the panic can't unwind
+pub fn is_rust_panic_symbol(symbol: &str) -> bool {
+    RUST_PANIC_PATTERNS
+        .iter()
+        .any(|pattern| symbol.contains(pattern))
+}
+
+/// Whether `symbol` is defined by the Rust standard library
+pub fn is_rust_std_symbol(symbol: &str) -> bool {
+    is_rust_panic_symbol(symbol)
+        || RUST_STD_PREFIXES
+            .iter()
+            .any(|prefix| symbol.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_rust_panic_symbols() {
+        assert!(is_rust_panic_symbol("rust_begin_unwind"));
+        assert!(is_rust_panic_symbol(
+            "core::panicking::panic_bounds_check::h0123456789abcdef"
+        ));
+        assert!(is_rust_panic_symbol("_ZN4core9panicking9panic_fmt17hE"));
+        assert!(!is_rust_panic_symbol("app::main"));
+        assert!(is_rust_std_symbol("_ZN4core6result13unwrap_failed17hE"));
+        assert!(!is_rust_std_symbol("app::core::main"));
+    }
+}
@@ -0,0 +1,114 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
+use super::debugger::Debugger;
+use anyhow::{anyhow, Result};
+use wasminspect_vm::{Limits, RefVal, Store, TableAddr};
+
+use structopt::StructOpt;
+
+pub struct TableCommand {}
+
+impl TableCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+enum Opts {
+    /// List tables with their size and limits
+    #[structopt(name = "list")]
+    List,
+    /// Print the references stored in a range of table slots
+    #[structopt(name = "read")]
+    Read {
+        #[structopt(name = "TABLE")]
+        table: usize,
+        #[structopt(name = "INDEX")]
+        index: usize,
+        #[structopt(name = "COUNT", default_value = "1")]
+        count: usize,
+    },
+}
+
+impl<D: Debugger> Command<D> for TableCommand {
+    fn name(&self) -> &'static str {
+        "table"
+    }
+
+    fn description(&self) -> &'static str {
+        "Commands for inspecting tables."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        let store = debugger.store()?;
+        let module_index = match debugger.current_frame() {
+            Some(frame) => frame.module_index,
+            None => debugger.main_module_index()?,
+        };
+        match opts {
+            Opts::List => {
+                for index in 0..store.table_count(module_index) {
+                    let table = store.table(TableAddr::new_unsafe(module_index, index));
+                    let table = table.borrow();
+                    // The declared limits, the current size is shown beside
+                    let limits = Limits {
+                        min: table.initial,
+                        max: table.max,
+                    };
+                    let output = format!(
+                        "{}: {} size {} limits {}",
+                        index,
+                        table.element_type(),
+                        table.buffer_len(),
+                        limits
+                    );
+                    context.printer.println(&output);
+                }
+            }
+            Opts::Read {
+                table,
+                index,
+                count,
+            } => {
+                let table_count = store.table_count(module_index);
+                if table >= table_count {
+                    return Err(anyhow!(
+                        "table {} out of range, module has {} tables",
+                        table,
+                        table_count
+                    ));
+                }
+                let table = store.table(TableAddr::new_unsafe(module_index, table));
+                let table = table.borrow();
+                for slot in index..index + count {
+                    let value = table.get_at(slot).map_err(|err| anyhow!("{}", err))?;
+                    let output = format!("[{}]: {}", slot, format_ref(store, value));
+                    context.printer.println(&output);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn format_ref(store: &Store, value: RefVal) -> String {
+    match value {
+        RefVal::NullRef(ty) => format!("null {:?}", ty).to_lowercase(),
+        RefVal::FuncRef(addr) => match store.func(addr) {
+            Some((func, _)) => format!("func {}", func.name()),
+            None => "func <unresolved>".to_string(),
+        },
+        RefVal::ExternRef(value) => format!("externref {}", value),
+    }
+}
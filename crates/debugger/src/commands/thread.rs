@@ -1,8 +1,10 @@
 use super::command::{Command, CommandContext, CommandResult};
-use super::debugger::{Debugger, StepStyle};
-use super::disassemble::display_asm;
-use super::list::{display_source, next_line_info};
+use super::completion::CompletionTree;
+use super::debugger::{Debugger, OutputPrinter, StepStyle};
+use super::list::next_line_info;
 use super::symbol::demangle_symbol;
+use serde_json::json;
+use wasminspect_vm::Signal;
 
 pub struct ThreadCommand {}
 
@@ -20,7 +22,11 @@ enum Opts {
     #[structopt(name = "info")]
     Info,
     #[structopt(name = "backtrace")]
-    Backtrace,
+    Backtrace {
+        /// Show function indices and code offsets of the VM's call frames
+        #[structopt(long)]
+        raw: bool,
+    },
     #[structopt(name = "step-in")]
     StepIn,
     #[structopt(name = "step-over")]
@@ -42,10 +48,14 @@ impl<D: Debugger> Command<D> for ThreadCommand {
         "Commands for operating the thread."
     }
 
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
     fn run(
         &self,
         debugger: &mut D,
-        context: &CommandContext,
+        context: &mut CommandContext,
         args: Vec<&str>,
     ) -> Result<Option<CommandResult>> {
         let opts = Opts::from_iter_safe(args.clone())?;
@@ -59,27 +69,17 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                 let code_offset = current_inst.offset;
                 let output = if let Some(line_info) = context.sourcemap.find_line_info(code_offset)
                 {
-                    format!(
-                        "0x{:x} `{} at {}:{}:{}`",
-                        code_offset,
-                        frame_name,
-                        line_info.filepath,
-                        line_info
-                            .line
-                            .map(|l| format!("{}", l))
-                            .unwrap_or_else(|| "".to_string()),
-                        Into::<u64>::into(line_info.column)
-                    )
+                    format!("0x{:x} `{} at {}`", code_offset, frame_name, line_info)
                 } else {
                     format!("0x{:x} `{}`", code_offset, frame_name)
                 };
                 context.printer.println(&output);
             }
-            Opts::Backtrace => {
-                for (index, frame) in debugger.frame().iter().rev().enumerate() {
-                    let output = format!("{}: {}", index, demangle_symbol(frame));
-                    context.printer.println(&output);
-                }
+            Opts::Backtrace { raw: false } => {
+                display_backtrace(debugger, context.printer.as_ref());
+            }
+            Opts::Backtrace { raw: true } => {
+                display_raw_backtrace(debugger, context.printer.as_ref());
             }
             Opts::StepIn | Opts::StepOver => {
                 let style = match opts {
@@ -88,19 +88,22 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                     _ => panic!(),
                 };
                 let initial_line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
-                while {
-                    debugger.step(style)?;
+                loop {
+                    // Stop at breakpoints hit on the way, like in a callee stepped over
+                    match debugger.step(style)? {
+                        Signal::Next => {}
+                        Signal::Breakpoint | Signal::End => break,
+                    }
                     let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
-                    initial_line_info.filepath == line_info.filepath
-                        && initial_line_info.line == line_info.line
-                } {}
-                let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
-                display_source(line_info, context.printer.as_ref())?;
+                    if initial_line_info.filepath != line_info.filepath
+                        || initial_line_info.line != line_info.line
+                    {
+                        break;
+                    }
+                }
             }
             Opts::StepOut => {
                 debugger.step(StepStyle::Out)?;
-                let line_info = next_line_info(debugger, context.sourcemap.as_ref())?;
-                display_source(line_info, context.printer.as_ref())?;
             }
             Opts::StepInstIn | Opts::StepInstOver => {
                 let style = match opts {
@@ -109,9 +112,63 @@ impl<D: Debugger> Command<D> for ThreadCommand {
                     _ => panic!(),
                 };
                 debugger.step(style)?;
-                display_asm(debugger, context.printer.as_ref(), Some(4), true)?;
             }
         }
         Ok(None)
     }
 }
+
+pub fn display_backtrace<D: Debugger>(debugger: &D, printer: &dyn OutputPrinter) {
+    let names = debugger.frame();
+    let frames: Vec<_> = names
+        .iter()
+        .rev()
+        .map(|frame| demangle_symbol(frame))
+        .collect();
+    let text = frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| format!("{}: {}", index, frame))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let record = json!({
+        "frames": frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| json!({ "index": index, "function": frame }))
+            .collect::<Vec<_>>(),
+    });
+    printer.print_record(&text, &record);
+}
+
+fn display_raw_backtrace<D: Debugger>(debugger: &D, printer: &dyn OutputPrinter) {
+    let frames: Vec<_> = debugger.raw_frames().into_iter().rev().collect();
+    let text = frames
+        .iter()
+        .enumerate()
+        .map(|(index, frame)| {
+            let mut line = format!(
+                "{}: func[{}] 0x{:x}",
+                index, frame.func_index, frame.inst_offset
+            );
+            if let Some(ret_offset) = frame.ret_offset {
+                line += &format!(" (returns to 0x{:x})", ret_offset);
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let record = json!({
+        "frames": frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| json!({
+                "index": index,
+                "func_index": frame.func_index,
+                "inst_offset": frame.inst_offset,
+                "ret_offset": frame.ret_offset,
+            }))
+            .collect::<Vec<_>>(),
+    });
+    printer.print_record(&text, &record);
+}
@@ -0,0 +1,107 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::completion::CompletionTree;
+use super::debugger::{describe_error, Debugger, RunResult};
+use anyhow::{anyhow, Result};
+
+use structopt::StructOpt;
+
+pub struct UntilCommand {}
+
+impl UntilCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(StructOpt)]
+struct Opts {
+    /// Code offset of the instruction to run to
+    #[structopt(name = "ADDRESS", required_unless = "line")]
+    address: Option<String>,
+    /// Line in the current source file to run to
+    #[structopt(short, long, conflicts_with = "ADDRESS")]
+    line: Option<u64>,
+}
+
+impl<D: Debugger> Command<D> for UntilCommand {
+    fn name(&self) -> &'static str {
+        "until"
+    }
+
+    fn description(&self) -> &'static str {
+        "Continue until the given instruction or line is reached."
+    }
+
+    fn completion(&self) -> CompletionTree {
+        CompletionTree::from_app(&Opts::clap())
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let opts = Opts::from_iter_safe(args)?;
+        if !debugger.is_running() {
+            return Err(anyhow!("Process is not running"));
+        }
+        let (target, inst_offset) = if let Some(line) = opts.line {
+            let (insts, next_index) = debugger.instructions()?;
+            let current_index = if next_index == 0 { 0 } else { next_index - 1 };
+            let line_info = insts
+                .get(current_index)
+                .and_then(|inst| context.sourcemap.find_line_info(inst.offset))
+                .ok_or_else(|| anyhow!("No source location for the current instruction"))?;
+            let inst_offset = context
+                .sourcemap
+                .find_code_offset(&line_info.filepath, line)
+                .ok_or_else(|| anyhow!("No code found for {}:{}", line_info.filepath, line))?;
+            (format!("{}:{}", line_info.filepath, line), inst_offset)
+        } else if let Some(address) = opts.address {
+            let inst_offset = if address.starts_with("0x") {
+                let raw = address.trim_start_matches("0x");
+                usize::from_str_radix(raw, 16)?
+            } else {
+                address.parse::<usize>()?
+            };
+            (format!("0x{:x}", inst_offset), inst_offset)
+        } else {
+            return Err(anyhow!("no target option"));
+        };
+
+        debugger.set_temporary_breakpoint(Some(inst_offset));
+        let result = debugger.process();
+        let reached = debugger.temporary_breakpoint().is_none();
+        debugger.set_temporary_breakpoint(None);
+
+        let not_reached = format!("{} was not reached", target);
+        match result {
+            Ok(RunResult::Finish(values)) => {
+                context.printer.eprintln(&not_reached);
+                Ok(Some(CommandResult::ProcessFinish(values)))
+            }
+            // Only `process launch` sets a timeout
+            Ok(RunResult::Breakpoint) | Ok(RunResult::Timeout) => {
+                if reached {
+                    context.printer.println(&format!("Reached {}", target));
+                } else {
+                    context.printer.println("Hit breakpoint");
+                }
+                Ok(None)
+            }
+            Ok(RunResult::Trap(err)) => {
+                context.printer.eprintln(&not_reached);
+                let output = describe_error(&err, context);
+                context
+                    .printer
+                    .eprintln(&format!("Stopped at trap: {}", output));
+                Ok(None)
+            }
+            Err(err) => {
+                context.printer.eprintln(&not_reached);
+                Err(err)
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use super::command::{Command, CommandContext, CommandResult};
+use super::debugger::Debugger;
+use super::symbol::demangle_symbol;
+use anyhow::{anyhow, Result};
+
+pub struct WhereCommand {}
+
+impl WhereCommand {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl<D: Debugger> Command<D> for WhereCommand {
+    fn name(&self) -> &'static str {
+        "where"
+    }
+
+    fn description(&self) -> &'static str {
+        "Summarize the current function, instruction, source location and call depth."
+    }
+
+    fn run(
+        &self,
+        debugger: &mut D,
+        context: &mut CommandContext,
+        _args: Vec<&str>,
+    ) -> Result<Option<CommandResult>> {
+        let frames = debugger.frame();
+        let frame_name = frames
+            .last()
+            .ok_or_else(|| anyhow!("Process is not running"))?;
+        let (insts, next_index) = debugger.instructions()?;
+        let current_index = if next_index == 0 { 0 } else { next_index - 1 };
+        let current_inst = &insts[current_index];
+        let mut output = format!(
+            "{} 0x{:x}: {:?}",
+            demangle_symbol(frame_name),
+            current_inst.offset,
+            current_inst.kind
+        );
+        if let Some(line_info) = context.sourcemap.find_line_info(current_inst.offset) {
+            output += &format!(" at {}", line_info);
+        }
+        output += &format!(" (depth {})", frames.len());
+        context.printer.println(&output);
+        Ok(None)
+    }
+}
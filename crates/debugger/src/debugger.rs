@@ -1,17 +1,24 @@
 use crate::commands::debugger::{self, Debugger, DebuggerOpts, RawHostModule, RunResult};
+use crate::commands::sourcemap::SourceMap;
+use crate::commands::symbol::{is_rust_panic_symbol, is_rust_std_symbol};
+use crate::trace::{Tracer, TRACE_STACK_DEPTH};
 use anyhow::{anyhow, Context, Result};
 use log::{trace, warn};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::{cell::RefCell, usize};
+use std::time::{Duration, Instant};
+use std::{
+    cell::{Cell, RefCell},
+    usize,
+};
 use wasminspect_vm::{
-    CallFrame, DefinedModuleInstance, Executor, FuncAddr, FunctionInstance, InstIndex, Instruction,
-    Interceptor, MemoryAddr, ModuleIndex, ProgramCounter, Signal, Store, Trap, WasmValue,
+    CallFrame, DefinedModuleInstance, ExecutableFuncAddr, Executor, FuncAddr, FunctionInstance,
+    HostMemory, InstIndex, Instruction, Interceptor, Label, MemoryAddr, ModuleIndex,
+    ProgramCounter, Signal, StackValue, Store, Trap, WasmValue,
 };
 use wasminspect_wasi::instantiate_wasi;
-use wasmparser::WasmFeatures;
 
 type RawModule = Vec<u8>;
 
@@ -33,24 +40,133 @@ pub struct MainDebugger {
     config: wasminspect_vm::Config,
     breakpoints: Breakpoints,
     is_interrupted: Arc<AtomicBool>,
+    deadline: Cell<Option<Instant>>,
+    /// Whether the last stop was for the deadline rather than a breakpoint
+    timed_out: Cell<bool>,
+    executed_insts: Cell<usize>,
+    /// Whether to count executed instructions into `profile`
+    profiling: bool,
+    /// Executed instruction count per function
+    profile: RefCell<HashMap<ExecutableFuncAddr, u64>>,
+    /// Whether to record executed instruction offsets into `coverage`
+    recording_coverage: bool,
+    /// Executed instruction offsets per function
+    coverage: RefCell<HashMap<ExecutableFuncAddr, BTreeSet<usize>>>,
+    /// Code offset of the instruction being executed, to locate traps
+    current_inst_offset: Cell<usize>,
+    /// Whether the run trapped, leaving the pc at the faulting instruction
+    trapped: Cell<bool>,
+    /// Instruction trace, only recorded when set
+    tracer: RefCell<Option<Tracer>>,
+    /// Progress reports of long runs, only made when set
+    progress: RefCell<Option<ProgressReporter>>,
+    stop_count: Cell<usize>,
+    /// Breakpoint hits to run through before stopping, only set while
+    /// `process_skipping` runs
+    skip_breakpoints: Cell<u64>,
+}
+
+/// Called with the executed instruction count and the current function name
+pub type ProgressCallback = Box<dyn FnMut(u64, &str)>;
+
+/// Calls `callback` with the executed instruction count and the current
+/// function every `interval` instructions
+struct ProgressReporter {
+    interval: u64,
+    executed: u64,
+    callback: ProgressCallback,
 }
 
+/// Checking the clock on every instruction is too slow, so the deadline is
+/// only checked once per this many instructions
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
 #[derive(Default)]
 struct Breakpoints {
     function_map: HashMap<String, debugger::Breakpoint>,
+    /// Function entry breakpoints by index in the main module
+    func_index_map: HashMap<u32, debugger::Breakpoint>,
     inst_map: HashMap<usize, debugger::Breakpoint>,
+    /// Source line breakpoints and the code offset they currently resolve to
+    line_map: BTreeMap<(String, u64), Option<usize>>,
+    /// One-shot breakpoint which is cleared once hit
+    temporary: Cell<Option<usize>>,
+    /// Hit and ignore counts, kept until the breakpoint is deleted
+    hits: RefCell<HashMap<debugger::Breakpoint, debugger::BreakpointHits>>,
 }
 
 impl Breakpoints {
-    fn should_break_func(&self, name: &str) -> bool {
-        // FIXME
-        self.function_map
-            .keys()
-            .any(|k| name.contains(Clone::clone(&k)))
+    /// Whether to stop on entry to the function at `exec_addr` named `name`.
+    /// Besides names, breakpoints refer to functions by their index or one of
+    /// their export names in the main module.
+    fn should_break_func(
+        &self,
+        name: &str,
+        exec_addr: ExecutableFuncAddr,
+        main_module_index: Option<ModuleIndex>,
+        store: &Store,
+    ) -> bool {
+        let resolves_to =
+            |addr: FuncAddr| store.func(addr).map(|(_, addr)| addr) == Some(exec_addr);
+        let main_module = main_module_index.and_then(|index| store.module(index).defined());
+        let by_name = self.function_map.iter().filter(|(k, _)| {
+            // FIXME
+            name.contains(k.as_str())
+                || main_module.map_or(
+                    false,
+                    |module| matches!(module.exported_func(k), Ok(Some(addr)) if resolves_to(addr)),
+                )
+        });
+        let by_index = self.func_index_map.iter().filter(|(index, _)| {
+            main_module_index.map_or(false, |module_index| {
+                resolves_to(FuncAddr::new_unsafe(module_index, **index as usize))
+            })
+        });
+        self.hit_any(
+            by_name
+                .map(|(_, breakpoint)| breakpoint.clone())
+                .chain(by_index.map(|(_, breakpoint)| breakpoint.clone())),
+        )
     }
 
     fn should_break_inst(&self, inst: &Instruction) -> bool {
-        self.inst_map.contains_key(&inst.offset)
+        let lines = self
+            .line_map
+            .iter()
+            .filter(|(_, offset)| **offset == Some(inst.offset))
+            .map(|((filepath, line), _)| debugger::Breakpoint::Line {
+                filepath: filepath.clone(),
+                line: *line,
+            });
+        self.hit_any(
+            self.inst_map
+                .get(&inst.offset)
+                .cloned()
+                .into_iter()
+                .chain(lines),
+        )
+    }
+
+    /// Count a hit of each of `breakpoints` and tell whether any of them has
+    /// used up its ignore count
+    fn hit_any(&self, breakpoints: impl Iterator<Item = debugger::Breakpoint>) -> bool {
+        let mut hits = self.hits.borrow_mut();
+        let mut should_break = false;
+        for breakpoint in breakpoints {
+            let hits = hits.entry(breakpoint).or_default();
+            hits.hit_count += 1;
+            should_break |= hits.hit_count > hits.ignore_count;
+        }
+        should_break
+    }
+
+    fn hit_temporary(&self, inst: &Instruction) -> bool {
+        if self.temporary.get() == Some(inst.offset) {
+            self.temporary.set(None);
+            true
+        } else {
+            false
+        }
     }
 
     fn insert(&mut self, breakpoint: debugger::Breakpoint) {
@@ -58,35 +174,119 @@ impl Breakpoints {
             debugger::Breakpoint::Function { name } => {
                 self.function_map.insert(name.clone(), breakpoint);
             }
+            debugger::Breakpoint::FunctionIndex { index } => {
+                self.func_index_map.insert(*index, breakpoint);
+            }
             debugger::Breakpoint::Instruction { inst_offset } => {
                 self.inst_map.insert(*inst_offset, breakpoint);
             }
+            debugger::Breakpoint::Line { filepath, line } => {
+                self.line_map.insert((filepath.clone(), *line), None);
+            }
+        }
+    }
+
+    fn list(&self) -> Vec<debugger::Breakpoint> {
+        let mut names: Vec<_> = self.function_map.keys().collect();
+        names.sort();
+        let mut indices: Vec<_> = self.func_index_map.keys().collect();
+        indices.sort();
+        let mut offsets: Vec<_> = self.inst_map.keys().collect();
+        offsets.sort();
+        let lines = self
+            .line_map
+            .keys()
+            .map(|(filepath, line)| debugger::Breakpoint::Line {
+                filepath: filepath.clone(),
+                line: *line,
+            });
+        names
+            .into_iter()
+            .map(|name| self.function_map[name].clone())
+            .chain(
+                indices
+                    .into_iter()
+                    .map(|index| self.func_index_map[index].clone()),
+            )
+            .chain(
+                offsets
+                    .into_iter()
+                    .map(|offset| self.inst_map[offset].clone()),
+            )
+            .chain(lines)
+            .collect()
+    }
+
+    fn remove(&mut self, breakpoint: &debugger::Breakpoint) {
+        self.hits.borrow_mut().remove(breakpoint);
+        match breakpoint {
+            debugger::Breakpoint::Function { name } => {
+                self.function_map.remove(name);
+            }
+            debugger::Breakpoint::FunctionIndex { index } => {
+                self.func_index_map.remove(index);
+            }
+            debugger::Breakpoint::Instruction { inst_offset } => {
+                self.inst_map.remove(inst_offset);
+            }
+            debugger::Breakpoint::Line { filepath, line } => {
+                self.line_map.remove(&(filepath.clone(), *line));
+            }
         }
     }
 }
 
-impl MainDebugger {
-    pub fn load_main_module(&mut self, module: &[u8], name: String) -> Result<()> {
-        if let Err(err) = wasmparser::validate(module) {
-            warn!("{}", err);
-            return Err(err.into());
+/// Offsets of all instructions in the code section, relative to its start
+/// as `Instruction::offset` is
+fn instruction_offsets(module: &[u8]) -> Result<HashSet<usize>> {
+    let mut offsets = HashSet::new();
+    let mut base_offset = 0;
+    for payload in wasmparser::Parser::new(0).parse_all(module) {
+        match payload? {
+            wasmparser::Payload::CodeSectionStart { range, .. } => {
+                base_offset = range.start;
+            }
+            wasmparser::Payload::CodeSectionEntry(body) => {
+                let mut reader = body.get_operators_reader()?;
+                while !reader.eof() {
+                    let (_, offset) = reader.read_with_offset()?;
+                    offsets.insert(offset - base_offset);
+                }
+            }
+            _ => continue,
         }
-        self.main_module = Some((module.to_vec(), name));
-        Ok(())
     }
+    Ok(offsets)
+}
 
-    pub fn new(preopen_dirs: Vec<(String, String)>, envs: Vec<(String, String)>) -> Result<Self> {
+impl MainDebugger {
+    pub fn new(
+        preopen_dirs: Vec<(String, String)>,
+        envs: Vec<(String, String)>,
+        config: wasminspect_vm::Config,
+    ) -> Result<Self> {
         let is_interrupted = Arc::new(AtomicBool::new(false));
         signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&is_interrupted))?;
         Ok(Self {
             instance: None,
             main_module: None,
             opts: DebuggerOpts::default(),
-            config: wasminspect_vm::Config {
-                features: WasmFeatures::default(),
-            },
+            config,
             breakpoints: Default::default(),
             is_interrupted,
+            deadline: Cell::new(None),
+            timed_out: Cell::new(false),
+            executed_insts: Cell::new(0),
+            profiling: false,
+            profile: Default::default(),
+            recording_coverage: false,
+            coverage: Default::default(),
+            current_inst_offset: Cell::new(0),
+            trapped: Cell::new(false),
+            tracer: RefCell::new(None),
+            progress: RefCell::new(None),
+            stop_count: Cell::new(0),
+            skip_breakpoints: Cell::new(0),
             preopen_dirs,
             envs,
         })
@@ -104,6 +304,179 @@ impl MainDebugger {
         }
     }
 
+    fn is_deadline_exceeded(&self) -> bool {
+        let deadline = match self.deadline.get() {
+            Some(deadline) => deadline,
+            None => return false,
+        };
+        let executed_insts = self.executed_insts.get().wrapping_add(1);
+        self.executed_insts.set(executed_insts);
+        executed_insts % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline
+    }
+
+    fn trap_error(&self, message: String, trap: &Trap) -> anyhow::Error {
+        self.trapped.set(true);
+        debugger::TrapError {
+            message,
+            code_offset: self.current_inst_offset.get(),
+            callee_offset: trap.callee().and_then(|addr| self.body_offset(addr)),
+            panic: match trap {
+                Trap::Unreachable => self.rust_panic(),
+                _ => None,
+            },
+        }
+        .into()
+    }
+
+    /// The Rust panic which led to the current `unreachable`, found from the
+    /// standard library frames on top of the stack
+    fn rust_panic(&self) -> Option<debugger::RustPanic> {
+        let names = self.frame();
+        let origin = names.iter().rposition(|name| !is_rust_std_symbol(name))?;
+        let library = &names[origin + 1..];
+        if !library.iter().any(|name| is_rust_panic_symbol(name)) {
+            return None;
+        }
+        Some(debugger::RustPanic {
+            function: names[origin].clone(),
+            code_offset: self.raw_frames()[origin].inst_offset,
+            entry: library[0].clone(),
+        })
+    }
+
+    /// Results which don't match the function type, reported like a trap at
+    /// the final `end`, which is still the current instruction
+    fn return_value_error(&self, message: String) -> anyhow::Error {
+        debugger::TrapError {
+            message: format!("Function exec failure {}", message),
+            code_offset: self.current_inst_offset.get(),
+            callee_offset: None,
+            panic: None,
+        }
+        .into()
+    }
+
+    /// Code offset of the body of the function at `addr`, if the main
+    /// module defines it
+    fn body_offset(&self, addr: ExecutableFuncAddr) -> Option<usize> {
+        let instance = self.instance.as_ref()?;
+        let func = instance.store.func_global(addr).defined()?;
+        if func.module_index() != instance.main_module_index {
+            return None;
+        }
+        func.instructions().first().map(|inst| inst.offset)
+    }
+
+    pub fn config(&self) -> &wasminspect_vm::Config {
+        &self.config
+    }
+
+    /// Write a trace of every executed instruction to `writer`, or stop
+    /// tracing with `None`. See `crate::trace` for the format.
+    pub fn set_trace(&mut self, writer: Option<Box<dyn std::io::Write>>) {
+        self.flush_trace();
+        *self.tracer.get_mut() = writer.map(Tracer::new);
+    }
+
+    /// Call `callback` with the number of instructions executed so far and
+    /// the current function name every `interval` instructions, or stop
+    /// reporting with `None`
+    pub fn set_progress(&mut self, progress: Option<(u64, ProgressCallback)>) {
+        *self.progress.get_mut() = progress.map(|(interval, callback)| ProgressReporter {
+            interval: interval.max(1),
+            executed: 0,
+            callback,
+        });
+    }
+
+    fn report_progress(&self, executor: &Executor) {
+        let mut progress = self.progress.borrow_mut();
+        let progress = match progress.as_mut() {
+            Some(progress) => progress,
+            None => return,
+        };
+        progress.executed += 1;
+        if progress.executed % progress.interval != 0 {
+            return;
+        }
+        if let Ok(store) = self.store() {
+            let func = store.func_global(executor.pc.exec_addr());
+            (progress.callback)(progress.executed, func.name());
+        }
+    }
+
+    fn flush_trace(&self) {
+        if let Some(tracer) = self.tracer.borrow_mut().as_mut() {
+            if let Err(err) = tracer.flush() {
+                warn!("Failed to write trace: {}", err);
+            }
+        }
+    }
+
+    fn record_trace(&self, inst: &Instruction, executor: &Executor) {
+        let mut tracer = self.tracer.borrow_mut();
+        if let Some(t) = tracer.as_mut() {
+            if let Err(err) = t.record(inst, executor.stack.peek_top_values(TRACE_STACK_DEPTH)) {
+                warn!("Failed to write trace, stop tracing: {}", err);
+                *tracer = None;
+            }
+        }
+    }
+
+    fn process_until_stop(&self) -> Result<RunResult> {
+        let store = self.store()?;
+        let executor = self.executor()?;
+        loop {
+            let result = executor
+                .borrow_mut()
+                .execute_step(store, self, &self.config);
+            match result {
+                Ok(Signal::Next) => continue,
+                Ok(Signal::Breakpoint) if self.timed_out.replace(false) => {
+                    return Ok(RunResult::Timeout)
+                }
+                Ok(Signal::Breakpoint) => return Ok(RunResult::Breakpoint),
+                Ok(Signal::End) => {
+                    let pc = executor.borrow().pc;
+                    let func = store.func_global(pc.exec_addr());
+                    // Popping the results consumes them, so keep the stack
+                    // to show it if they don't match the function type
+                    let stack = executor.borrow().stack.clone();
+                    let results = executor.borrow_mut().pop_result(func.ty().returns.to_vec());
+                    match results {
+                        Ok(results) => return Ok(RunResult::Finish(results)),
+                        Err(err) => {
+                            executor.borrow_mut().stack = stack;
+                            let err = self.return_value_error(err.to_string());
+                            if self.opts.break_on_trap {
+                                return Ok(RunResult::Trap(err));
+                            }
+                            return Err(err);
+                        }
+                    }
+                }
+                Err(err) => {
+                    let err = self.trap_error(format!("Function exec failure {}", err), &err);
+                    if self.opts.break_on_trap {
+                        return Ok(RunResult::Trap(err));
+                    }
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// The first memory of the main module, if it has any
+    pub fn main_memory(&self) -> Result<Option<Rc<RefCell<HostMemory>>>> {
+        let instance = self.instance()?;
+        let store = &instance.store;
+        if store.memory_count(instance.main_module_index) == 0 {
+            return Ok(None);
+        }
+        let addr = MemoryAddr::new_unsafe(instance.main_module_index, 0);
+        Ok(Some(store.memory(addr)))
+    }
+
     fn executor(&self) -> Result<Rc<RefCell<Executor>>> {
         let instance = self.instance()?;
         if let Some(ref executor) = instance.executor {
@@ -178,6 +551,18 @@ impl MainDebugger {
                 let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
                 let executor = Rc::new(RefCell::new(Executor::new(frame, ret_types.len(), pc)));
                 instance.executor = Some(executor);
+                self.trapped.set(false);
+                // `invoke_func` only sees calls, so check the entry function here
+                let should_break = self.breakpoints.should_break_func(
+                    func.name(),
+                    exec_addr,
+                    Some(instance.main_module_index),
+                    &instance.store,
+                );
+                if should_break && !self.skip_breakpoint() {
+                    self.stop_count.set(self.stop_count.get() + 1);
+                    return Ok(debugger::RunResult::Breakpoint);
+                }
                 Ok(self.process()?)
             }
         }
@@ -189,19 +574,193 @@ impl debugger::Debugger for MainDebugger {
         self.opts.clone()
     }
     fn set_opts(&mut self, opts: DebuggerOpts) {
+        self.config = wasminspect_vm::ConfigBuilder::from(self.config.clone())
+            .poison_locals(opts.poison_locals)
+            .build();
         self.opts = opts
     }
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.deadline
+            .set(timeout.map(|timeout| Instant::now() + timeout));
+        self.timed_out.set(false);
+    }
+    fn load_main_module(&mut self, module: &[u8], name: String) -> Result<()> {
+        if let Err(err) = wasminspect_vm::validate(module, &self.config) {
+            warn!("{}", err);
+            return Err(err.into());
+        }
+        self.main_module = Some((module.to_vec(), name));
+        Ok(())
+    }
+    fn main_module_source(&self) -> Option<(&[u8], &str)> {
+        self.main_module
+            .as_ref()
+            .map(|(bytes, name)| (bytes.as_slice(), name.as_str()))
+    }
     fn instructions(&self) -> Result<(&[Instruction], usize)> {
         let executor = self.executor()?;
         let executor = executor.borrow();
         let insts = executor.current_func_insts(self.store()?)?;
-        Ok((insts, executor.pc.inst_index().0 as usize))
+        let next_index = executor.pc.inst_index().0 as usize;
+        // Commands show the instruction before the next one as the current
+        // one, which after a trap is the faulting instruction itself
+        if self.trapped.get() {
+            Ok((insts, next_index + 1))
+        } else {
+            Ok((insts, next_index))
+        }
     }
 
     fn set_breakpoint(&mut self, breakpoint: debugger::Breakpoint) {
         self.breakpoints.insert(breakpoint)
     }
 
+    fn delete_breakpoint(&mut self, breakpoint: debugger::Breakpoint) {
+        self.breakpoints.remove(&breakpoint)
+    }
+
+    fn breakpoints(&self) -> Vec<debugger::Breakpoint> {
+        self.breakpoints.list()
+    }
+
+    fn set_ignore_count(&mut self, breakpoint: &debugger::Breakpoint, count: u64) {
+        let mut hits = self.breakpoints.hits.borrow_mut();
+        hits.entry(breakpoint.clone()).or_default().ignore_count = count;
+    }
+
+    fn breakpoint_hits(&self, breakpoint: &debugger::Breakpoint) -> debugger::BreakpointHits {
+        let hits = self.breakpoints.hits.borrow();
+        hits.get(breakpoint).copied().unwrap_or_default()
+    }
+
+    fn set_temporary_breakpoint(&mut self, inst_offset: Option<usize>) {
+        self.breakpoints.temporary.set(inst_offset)
+    }
+
+    fn temporary_breakpoint(&self) -> Option<usize> {
+        self.breakpoints.temporary.get()
+    }
+
+    fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+    }
+
+    fn is_profiling(&self) -> bool {
+        self.profiling
+    }
+
+    fn profile(&self) -> Result<Vec<(String, u64)>> {
+        let store = self.store()?;
+        let mut profile: Vec<_> = self
+            .profile
+            .borrow()
+            .iter()
+            .map(|(addr, count)| (store.func_global(*addr).name().clone(), *count))
+            .collect();
+        profile.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(profile)
+    }
+
+    fn reset_profile(&mut self) {
+        self.profile.borrow_mut().clear();
+    }
+
+    fn set_recording_coverage(&mut self, enabled: bool) {
+        self.recording_coverage = enabled;
+    }
+
+    fn is_recording_coverage(&self) -> bool {
+        self.recording_coverage
+    }
+
+    fn coverage(&self) -> Result<Vec<debugger::FunctionCoverage>> {
+        let instance = self
+            .instance
+            .as_ref()
+            .ok_or_else(|| anyhow!("No instance"))?;
+        let coverage = self.coverage.borrow();
+        let mut result = Vec::new();
+        for addr in instance.store.module_funcs(instance.main_module_index) {
+            let func = match instance.store.func_global(addr).defined() {
+                Some(func) if func.module_index() == instance.main_module_index => func,
+                _ => continue,
+            };
+            result.push(debugger::FunctionCoverage {
+                name: func.name().clone(),
+                covered_offsets: coverage
+                    .get(&addr)
+                    .map(|offsets| offsets.iter().cloned().collect())
+                    .unwrap_or_default(),
+                instruction_count: func.instructions().len(),
+            });
+        }
+        Ok(result)
+    }
+
+    fn resolve_breakpoints(&mut self, sourcemap: &dyn SourceMap) -> Vec<debugger::Breakpoint> {
+        let mut unresolved = vec![];
+        for ((filepath, line), offset) in self.breakpoints.line_map.iter_mut() {
+            *offset = sourcemap.find_code_offset(filepath, *line);
+            if offset.is_none() {
+                unresolved.push(debugger::Breakpoint::Line {
+                    filepath: filepath.clone(),
+                    line: *line,
+                });
+            }
+        }
+        let valid_offsets = match &self.main_module {
+            Some((module, _)) => match instruction_offsets(module) {
+                Ok(offsets) => offsets,
+                Err(err) => {
+                    warn!("Failed to collect instruction offsets: {}", err);
+                    return unresolved;
+                }
+            },
+            None => return unresolved,
+        };
+        let mut stale_offsets: Vec<_> = self
+            .breakpoints
+            .inst_map
+            .keys()
+            .filter(|offset| !valid_offsets.contains(offset))
+            .collect();
+        stale_offsets.sort();
+        unresolved.extend(
+            stale_offsets
+                .into_iter()
+                .map(|offset| self.breakpoints.inst_map[offset].clone()),
+        );
+        unresolved
+    }
+
+    fn stack_entries(&self) -> Vec<debugger::StackEntry> {
+        let (executor, store) = match (self.executor(), self.store()) {
+            (Ok(executor), Ok(store)) => (executor, store),
+            _ => return Vec::new(),
+        };
+        let executor = executor.borrow();
+        executor
+            .stack
+            .peek_entries()
+            .iter()
+            .map(|entry| match entry {
+                StackValue::Value(value) => debugger::StackEntry::Value(*value),
+                StackValue::Label(label) => debugger::StackEntry::Label {
+                    kind: match label {
+                        Label::If { .. } => "if",
+                        Label::Block { .. } => "block",
+                        Label::Loop { .. } => "loop",
+                        Label::Return { .. } => "return",
+                    },
+                    arity: label.arity(),
+                },
+                StackValue::Activation(frame) => debugger::StackEntry::Frame {
+                    name: store.func_global(frame.exec_addr).name().clone(),
+                },
+            })
+            .collect()
+    }
+
     fn stack_values(&self) -> Vec<WasmValue> {
         if let Ok(ref executor) = self.executor() {
             let executor = executor.borrow();
@@ -224,7 +783,11 @@ impl debugger::Debugger for MainDebugger {
     fn locals(&self) -> Vec<WasmValue> {
         if let Ok(ref executor) = self.executor() {
             let executor = executor.borrow();
-            executor.stack.current_frame().unwrap().locals.clone()
+            executor
+                .stack
+                .current_frame()
+                .map(|frame| frame.locals.clone())
+                .unwrap_or_default()
         } else {
             Vec::new()
         }
@@ -236,7 +799,7 @@ impl debugger::Debugger for MainDebugger {
             return None;
         };
         let executor = executor.borrow();
-        let frame = executor.stack.current_frame().unwrap();
+        let frame = executor.stack.current_frame().ok()?;
         let func = match self.store() {
             Ok(store) => store.func_global(frame.exec_addr),
             Err(_) => return None,
@@ -247,6 +810,38 @@ impl debugger::Debugger for MainDebugger {
             argument_count: func.ty().params.len(),
         })
     }
+    fn raw_frames(&self) -> Vec<debugger::RawFrame> {
+        let (executor, store) = match (self.executor(), self.store()) {
+            (Ok(executor), Ok(store)) => (executor, store),
+            _ => return Vec::new(),
+        };
+        let executor = executor.borrow();
+        let frames = executor.stack.peek_frames();
+        let offset_at = |pc: &ProgramCounter, inst_index: u32| {
+            let func = store.func_global(pc.exec_addr()).defined().unwrap();
+            func.instructions()[inst_index as usize].offset
+        };
+        frames
+            .iter()
+            .enumerate()
+            .map(|(depth, frame)| {
+                // Callers are at the call right before where their callee returns to
+                let inst_offset = match frames.get(depth + 1).and_then(|callee| callee.ret_pc) {
+                    Some(ret_pc) => offset_at(&ret_pc, ret_pc.inst_index().0 - 1),
+                    None => offset_at(&executor.pc, executor.pc.inst_index().0),
+                };
+                debugger::RawFrame {
+                    func_index: store
+                        .module_funcs(frame.module_index)
+                        .iter()
+                        .position(|addr| *addr == frame.exec_addr)
+                        .unwrap_or_default(),
+                    inst_offset,
+                    ret_offset: frame.ret_pc.map(|pc| offset_at(&pc, pc.inst_index().0)),
+                }
+            })
+            .collect()
+    }
     fn frame(&self) -> Vec<String> {
         let instance = if let Ok(instance) = self.instance() {
             instance
@@ -266,13 +861,14 @@ impl debugger::Debugger for MainDebugger {
             .collect();
     }
     fn memory(&self) -> Result<Vec<u8>> {
-        let instance = self.instance()?;
-        let store = &instance.store;
-        if store.memory_count(instance.main_module_index) == 0 {
-            return Ok(vec![]);
+        match self.main_memory()? {
+            Some(memory) => Ok(memory.borrow().raw_data().to_vec()),
+            None => Ok(vec![]),
         }
-        let addr = MemoryAddr::new_unsafe(instance.main_module_index, 0);
-        Ok(store.memory(addr).borrow().raw_data().to_vec())
+    }
+
+    fn main_module_index(&self) -> Result<ModuleIndex> {
+        Ok(self.instance()?.main_module_index)
     }
 
     fn is_running(&self) -> bool {
@@ -282,27 +878,34 @@ impl debugger::Debugger for MainDebugger {
     fn step(&self, style: debugger::StepStyle) -> Result<Signal> {
         let store = self.store()?;
         let executor = self.executor()?;
+        self.stop_count.set(self.stop_count.get() + 1);
         use debugger::StepStyle::*;
 
+        // Checked after every instruction of a callee, so it must not walk the stack
         fn frame_depth(executor: &Executor) -> usize {
-            executor.stack.peek_frames().len()
+            executor.stack.frame_depth()
         }
         match style {
-            InstIn => {
-                return Ok(executor
-                    .borrow_mut()
-                    .execute_step(store, self, &self.config)?)
-            }
+            InstIn => executor
+                .borrow_mut()
+                .execute_step(store, self, &self.config)
+                .map_err(|err| self.trap_error(err.to_string(), &err)),
             InstOver => {
                 let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal =
-                    executor
-                        .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
+                let mut last_signal = executor
+                    .borrow_mut()
+                    .execute_step(store, self, &self.config)
+                    .map_err(|err| self.trap_error(err.to_string(), &err))?;
+                // A breakpoint on the callee itself stops right at its entry
+                if let Signal::Breakpoint = last_signal {
+                    return Ok(last_signal);
+                }
+                // Run the callee to completion without stopping in it
                 while initial_frame_depth < frame_depth(&executor.borrow()) {
                     last_signal = executor
                         .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
+                        .execute_step(store, self, &self.config)
+                        .map_err(|err| self.trap_error(err.to_string(), &err))?;
                     if let Signal::Breakpoint = last_signal {
                         return Ok(last_signal);
                     }
@@ -311,14 +914,15 @@ impl debugger::Debugger for MainDebugger {
             }
             Out => {
                 let initial_frame_depth = frame_depth(&executor.borrow());
-                let mut last_signal =
-                    executor
-                        .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
+                let mut last_signal = executor
+                    .borrow_mut()
+                    .execute_step(store, self, &self.config)
+                    .map_err(|err| self.trap_error(err.to_string(), &err))?;
                 while initial_frame_depth <= frame_depth(&executor.borrow()) {
                     last_signal = executor
                         .borrow_mut()
-                        .execute_step(store, self, &self.config)?;
+                        .execute_step(store, self, &self.config)
+                        .map_err(|err| self.trap_error(err.to_string(), &err))?;
                     if let Signal::Breakpoint = last_signal {
                         return Ok(last_signal);
                     }
@@ -329,26 +933,21 @@ impl debugger::Debugger for MainDebugger {
     }
 
     fn process(&self) -> Result<RunResult> {
-        let store = self.store()?;
-        let executor = self.executor()?;
-        loop {
-            let result = executor
-                .borrow_mut()
-                .execute_step(store, self, &self.config);
-            match result {
-                Ok(Signal::Next) => continue,
-                Ok(Signal::Breakpoint) => return Ok(RunResult::Breakpoint),
-                Ok(Signal::End) => {
-                    let pc = executor.borrow().pc;
-                    let func = store.func_global(pc.exec_addr());
-                    let results = executor
-                        .borrow_mut()
-                        .pop_result(func.ty().returns.to_vec())?;
-                    return Ok(RunResult::Finish(results));
-                }
-                Err(err) => return Err(anyhow!("Function exec failure {}", err)),
-            }
-        }
+        self.stop_count.set(self.stop_count.get() + 1);
+        let result = self.process_until_stop();
+        self.flush_trace();
+        result
+    }
+
+    fn process_skipping(&self, skip: u64) -> Result<RunResult> {
+        self.skip_breakpoints.set(skip);
+        let result = self.process();
+        self.skip_breakpoints.set(0);
+        result
+    }
+
+    fn stop_count(&self) -> usize {
+        self.stop_count.get()
     }
 
     fn run(&mut self, name: Option<&str>, args: Vec<WasmValue>) -> Result<debugger::RunResult> {
@@ -412,7 +1011,10 @@ impl debugger::Debugger for MainDebugger {
         store.load_host_module("wasi_snapshot_preview1".to_string(), wasi_snapshot_preview);
         store.load_host_module("wasi_unstable".to_string(), wasi_unstable);
 
-        let main_module_index = store.load_module(None, main_module)?;
+        let main_module_index = store.load_module_with_config(None, main_module, &self.config)?;
+        // Function addresses are only valid for the store they came from
+        self.profile.borrow_mut().clear();
+        self.coverage.borrow_mut().clear();
 
         self.instance = Some(Instance {
             main_module_index,
@@ -423,27 +1025,68 @@ impl debugger::Debugger for MainDebugger {
     }
 }
 
+impl MainDebugger {
+    /// Whether to run through a breakpoint hit, counting it as skipped
+    fn skip_breakpoint(&self) -> bool {
+        let skip = self.skip_breakpoints.get();
+        if skip == 0 {
+            return false;
+        }
+        self.skip_breakpoints.set(skip - 1);
+        true
+    }
+}
+
 impl Interceptor for MainDebugger {
-    fn invoke_func(
-        &self,
-        name: &str,
-        _executor: &Executor,
-        _store: &Store,
-    ) -> Result<Signal, Trap> {
+    fn invoke_func(&self, name: &str, executor: &Executor, store: &Store) -> Result<Signal, Trap> {
         trace!("Invoke function '{}'", name);
-        if self.breakpoints.should_break_func(name) {
+        let main_module_index = self
+            .instance()
+            .ok()
+            .map(|instance| instance.main_module_index);
+        let should_break = self.breakpoints.should_break_func(
+            name,
+            executor.pc.exec_addr(),
+            main_module_index,
+            store,
+        );
+        if should_break && !self.skip_breakpoint() {
             Ok(Signal::Breakpoint)
         } else {
             Ok(Signal::Next)
         }
     }
 
-    fn execute_inst(&self, inst: &Instruction) -> Result<Signal, Trap> {
-        if self.breakpoints.should_break_inst(inst) {
+    fn execute_inst(&self, inst: &Instruction, executor: &Executor) -> Result<Signal, Trap> {
+        self.current_inst_offset.set(inst.offset);
+        self.record_trace(inst, executor);
+        self.report_progress(executor);
+        if self.profiling {
+            *self
+                .profile
+                .borrow_mut()
+                .entry(executor.pc.exec_addr())
+                .or_default() += 1;
+        }
+        if self.recording_coverage {
+            self.coverage
+                .borrow_mut()
+                .entry(executor.pc.exec_addr())
+                .or_default()
+                .insert(inst.offset);
+        }
+        // Count breakpoint hits even when the temporary one stops here too
+        let should_break = self.breakpoints.should_break_inst(inst);
+        // Stepping relies on the temporary breakpoint, so it's never skipped
+        if self.breakpoints.hit_temporary(inst) || (should_break && !self.skip_breakpoint()) {
             Ok(Signal::Breakpoint)
         } else if self.is_interrupted.swap(false, Ordering::Relaxed) {
             println!("Interrupted by signal");
             Ok(Signal::Breakpoint)
+        } else if self.is_deadline_exceeded() {
+            self.deadline.set(None);
+            self.timed_out.set(true);
+            Ok(Signal::Breakpoint)
         } else {
             Ok(Signal::Next)
         }
@@ -452,4 +1095,10 @@ impl Interceptor for MainDebugger {
     fn after_store(&self, _addr: usize, _bytes: &[u8]) -> Result<Signal, Trap> {
         Ok(Signal::Next)
     }
+
+    fn uninitialized_local(&self, local_index: u32, _executor: &Executor) -> Result<Signal, Trap> {
+        // Not on stdout, which carries DAP messages and `--output json`
+        warn!("Local {} was read before it was ever written", local_index);
+        Ok(Signal::Breakpoint)
+    }
 }
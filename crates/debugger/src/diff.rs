@@ -0,0 +1,237 @@
+//! Differential execution of two runs
+//!
+//! Both runs execute in lockstep, recording what the instruction trace
+//! records for each step, until they stop matching. This finds where two
+//! inputs, or two builds of the same program, first behave differently.
+
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use wasminspect_vm::{
+    CallFrame, Config, Executor, FuncAddr, InstIndex, Instruction, Interceptor, ProgramCounter,
+    Signal, Store, Trap, WasmInstance, WasmValue,
+};
+use wasmparser::Type;
+
+/// One side of a differential run, calling `func_addr` of `instance` with `args`
+pub struct Run {
+    pub instance: WasmInstance,
+    pub func_addr: FuncAddr,
+    pub args: Vec<WasmValue>,
+}
+
+/// A point in a run where the other run did something else
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunState {
+    /// About to execute the instruction at code offset `pc`
+    Executing {
+        pc: usize,
+        op: String,
+        /// Operand stack of all frames, top first
+        stack: Vec<WasmValue>,
+    },
+    Finished(Vec<WasmValue>),
+    Trapped(String),
+}
+
+/// The first step at which two runs differ
+#[derive(Debug)]
+pub struct Divergence {
+    /// Number of steps both runs took identically before
+    pub step: usize,
+    pub a: RunState,
+    pub b: RunState,
+}
+
+/// Run `a` and `b` side by side and find the first step where they differ,
+/// whether in the executed instruction, the operand stack, or how they end.
+/// `None` if both runs behaved the same up to their results.
+///
+/// Runs which never finish never diverge either, so give `config` some fuel
+/// if that can happen.
+pub fn diff_runs(a: Run, b: Run, config: &Config) -> Result<Option<Divergence>> {
+    let recorder = Recorder::default();
+    let mut a = Side::start(a)?;
+    let mut b = Side::start(b)?;
+    let mut step = 0;
+    loop {
+        match (a.next(&recorder, config), b.next(&recorder, config)) {
+            (None, None) => return Ok(None),
+            (state_a, state_b) if state_a == state_b => step += 1,
+            (state_a, state_b) => {
+                // A run only stops after reporting how it ended, which the
+                // other run can't have matched
+                let (a, b) = state_a.zip(state_b).expect("runs ended at different steps");
+                return Ok(Some(Divergence { step, a, b }));
+            }
+        }
+    }
+}
+
+struct Side {
+    instance: WasmInstance,
+    executor: Executor,
+    returns: Vec<Type>,
+    /// How the run ended, reported after its last instruction
+    end: Option<RunState>,
+    finished: bool,
+}
+
+impl Side {
+    fn start(run: Run) -> Result<Self> {
+        let (func, exec_addr) = run
+            .instance
+            .store
+            .func(run.func_addr)
+            .ok_or_else(|| anyhow!("Function not found"))?;
+        let func = func
+            .defined()
+            .ok_or_else(|| anyhow!("Can't trace the host function {}", func.name()))?;
+        let params = &func.ty().params;
+        if run.args.len() != params.len()
+            || !run
+                .args
+                .iter()
+                .zip(params.iter())
+                .all(|(arg, ty)| arg.isa(*ty))
+        {
+            return Err(anyhow!(
+                "Function {} expects {:?} but got {:?}",
+                func.name(),
+                params,
+                run.args
+            ));
+        }
+        let returns = func.ty().returns.to_vec();
+        let frame = CallFrame::new_from_func(exec_addr, func, run.args, None);
+        let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
+        Ok(Self {
+            executor: Executor::new(frame, returns.len(), pc),
+            instance: run.instance,
+            returns,
+            end: None,
+            finished: false,
+        })
+    }
+
+    /// The state before the next instruction, or how the run ended once
+    /// it did. `None` after that.
+    fn next(&mut self, recorder: &Recorder, config: &Config) -> Option<RunState> {
+        if self.finished {
+            return self.end.take();
+        }
+        let result = self
+            .executor
+            .execute_step(&self.instance.store, recorder, config);
+        let step = recorder.step.borrow_mut().take();
+        let end = match result {
+            Ok(Signal::End) => match self.executor.pop_result(self.returns.clone()) {
+                Ok(values) => RunState::Finished(values),
+                Err(err) => RunState::Trapped(err.to_string()),
+            },
+            Ok(Signal::Next) | Ok(Signal::Breakpoint) => return step,
+            Err(trap) => RunState::Trapped(trap.to_string()),
+        };
+        self.finished = true;
+        // Some traps happen before the instruction is reached
+        match step {
+            Some(step) => {
+                self.end = Some(end);
+                Some(step)
+            }
+            None => Some(end),
+        }
+    }
+}
+
+/// Captures the state before each instruction, like the trace does
+#[derive(Default)]
+struct Recorder {
+    step: RefCell<Option<RunState>>,
+}
+
+impl Interceptor for Recorder {
+    fn invoke_func(
+        &self,
+        _name: &str,
+        _executor: &Executor,
+        _store: &Store,
+    ) -> Result<Signal, Trap> {
+        Ok(Signal::Next)
+    }
+
+    fn execute_inst(&self, inst: &Instruction, executor: &Executor) -> Result<Signal, Trap> {
+        *self.step.borrow_mut() = Some(RunState::Executing {
+            pc: inst.offset,
+            op: inst.kind.name().to_string(),
+            stack: executor
+                .stack
+                .peek_top_values(usize::MAX)
+                .copied()
+                .collect(),
+        });
+        Ok(Signal::Next)
+    }
+
+    fn after_store(&self, _addr: usize, _bytes: &[u8]) -> Result<Signal, Trap> {
+        Ok(Signal::Next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const ADD_ONE: [u8; 38] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+        0x03, 0x02, 0x01, 0x00,
+        // (export "f" (func 0))
+        0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00,
+        // (func (param i32) (result i32) (i32.add (local.get 0) (i32.const 1)))
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x41, 0x01, 0x6a, 0x0b,
+    ];
+
+    fn run(arg: i32) -> Run {
+        let mut instance = WasmInstance::new();
+        let mut bytes = ADD_ONE;
+        let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+        let func_addr = instance.get_func(module_index, "f").unwrap();
+        Run {
+            instance,
+            func_addr,
+            args: vec![WasmValue::I32(arg)],
+        }
+    }
+
+    #[test]
+    fn same_inputs_dont_diverge() {
+        let divergence = diff_runs(run(1), run(1), &Config::default()).unwrap();
+        assert!(divergence.is_none());
+    }
+
+    #[test]
+    fn reports_first_differing_stack() {
+        let divergence = diff_runs(run(1), run(2), &Config::default())
+            .unwrap()
+            .unwrap();
+        // local.get runs on an empty stack, then the argument is pushed
+        assert_eq!(divergence.step, 1);
+        assert_eq!(
+            divergence.a,
+            RunState::Executing {
+                pc: 5,
+                op: "I32Const".to_string(),
+                stack: vec![WasmValue::I32(1)],
+            }
+        );
+        assert_eq!(
+            divergence.b,
+            RunState::Executing {
+                pc: 5,
+                op: "I32Const".to_string(),
+                stack: vec![WasmValue::I32(2)],
+            }
+        );
+    }
+}
@@ -444,6 +444,16 @@ impl sourcemap::SourceMap for DwarfSourceMap {
     fn set_directory_map(&self, from: String, to: String) {
         self.directory_map.borrow_mut().insert(from, to);
     }
+    fn directory_map(&self) -> Vec<(String, String)> {
+        let mut map: Vec<_> = self
+            .directory_map
+            .borrow()
+            .iter()
+            .map(|(from, to)| (from.clone(), to.clone()))
+            .collect();
+        map.sort();
+        map
+    }
     fn find_line_info(&self, offset: usize) -> Option<sourcemap::LineInfo> {
         let mut line_info = match self
             .address_sorted_rows
@@ -463,6 +473,22 @@ impl sourcemap::SourceMap for DwarfSourceMap {
         }
         Some(line_info)
     }
+    fn find_code_offset(&self, filepath: &str, line: u64) -> Option<usize> {
+        let directory_map = self.directory_map.borrow();
+        self.address_sorted_rows
+            .iter()
+            .find(|(_, line_info)| {
+                if line_info.line != Some(line) {
+                    return false;
+                }
+                let mut path = line_info.filepath.clone();
+                for (from, to) in directory_map.iter() {
+                    path = path.replace(from, to);
+                }
+                Path::new(&path).ends_with(filepath)
+            })
+            .map(|(addr, _)| *addr as usize)
+    }
 }
 
 use crate::commands::subroutine;
@@ -572,6 +598,13 @@ impl subroutine::SubroutineMap for DwarfSubroutineMap {
         };
         Ok(subroutine.frame_base)
     }
+    fn function_name(&self, code_offset: usize) -> Option<String> {
+        let offset = &(code_offset as u64);
+        self.subroutines
+            .iter()
+            .find(|s| s.pc.contains(offset))
+            .and_then(|s| s.name.clone())
+    }
     fn display_variable(
         &self,
         code_offset: usize,
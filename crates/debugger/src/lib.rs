@@ -1,21 +1,30 @@
 mod commands;
 mod debugger;
+mod diff;
 mod dwarf;
 mod process;
+mod trace;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
 
 pub use commands::command::CommandContext;
 pub use commands::command::CommandResult;
-pub use commands::debugger::{Debugger, RunResult};
-pub use debugger::MainDebugger;
+pub use commands::debugger::{
+    describe_error, Breakpoint, BreakpointHits, Debugger, OutputPrinter, RunResult, StepStyle,
+    TrapError,
+};
+pub use commands::format::{typed_value, Radix};
+pub use debugger::{MainDebugger, ProgressCallback};
+pub use diff::{diff_runs, Divergence, Run, RunState};
 pub use linefeed;
 pub use process::Interactive;
 pub use process::Process;
+pub use serde_json;
 
 use anyhow::{anyhow, Result};
 use commands::command;
 use log::warn;
+use wasminspect_vm::Config;
 
 pub fn try_load_dwarf(
     buffer: &[u8],
@@ -28,7 +37,75 @@ pub fn try_load_dwarf(
     Ok(())
 }
 
-struct ConsolePrinter {}
+/// Assemble `bytes` into a binary module if they are a text module, told
+/// apart by a `.wat` name or by leading text where the `\0asm` magic would be
+pub fn assemble_if_text<'a>(bytes: &'a [u8], name: &str) -> Result<Cow<'a, [u8]>> {
+    let looks_like_text = || {
+        let first = bytes.iter().find(|b| !b.is_ascii_whitespace());
+        matches!(first, Some(b'(') | Some(b';'))
+    };
+    if bytes.starts_with(b"\0asm") || !(name.ends_with(".wat") || looks_like_text()) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let text = std::str::from_utf8(bytes)?;
+    let adjust = |mut err: wast::Error| {
+        err.set_path(name.as_ref());
+        err.set_text(text);
+        err
+    };
+    let buf = wast::parser::ParseBuffer::new(text).map_err(adjust)?;
+    let mut wat = wast::parser::parse::<wast::Wat>(&buf).map_err(adjust)?;
+    Ok(Cow::Owned(wat.module.encode().map_err(adjust)?))
+}
+
+/// Load `bytes` as the main module together with its DWARF info and local
+/// names, and re-resolve existing breakpoints against the new module. Text
+/// modules are assembled first, and have no DWARF info.
+pub fn reload_main_module<D: Debugger>(
+    debugger: &mut D,
+    context: &mut CommandContext,
+    bytes: &[u8],
+    basename: String,
+) -> Result<()> {
+    let module = assemble_if_text(bytes, &basename)?;
+    let bytes = &*module;
+    debugger.load_main_module(bytes, basename)?;
+    match try_load_dwarf(bytes, context) {
+        Ok(_) => (),
+        Err(err) => {
+            warn!("Failed to load dwarf info: {}", err);
+        }
+    }
+    context.names = commands::names::NameMap::parse(bytes).unwrap_or_else(|err| {
+        warn!("Failed to load name section: {}", err);
+        commands::names::NameMap::default()
+    });
+    for breakpoint in debugger.resolve_breakpoints(&*context.sourcemap) {
+        let output = format!("Breakpoint {} doesn't map to any code", breakpoint);
+        context.printer.eprintln(&output);
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct ConsolePrinter {
+    color: std::cell::Cell<bool>,
+}
+
+impl ConsolePrinter {
+    fn ansi_code(style: commands::debugger::Style) -> Option<&'static str> {
+        use commands::debugger::Style;
+        match style {
+            Style::Plain => None,
+            Style::Marker => Some("1;33"),
+            Style::Opcode => Some("36"),
+            Style::Immediate => Some("35"),
+            Style::CurrentLine => Some("1"),
+            Style::ContextLine => Some("2"),
+        }
+    }
+}
+
 impl commands::debugger::OutputPrinter for ConsolePrinter {
     fn println(&self, output: &str) {
         println!("{}", output);
@@ -36,6 +113,109 @@ impl commands::debugger::OutputPrinter for ConsolePrinter {
     fn eprintln(&self, output: &str) {
         eprintln!("{}", output);
     }
+    fn println_styled(&self, spans: &[(commands::debugger::Style, &str)]) {
+        // Escape sequences would end up verbatim in files and pipes
+        let color = self.color.get() && atty::is(atty::Stream::Stdout);
+        let mut line = String::new();
+        for (style, text) in spans {
+            match Self::ansi_code(*style).filter(|_| color && !text.is_empty()) {
+                Some(code) => line += &format!("\x1B[{}m{}\x1B[0m", code, text),
+                None => line += text,
+            }
+        }
+        println!("{}", line);
+    }
+    fn set_color(&self, enable: bool) {
+        self.color.set(enable);
+    }
+}
+
+/// Prints every line as a JSON object on its own line: command results as
+/// their records, other output as `{"output": ...}` and errors as
+/// `{"error": ...}` on stderr
+#[derive(Default)]
+pub struct JsonPrinter {}
+
+impl commands::debugger::OutputPrinter for JsonPrinter {
+    fn println(&self, output: &str) {
+        println!("{}", serde_json::json!({ "output": output }));
+    }
+    fn eprintln(&self, output: &str) {
+        eprintln!("{}", serde_json::json!({ "error": output }));
+    }
+    fn print_record(&self, _text: &str, record: &serde_json::Value) {
+        println!("{}", record);
+    }
+}
+
+/// How command output is printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    /// Names accepted by `FromStr`
+    pub const NAMES: &'static [&'static str] = &["text", "json"];
+
+    fn printer(self) -> Box<dyn OutputPrinter> {
+        match self {
+            Self::Text => Box::new(ConsolePrinter::default()),
+            Self::Json => Box::new(JsonPrinter::default()),
+        }
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("unknown output format '{}'", s)),
+        }
+    }
+}
+
+/// Command line options shared by `run_batch`, `run_eval` and `run_loop`
+#[derive(Default)]
+pub struct RunOptions {
+    pub preopen_dirs: Vec<(String, String)>,
+    pub envs: Vec<(String, String)>,
+    /// File to write the instruction trace to
+    pub trace: Option<String>,
+    pub config: Config,
+    pub output: OutputFormat,
+}
+
+impl RunOptions {
+    fn start_debugger(
+        self,
+        module_input: Option<ModuleInput>,
+    ) -> Result<(
+        process::Process<debugger::MainDebugger>,
+        command::CommandContext,
+    )> {
+        let Self {
+            preopen_dirs,
+            envs,
+            trace,
+            config,
+            output,
+        } = self;
+        let (mut process, mut context) =
+            start_debugger_with_config(module_input, preopen_dirs, envs, config)?;
+        context.printer = output.printer();
+        set_trace(&mut process, trace)?;
+        Ok((process, context))
+    }
 }
 
 pub struct ModuleInput {
@@ -51,21 +231,34 @@ pub fn start_debugger(
     process::Process<debugger::MainDebugger>,
     command::CommandContext,
 )> {
-    let mut debugger = debugger::MainDebugger::new(preopen_dirs, envs)?;
+    start_debugger_with_config(module_input, preopen_dirs, envs, Config::default())
+}
+
+/// `start_debugger` validating and running modules with `config`
+pub fn start_debugger_with_config(
+    module_input: Option<ModuleInput>,
+    preopen_dirs: Vec<(String, String)>,
+    envs: Vec<(String, String)>,
+    config: Config,
+) -> Result<(
+    process::Process<debugger::MainDebugger>,
+    command::CommandContext,
+)> {
+    let mut debugger = debugger::MainDebugger::new(preopen_dirs, envs, config)?;
     let mut context = commands::command::CommandContext {
         sourcemap: Box::new(commands::sourcemap::EmptySourceMap::new()),
         subroutine: Box::new(commands::subroutine::EmptySubroutineMap::new()),
-        printer: Box::new(ConsolePrinter {}),
+        names: commands::names::NameMap::default(),
+        printer: Box::new(ConsolePrinter::default()),
     };
 
     if let Some(ref module_input) = module_input {
-        debugger.load_main_module(&module_input.bytes, module_input.basename.clone())?;
-        match try_load_dwarf(&module_input.bytes, &mut context) {
-            Ok(_) => (),
-            Err(err) => {
-                warn!("Failed to load dwarf info: {}", err);
-            }
-        }
+        reload_main_module(
+            &mut debugger,
+            &mut context,
+            &module_input.bytes,
+            module_input.basename.clone(),
+        )?;
     }
     let process = process::Process::new(
         debugger,
@@ -81,7 +274,15 @@ pub fn start_debugger(
             Box::new(commands::local::LocalCommand::new()),
             Box::new(commands::frame::FrameCommand::new()),
             Box::new(commands::settings::SettingsCommand::new()),
+            Box::new(commands::session::SessionCommand::new()),
             Box::new(commands::process::ProcessCommand::new()),
+            Box::new(commands::image::ImageCommand::new()),
+            Box::new(commands::segment::SegmentCommand::new()),
+            Box::new(commands::coverage::CoverageCommand::new()),
+            Box::new(commands::profile::ProfileCommand::new()),
+            Box::new(commands::table::TableCommand::new()),
+            Box::new(commands::until::UntilCommand::new()),
+            Box::new(commands::r#where::WhereCommand::new()),
         ],
         vec![
             Box::new(commands::run::RunCommand::new()),
@@ -91,13 +292,96 @@ pub fn start_debugger(
     Ok((process, context))
 }
 
+fn set_trace(process: &mut process::Process<MainDebugger>, trace: Option<String>) -> Result<()> {
+    if let Some(trace) = trace {
+        let file = std::fs::File::create(&trace)
+            .map_err(|err| anyhow!("failed to create {}: {}", trace, err))?;
+        process.debugger.set_trace(Some(Box::new(file)));
+    }
+    Ok(())
+}
+
+/// Exit status when the guest trapped
+pub const EXIT_TRAP: i32 = 1;
+/// Exit status for any other failure, like an invalid command line or a
+/// missing entry function
+pub const EXIT_FAILURE: i32 = 2;
+
+/// Exit status of `wasminspect` for a run which failed with `err`
+pub fn exit_status(err: &anyhow::Error) -> i32 {
+    if err.downcast_ref::<TrapError>().is_some() {
+        EXIT_TRAP
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+/// Run the commands in `script` without prompting, failing with the first
+/// command which fails. The init file is not read so that runs are
+/// reproducible. The error is printed before it is returned.
+pub fn run_batch(
+    module_input: Option<ModuleInput>,
+    script: String,
+    options: RunOptions,
+) -> Result<()> {
+    let output = options.output;
+    let (mut process, mut context) = match options.start_debugger(module_input) {
+        Ok(started) => started,
+        Err(err) => {
+            output.printer().eprintln(&format!("{:#}", err));
+            return Err(err);
+        }
+    };
+    let result = process.source_file(&script, &mut context).map(|_| ());
+    if let Err(err) = &result {
+        let output = describe_error(err, &context);
+        context.printer.eprintln(&output);
+    }
+    result
+}
+
+/// Run `commands` in order without prompting, like `run_batch` does with
+/// the lines of a script, for one-off queries from scripts
+pub fn run_eval(
+    module_input: Option<ModuleInput>,
+    commands: Vec<String>,
+    options: RunOptions,
+) -> Result<()> {
+    let output = options.output;
+    let (mut process, mut context) = match options.start_debugger(module_input) {
+        Ok(started) => started,
+        Err(err) => {
+            output.printer().eprintln(&format!("{:#}", err));
+            return Err(err);
+        }
+    };
+    let mut result = Ok(());
+    for command in &commands {
+        match process.execute_command(command, &mut context) {
+            Ok(Some(CommandResult::Exit)) => break,
+            Ok(_) => {}
+            Err(err) => {
+                result = Err(err);
+                break;
+            }
+        }
+    }
+    if let Err(err) = &result {
+        let output = describe_error(err, &context);
+        context.printer.eprintln(&output);
+    }
+    result
+}
+
+/// Run the interactive prompt, reading and writing the command history
+/// file if `history`
 pub fn run_loop(
     module_input: Option<ModuleInput>,
     init_source: Option<String>,
-    preopen_dirs: Vec<(String, String)>,
-    envs: Vec<(String, String)>,
+    history: bool,
+    options: RunOptions,
 ) -> Result<()> {
-    let (mut process, context) = start_debugger(module_input, preopen_dirs, envs)?;
+    let (mut process, mut context) = options.start_debugger(module_input)?;
 
     {
         let is_default = init_source.is_none();
@@ -117,11 +401,18 @@ pub fn run_loop(
             }
         };
         for line in lines {
-            process.dispatch_command(&line, &context)?;
+            process.dispatch_command(&line, &mut context)?;
         }
     }
-    let mut interactive = Interactive::new_with_loading_history()?;
+    let mut interactive = if history {
+        Interactive::new_with_loading_history()?
+    } else {
+        Interactive::new_without_history()?
+    };
+    interactive.set_completer(process.completer());
     let process = Rc::new(RefCell::new(process));
-    while let CommandResult::ProcessFinish(_) = interactive.run_loop(&context, process.clone())? {}
+    while let CommandResult::ProcessFinish(_) =
+        interactive.run_loop(&mut context, process.clone())?
+    {}
     Ok(())
 }
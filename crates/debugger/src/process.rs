@@ -1,10 +1,18 @@
+use crate::commands::alias::UserAlias;
 use crate::commands::command::{self, AliasCommand, Command, CommandResult};
-use crate::commands::debugger::Debugger;
-use anyhow::{Context, Result};
+use crate::commands::completion::{CommandCompleter, CompletionTree};
+use crate::commands::debugger::{describe_error, Debugger};
+use anyhow::{anyhow, Context, Result};
 use linefeed::{DefaultTerminal, Interface, ReadResult};
-use std::{cell::RefCell, io, rc::Rc};
+use std::{cell::RefCell, io, rc::Rc, sync::Arc};
 use std::{collections::HashMap, time::Duration};
 
+/// Commands handled by `Process` itself rather than a `Command`
+const BUILTIN_COMMANDS: [&str; 4] = ["help", "alias", "unalias", "source"];
+
+/// Number of nested alias expansions and sourced files before giving up
+const MAX_NESTING_DEPTH: usize = 16;
+
 pub struct Process<D: Debugger> {
     pub debugger: D,
     commands: HashMap<String, Box<dyn Command<D>>>,
@@ -32,37 +40,212 @@ impl<D: Debugger> Process<D> {
         })
     }
 
+    /// Run a command line, printing its error instead of returning it
     pub fn dispatch_command(
         &mut self,
         line: &str,
-        context: &command::CommandContext,
+        context: &mut command::CommandContext,
+    ) -> Result<Option<CommandResult>> {
+        match self.execute_command(line, context) {
+            Ok(result) => Ok(result),
+            Err(err) => {
+                eprintln!("{}", describe_error(&err, context));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Run a command line and return the error of a failing command
+    pub fn execute_command(
+        &mut self,
+        line: &str,
+        context: &mut command::CommandContext,
+    ) -> Result<Option<CommandResult>> {
+        self.execute_command_at_depth(line, context, 0)
+    }
+
+    /// Run the command lines in `path` in order, stopping at the first one
+    /// which fails. Empty lines and lines starting with `#` are skipped.
+    pub fn source_file(
+        &mut self,
+        path: &str,
+        context: &mut command::CommandContext,
+    ) -> Result<Option<CommandResult>> {
+        self.source_file_at_depth(path, context, 0)
+    }
+
+    /// `depth` counts the aliases expanded and files sourced to reach `line`,
+    /// which stops an alias or script using itself from recursing forever
+    fn execute_command_at_depth(
+        &mut self,
+        line: &str,
+        context: &mut command::CommandContext,
+        depth: usize,
     ) -> Result<Option<CommandResult>> {
         let cmd_name = extract_command_name(line);
         let args = shell_words::split(line)?;
         // FIXME
-        let args = args.iter().map(AsRef::as_ref).collect();
+        let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
         if let Some(cmd) = self.commands.get(cmd_name) {
-            match cmd.run(&mut self.debugger, context, args) {
-                Ok(result) => Ok(result),
-                Err(err) => {
-                    eprintln!("{}", err);
-                    Ok(None)
-                }
+            let stop_count = self.debugger.stop_count();
+            let result = cmd.run(&mut self.debugger, context, args);
+            let finished = matches!(result, Ok(Some(CommandResult::ProcessFinish(_))));
+            if stop_count != self.debugger.stop_count() && self.debugger.is_running() && !finished {
+                self.display_stop(context);
             }
+            result
         } else if let Some(alias) = self.aliases.get(cmd_name) {
+            if depth >= MAX_NESTING_DEPTH {
+                return Err(anyhow!("alias '{}' expands into itself", cmd_name));
+            }
             let line = alias.run(args)?;
-            self.dispatch_command(&line, context)
+            self.execute_command_at_depth(&line, context, depth + 1)
         } else if cmd_name == "help" {
-            println!("Available commands:");
-            for command in self.commands.values() {
-                println!("  {} -- {}", command.name(), command.description());
-            }
+            self.print_help(context);
+            Ok(None)
+        } else if cmd_name == "alias" {
+            self.define_alias(&args[1..], context)?;
             Ok(None)
+        } else if cmd_name == "unalias" {
+            self.remove_alias(&args[1..])?;
+            Ok(None)
+        } else if cmd_name == "source" {
+            match args.as_slice() {
+                [_, path] => self.source_file_at_depth(path, context, depth),
+                _ => Err(anyhow!("usage: source <file>")),
+            }
         } else if cfg!(feature = "remote-api") && cmd_name == "start-server" {
             Ok(Some(CommandResult::Exit))
         } else {
-            eprintln!("'{}' is not a valid command.", cmd_name);
-            Ok(None)
+            Err(anyhow!("'{}' is not a valid command.", cmd_name))
+        }
+    }
+
+    fn source_file_at_depth(
+        &mut self,
+        path: &str,
+        context: &mut command::CommandContext,
+        depth: usize,
+    ) -> Result<Option<CommandResult>> {
+        if depth >= MAX_NESTING_DEPTH {
+            return Err(anyhow!("{} sources itself", path));
+        }
+        let script =
+            std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path))?;
+        for (index, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match self.execute_command_at_depth(line, context, depth + 1) {
+                // A finished process doesn't end the script, it can be run again
+                Ok(None) | Ok(Some(CommandResult::ProcessFinish(_))) => {}
+                Ok(Some(CommandResult::Exit)) => return Ok(Some(CommandResult::Exit)),
+                Err(err) => return Err(err.context(format!("{}:{}", path, index + 1))),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Completer offering the names, subcommands and flags of every command
+    pub fn completer(&self) -> CommandCompleter {
+        let commands = self
+            .commands
+            .values()
+            .map(|command| (command.name().to_string(), command.completion()));
+        let aliases = self
+            .aliases
+            .values()
+            .map(|alias| (alias.name().to_string(), alias.completion()));
+        let alias_names = self.aliases.keys().cloned();
+        let builtins = BUILTIN_COMMANDS.iter().map(|name| {
+            let tree = if *name == "unalias" {
+                CompletionTree::from_words(alias_names.clone())
+            } else {
+                CompletionTree::default()
+            };
+            (name.to_string(), tree)
+        });
+        CommandCompleter::new(commands.chain(aliases).chain(builtins).collect())
+    }
+
+    /// `alias <name> <expansion>` registers an alias, `alias` alone lists them
+    fn define_alias(&mut self, args: &[&str], context: &command::CommandContext) -> Result<()> {
+        let (name, expansion) = match args.split_first() {
+            Some((name, expansion)) if !expansion.is_empty() => (*name, expansion.join(" ")),
+            Some((name, _)) => return Err(anyhow!("alias '{}' needs an expansion", name)),
+            None => {
+                let mut aliases: Vec<_> = self
+                    .aliases
+                    .values()
+                    .map(|alias| (alias.name(), alias.description()))
+                    .collect();
+                aliases.sort_unstable();
+                for (name, description) in aliases {
+                    context
+                        .printer
+                        .println(&format!("{} -- {}", name, description));
+                }
+                return Ok(());
+            }
+        };
+        if self.commands.contains_key(name) || BUILTIN_COMMANDS.contains(&name) {
+            return Err(anyhow!("'{}' is a command and can't be an alias", name));
+        }
+        self.aliases.insert(
+            name.to_string(),
+            Box::new(UserAlias::new(name.to_string(), expansion)),
+        );
+        Ok(())
+    }
+
+    /// `unalias <name>` removes an alias
+    fn remove_alias(&mut self, args: &[&str]) -> Result<()> {
+        let name = match args {
+            [name] => name,
+            _ => return Err(anyhow!("usage: unalias <name>")),
+        };
+        match self.aliases.remove(*name) {
+            Some(_) => Ok(()),
+            None => Err(anyhow!("'{}' is not an alias", name)),
+        }
+    }
+
+    /// List the commands and then the aliases, each sorted by name
+    fn print_help(&self, context: &command::CommandContext) {
+        let mut commands: Vec<_> = self
+            .commands
+            .values()
+            .map(|command| (command.name(), command.description()))
+            .collect();
+        commands.sort_unstable();
+        let mut aliases: Vec<_> = self
+            .aliases
+            .values()
+            .map(|alias| (alias.name(), alias.description()))
+            .collect();
+        aliases.sort_unstable();
+
+        let printer = &context.printer;
+        printer.println("Available commands:");
+        for (name, description) in commands {
+            printer.println(&format!("  {} -- {}", name, description));
+        }
+        printer.println("Aliases:");
+        for (name, description) in aliases {
+            printer.println(&format!("  {} -- {}", name, description));
+        }
+    }
+
+    /// Print the views configured by the `stop-display` setting. Views which
+    /// aren't available at this stop, like source without DWARF, are skipped.
+    fn display_stop(&mut self, context: &mut command::CommandContext) {
+        for view in self.debugger.get_opts().stop_display {
+            let line = view.command();
+            let args = line.split(' ').collect();
+            if let Some(cmd) = self.commands.get(extract_command_name(line)) {
+                let _ = cmd.run(&mut self.debugger, context, args);
+            }
         }
     }
 }
@@ -70,7 +253,8 @@ impl<D: Debugger> Process<D> {
 pub struct Interactive {
     pub interface: Interface<DefaultTerminal>,
 
-    history_file: String,
+    /// Where the history is loaded from and saved to, `None` to keep none
+    history_file: Option<String>,
 }
 
 fn history_file_path() -> String {
@@ -82,28 +266,41 @@ fn history_file_path() -> String {
 
 impl Interactive {
     pub fn new_with_loading_history() -> anyhow::Result<Self> {
-        Self::new(&history_file_path())
+        Self::new(Some(&history_file_path()))
+    }
+
+    /// A prompt which neither reads nor writes a history file, for shared
+    /// or ephemeral environments
+    pub fn new_without_history() -> anyhow::Result<Self> {
+        Self::new(None)
     }
 
-    pub fn new(history_file: &str) -> anyhow::Result<Self> {
+    pub fn new(history_file: Option<&str>) -> anyhow::Result<Self> {
         let interface = Interface::new("wasminspect").with_context(|| "new Interface")?;
         interface
             .set_prompt("(wasminspect) ")
             .with_context(|| "set prompt")?;
-        if let Err(e) = interface.load_history(history_file) {
-            if e.kind() == io::ErrorKind::NotFound {
-            } else {
-                eprintln!("Could not load history file {}: {}", history_file, e);
+        if let Some(history_file) = history_file {
+            if let Err(e) = interface.load_history(history_file) {
+                if e.kind() == io::ErrorKind::NotFound {
+                } else {
+                    eprintln!("Could not load history file {}: {}", history_file, e);
+                }
             }
         }
         Ok(Self {
             interface,
-            history_file: history_file.to_string(),
+            history_file: history_file.map(str::to_string),
         })
     }
+
+    pub fn set_completer(&self, completer: CommandCompleter) {
+        self.interface.set_completer(Arc::new(completer));
+    }
+
     pub fn run_step<D: Debugger>(
         &mut self,
-        context: &command::CommandContext,
+        context: &mut command::CommandContext,
         process: Rc<RefCell<Process<D>>>,
         last_line: &mut Option<String>,
         timeout: Option<Duration>,
@@ -116,7 +313,12 @@ impl Interactive {
         let result = if !line.trim().is_empty() {
             self.interface.add_history_unique(line.clone());
             *last_line = Some(line.clone());
-            process.borrow_mut().dispatch_command(&line, context)?
+            let result = process.borrow_mut().dispatch_command(&line, context)?;
+            // Aliases are completed too, so pick up the ones just changed
+            if matches!(extract_command_name(&line), "alias" | "unalias") {
+                self.set_completer(process.borrow().completer());
+            }
+            result
         } else if let Some(last_line) = last_line.as_ref() {
             process.borrow_mut().dispatch_command(last_line, context)?
         } else {
@@ -127,7 +329,7 @@ impl Interactive {
 
     pub fn run_loop<D: Debugger>(
         &mut self,
-        context: &command::CommandContext,
+        context: &mut command::CommandContext,
         process: Rc<RefCell<Process<D>>>,
     ) -> Result<CommandResult> {
         let mut last_line: Option<String> = None;
@@ -150,8 +352,10 @@ fn extract_command_name(s: &str) -> &str {
 
 impl Drop for Interactive {
     fn drop(&mut self) {
-        if let Err(error) = self.interface.save_history(&self.history_file) {
-            println!("Error while saving command history: {}", error);
+        if let Some(history_file) = &self.history_file {
+            if let Err(error) = self.interface.save_history(history_file) {
+                println!("Error while saving command history: {}", error);
+            }
         }
     }
 }
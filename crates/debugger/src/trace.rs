@@ -0,0 +1,112 @@
+//! Instruction trace written while the debuggee runs
+//!
+//! The trace is JSON Lines, one object per executed instruction:
+//!
+//! ```json
+//! {"pc":52,"op":"I32Add","stack":["i32:2","i32:1"]}
+//! ```
+//!
+//! - `pc` is the code offset of the instruction, as shown by `disassemble`
+//! - `op` is the instruction name without its immediates
+//! - `stack` holds up to `TRACE_STACK_DEPTH` operand values before the
+//!   instruction runs, top of the stack first. Values are `<type>:<value>`,
+//!   with floats written as their hex bit pattern so that NaN payloads
+//!   survive a round trip, and null references as `<type>:null`.
+
+use crate::commands::format::{typed_value, Radix};
+use serde::Serialize;
+use std::io::{self, BufWriter, Write};
+use wasminspect_vm::{Instruction, NumVal, WasmValue};
+
+/// Number of operand stack values recorded per instruction
+pub const TRACE_STACK_DEPTH: usize = 3;
+
+#[derive(Serialize)]
+struct TraceLine {
+    pc: usize,
+    op: &'static str,
+    stack: Vec<String>,
+}
+
+pub struct Tracer {
+    writer: BufWriter<Box<dyn Write>>,
+}
+
+impl Tracer {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    /// Record `inst` with the operand stack `values`, top first
+    pub fn record<'a>(
+        &mut self,
+        inst: &Instruction,
+        values: impl Iterator<Item = &'a WasmValue>,
+    ) -> io::Result<()> {
+        let stack: Vec<_> = values.take(TRACE_STACK_DEPTH).map(format_value).collect();
+        let line = TraceLine {
+            pc: inst.offset,
+            op: inst.kind.name(),
+            stack,
+        };
+        serde_json::to_writer(&mut self.writer, &line)?;
+        self.writer.write_all(b"\n")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Encode `value` as `<type>:<value>`, as in the trace and JSON output
+pub(crate) fn format_value(value: &WasmValue) -> String {
+    let radix = match value {
+        WasmValue::Num(NumVal::F32(_)) | WasmValue::Num(NumVal::F64(_)) => Radix::Hex,
+        _ => Radix::Dec,
+    };
+    let (ty, value) = typed_value(value, radix);
+    format!("{}:{}", ty, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasminspect_vm::InstructionKind;
+
+    #[test]
+    fn record_writes_top_of_stack_first() {
+        let buffer = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        struct Shared(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut tracer = Tracer::new(Box::new(Shared(buffer.clone())));
+        let inst = Instruction {
+            kind: InstructionKind::I32Const { value: 16 },
+            offset: 0x2a,
+            len: 2,
+        };
+        let values = [
+            WasmValue::I64(-4),
+            WasmValue::from(1.5f32),
+            WasmValue::I32(2),
+            WasmValue::I32(1),
+        ];
+        tracer.record(&inst, values.iter()).unwrap();
+        tracer.flush().unwrap();
+
+        let output = String::from_utf8(buffer.borrow().clone()).unwrap();
+        assert_eq!(
+            output,
+            "{\"pc\":42,\"op\":\"I32Const\",\"stack\":[\"i64:-4\",\"f32:0x3fc00000\",\"i32:2\"]}\n"
+        );
+    }
+}
@@ -61,3 +61,130 @@ fn build_translate_arm(
         }
     }
 }
+
+/// Type prefixes the text format separates from the rest with a `.`
+const TYPE_PREFIXES: &[&str] = &[
+    "I32", "I64", "F32", "F64", "V128", "I8x16", "I16x8", "I32x4", "I64x2", "F32x4", "F64x2",
+    "Memory", "Table", "Local", "Global", "Ref", "Data", "Elem", "Atomic",
+];
+const SIMD_PREFIXES: &[&str] = &["V128", "I8x16", "I16x8", "I32x4", "I64x2", "F32x4", "F64x2"];
+
+pub fn instruction_info(ast: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let variants = match &ast.data {
+        Data::Enum(v) => &v.variants,
+        _ => return Err(anyhow!("unexpected non enum type")),
+    };
+    let name = &ast.ident;
+    let names = variants
+        .iter()
+        .map(|v| v.ident.to_string())
+        .collect::<Vec<_>>();
+    let patterns = variants.iter().map(build_pattern).collect::<Vec<_>>();
+    let mnemonics = names.iter().map(|n| mnemonic(n));
+    // Classify variants by their names once here, so that none of this is
+    // string matching at run time
+    let matching = |pred: &dyn Fn(&str) -> bool| {
+        let patterns = names
+            .iter()
+            .zip(&patterns)
+            .filter(|(name, _)| pred(name))
+            .map(|(_, pattern)| pattern);
+        quote! { matches!(self, #(#patterns)|*) }
+    };
+    let accesses_memory =
+        matching(&|n| n.contains("Load") || n.contains("Store") || n.contains("Atomic"));
+    let is_float = matching(&|n| n.starts_with("F32") || n.starts_with("F64"));
+    let is_atomic = matching(&|n| n.contains("Atomic"));
+    let is_simd = matching(&|n| SIMD_PREFIXES.iter().any(|prefix| n.starts_with(prefix)));
+
+    Ok(quote! {
+        impl #name {
+            /// Name of the instruction without its immediates, e.g. `I32Const`
+            pub fn name(&self) -> &'static str {
+                match self {
+                    #(#patterns => #names),*
+                }
+            }
+
+            /// Name of the instruction in the text format, e.g. `i32.const`
+            pub fn mnemonic(&self) -> &'static str {
+                match self {
+                    #(#patterns => #mnemonics),*
+                }
+            }
+
+            /// Loads, stores and atomic instructions
+            pub(crate) fn accesses_memory(&self) -> bool {
+                #accesses_memory
+            }
+
+            /// Instructions on `f32` or `f64` values, SIMD lanes included
+            pub(crate) fn is_float(&self) -> bool {
+                #is_float
+            }
+
+            /// Instructions of the threads proposal
+            pub(crate) fn is_atomic(&self) -> bool {
+                #is_atomic
+            }
+
+            /// Instructions of the SIMD proposal
+            pub(crate) fn is_simd(&self) -> bool {
+                #is_simd
+            }
+        }
+    })
+}
+
+/// A pattern matching `variant` whatever its fields are
+fn build_pattern(variant: &Variant) -> proc_macro2::TokenStream {
+    let variant_name = &variant.ident;
+    match &variant.fields {
+        syn::Fields::Named(_) => quote! { Self::#variant_name { .. } },
+        syn::Fields::Unnamed(_) => quote! { Self::#variant_name(..) },
+        syn::Fields::Unit => quote! { Self::#variant_name },
+    }
+}
+
+/// The text format name of the instruction `variant` names, e.g.
+/// `i32.atomic.rmw8.add_u` for `I32AtomicRmw8AddU`
+fn mnemonic(variant: &str) -> String {
+    match variant {
+        "TypedSelect" => return "select".to_string(),
+        "V128AndNot" => return "v128.andnot".to_string(),
+        _ => {}
+    }
+    let mut words = vec![];
+    for c in variant.chars() {
+        if c.is_ascii_uppercase() || words.is_empty() {
+            words.push(String::new());
+        }
+        words.last_mut().unwrap().push(c);
+    }
+    if !TYPE_PREFIXES.contains(&words[0].as_str()) {
+        return words.join("_").to_lowercase();
+    }
+    let mut mnemonic = format!("{}.", words[0].to_lowercase());
+    let mut rest = &words[1..];
+    if rest.first().map(String::as_str) == Some("Atomic") {
+        mnemonic += "atomic.";
+        rest = &rest[1..];
+        if let Some(rmw) = rest.first().filter(|word| word.starts_with("Rmw")) {
+            mnemonic += &format!("{}.", rmw.to_lowercase());
+            rest = &rest[1..];
+        }
+    }
+    let mut rest = rest.join("_").to_lowercase();
+    // SIMD names which don't split at every word
+    for (from, to) in &[
+        ("rounding_average", "avgr"),
+        ("q15_mulr", "q15mulr"),
+        ("ext_mul", "extmul"),
+        ("ext_add_pairwise", "extadd_pairwise"),
+        ("p_min", "pmin"),
+        ("p_max", "pmax"),
+    ] {
+        rest = rest.replace(from, to);
+    }
+    mnemonic + &rest
+}
@@ -10,3 +10,10 @@ pub fn try_from_wasmparser_operator(args: TokenStream) -> TokenStream {
         .unwrap()
         .into()
 }
+
+#[proc_macro_derive(InstructionInfo)]
+pub fn instruction_info(args: TokenStream) -> TokenStream {
+    inst::instruction_info(syn::parse_macro_input!(args as DeriveInput))
+        .unwrap()
+        .into()
+}
@@ -1,6 +1,361 @@
+use crate::inst::{InstructionClass, InstructionKind};
 use wasmparser::WasmFeatures;
 
-#[derive(Default)]
+/// Runtime configuration of the VM
+///
+/// `Config::default()` is the WebAssembly MVP feature set plus the proposals
+/// which are already part of the spec (reference types, multi-value and bulk
+/// memory), with no fuel limit and a call depth limit of
+/// `DEFAULT_MAX_CALL_DEPTH`. Use `Config::builder()` to change them.
+///
+/// Every instruction costs one unit of fuel unless `costs` says otherwise.
+///
+/// NaN canonicalization is off by default, keeping the payloads the host
+/// float operations produce as the spec allows.
+///
+/// Memories and tables are only bounded by what the module declares unless
+/// `max_memory_pages` or `max_table_elems` is set.
+#[derive(Clone)]
 pub struct Config {
+    /// Proposals accepted by the validator and the executor.
+    /// Prefer `Config::builder()` to depending on this field.
     pub features: WasmFeatures,
+    pub(crate) fuel: Option<u64>,
+    pub(crate) costs: CostTable,
+    pub(crate) max_call_depth: usize,
+    pub(crate) canonicalize_nans: bool,
+    pub(crate) max_memory_pages: Option<usize>,
+    pub(crate) max_table_elems: Option<usize>,
+    pub(crate) extended_const: bool,
+    pub(crate) poison_locals: bool,
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1024;
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            features: WasmFeatures::default(),
+            fuel: None,
+            costs: CostTable::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            canonicalize_nans: false,
+            max_memory_pages: None,
+            max_table_elems: None,
+            extended_const: false,
+            poison_locals: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// The default configuration accepting exactly `features`
+    pub fn new(features: WasmFeatures) -> Self {
+        Self {
+            features,
+            ..Self::default()
+        }
+    }
+
+    /// Maximum fuel a single invocation can consume
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Fuel each class of instructions consumes
+    pub fn costs(&self) -> &CostTable {
+        &self.costs
+    }
+
+    /// Maximum number of nested call frames
+    pub fn max_call_depth(&self) -> usize {
+        self.max_call_depth
+    }
+
+    /// Whether NaN results of float arithmetic are replaced with the canonical NaN
+    pub fn canonicalize_nans(&self) -> bool {
+        self.canonicalize_nans
+    }
+
+    /// Maximum number of pages of any memory, whatever the module declares
+    pub fn max_memory_pages(&self) -> Option<usize> {
+        self.max_memory_pages
+    }
+
+    /// Maximum number of elements of any table, whatever the module declares
+    pub fn max_table_elems(&self) -> Option<usize> {
+        self.max_table_elems
+    }
+
+    /// Whether reading a declared local before writing it is reported to the
+    /// `Interceptor`
+    pub fn poison_locals(&self) -> bool {
+        self.poison_locals
+    }
+}
+
+/// Fuel consumed per instruction by class, to model a metering scheme.
+/// The default charges every instruction one unit, so that fuel counts
+/// executed instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostTable {
+    pub memory: u64,
+    pub call: u64,
+    pub float: u64,
+    pub other: u64,
+}
+
+impl Default for CostTable {
+    fn default() -> Self {
+        Self {
+            memory: 1,
+            call: 1,
+            float: 1,
+            other: 1,
+        }
+    }
+}
+
+impl CostTable {
+    /// The fuel `kind` consumes
+    pub fn cost(&self, kind: &InstructionKind) -> u64 {
+        match kind.class() {
+            InstructionClass::Memory => self.memory,
+            InstructionClass::Call => self.call,
+            InstructionClass::Float => self.float,
+            InstructionClass::Other => self.other,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+/// Generates a setter per proposal plus lookups by the proposal's name.
+/// Proposals after the `;` are unknown to the validator and only affect
+/// the executor, so they are fields of `Config` itself.
+macro_rules! features {
+    (
+        $($(#[$attr:meta])* $name:ident = $label:literal,)*
+        ;
+        $($(#[$own_attr:meta])* $own_name:ident = $own_label:literal,)*
+    ) => {
+        impl Config {
+            /// Names of the proposals accepted by `ConfigBuilder::feature`
+            pub const FEATURE_NAMES: &'static [&'static str] = &[$($label,)* $($own_label,)*];
+
+            /// Every proposal by name, with whether it is enabled
+            pub fn feature_states(&self) -> Vec<(&'static str, bool)> {
+                vec![$(($label, self.features.$name),)* $(($own_label, self.$own_name),)*]
+            }
+
+            /// Whether the proposal named `name` is enabled.
+            /// `false` if there is no proposal with that name.
+            pub fn feature_enabled(&self, name: &str) -> bool {
+                match name {
+                    $($label => self.features.$name,)*
+                    $($own_label => self.$own_name,)*
+                    _ => false,
+                }
+            }
+
+            $(
+                $(#[$own_attr])*
+                pub fn $own_name(&self) -> bool {
+                    self.$own_name
+                }
+            )*
+        }
+
+        impl ConfigBuilder {
+            $(
+                $(#[$attr])*
+                pub fn $name(mut self, enable: bool) -> Self {
+                    self.config.features.$name = enable;
+                    self
+                }
+            )*
+
+            $(
+                $(#[$own_attr])*
+                pub fn $own_name(mut self, enable: bool) -> Self {
+                    self.config.$own_name = enable;
+                    self
+                }
+            )*
+
+            /// Enable or disable a proposal by one of `Config::FEATURE_NAMES`.
+            /// `None` if there is no proposal with that name.
+            pub fn feature(self, name: &str, enable: bool) -> Option<Self> {
+                match name {
+                    $($label => Some(self.$name(enable)),)*
+                    $($own_label => Some(self.$own_name(enable)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+features! {
+    /// The reference types proposal (enabled by default)
+    reference_types = "reference-types",
+    /// The multi-value proposal (enabled by default)
+    multi_value = "multi-value",
+    /// The bulk memory operations proposal (enabled by default)
+    bulk_memory = "bulk-memory",
+    /// The SIMD proposal
+    simd = "simd",
+    /// The threads proposal
+    threads = "threads",
+    /// The tail-call proposal
+    tail_call = "tail-call",
+    /// The multi-memory proposal
+    multi_memory = "multi-memory",
+    /// The exception handling proposal
+    exceptions = "exceptions",
+    /// The memory64 proposal
+    memory64 = "memory64",
+    /// The module linking proposal
+    module_linking = "module-linking",
+    ;
+    /// The extended constant expressions proposal, allowing `add`, `sub`
+    /// and `mul` of `i32` and `i64` in initializers
+    extended_const = "extended-const",
+}
+
+impl ConfigBuilder {
+    /// Trap once an invocation would consume more than `fuel`, which is its
+    /// number of instructions with the default `costs`. `None` means no limit.
+    pub fn fuel(mut self, fuel: Option<u64>) -> Self {
+        self.config.fuel = fuel;
+        self
+    }
+
+    /// Charge instructions by class from `costs` against the fuel limit
+    pub fn costs(mut self, costs: CostTable) -> Self {
+        self.config.costs = costs;
+        self
+    }
+
+    /// Trap with `Trap::CallStackExhausted` once calls nest deeper than
+    /// `depth` frames
+    pub fn max_call_depth(mut self, depth: usize) -> Self {
+        self.config.max_call_depth = depth;
+        self
+    }
+
+    /// Replace NaN results of float arithmetic with the positive canonical NaN.
+    /// This deviates from the spec, which allows any NaN payload, but makes
+    /// float results reproducible across platforms.
+    pub fn canonicalize_nans(mut self, enable: bool) -> Self {
+        self.config.canonicalize_nans = enable;
+        self
+    }
+
+    /// Refuse to instantiate or grow a memory beyond `pages` pages.
+    /// `memory.grow` over the limit fails with -1 like over the declared maximum.
+    pub fn max_memory_pages(mut self, pages: Option<usize>) -> Self {
+        self.config.max_memory_pages = pages;
+        self
+    }
+
+    /// Refuse to instantiate or grow a table beyond `elems` elements.
+    /// `table.grow` over the limit fails with -1 like over the declared maximum.
+    pub fn max_table_elems(mut self, elems: Option<usize>) -> Self {
+        self.config.max_table_elems = elems;
+        self
+    }
+
+    /// Report reads of declared locals which were not written yet to
+    /// `Interceptor::uninitialized_local`. The read still yields zero as the
+    /// spec says, so this only helps to find code relying on a value it
+    /// never set. Frames already running when this is enabled count their
+    /// declared locals as unwritten from then on.
+    pub fn poison_locals(mut self, enable: bool) -> Self {
+        self.config.poison_locals = enable;
+        self
+    }
+
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
+
+impl From<Config> for ConfigBuilder {
+    /// Continue building from an existing `config`
+    fn from(config: Config) -> Self {
+        Self { config }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_toggles_proposal_by_name() {
+        let config = Config::builder()
+            .feature("tail-call", true)
+            .and_then(|builder| builder.feature("bulk-memory", false))
+            .unwrap()
+            .build();
+        assert!(config.features.tail_call);
+        assert!(!config.features.bulk_memory);
+        assert!(config.feature_states().contains(&("tail-call", true)));
+        assert!(config.feature_enabled("tail-call"));
+        assert!(!config.feature_enabled("bulk-memory"));
+        assert!(Config::builder().feature("tail_call", true).is_none());
+    }
+
+    #[test]
+    fn new_keeps_defaults_besides_features() {
+        let features = WasmFeatures {
+            simd: true,
+            ..WasmFeatures::default()
+        };
+        let config = Config::new(features);
+        assert!(config.features.simd);
+        assert_eq!(config.fuel(), None);
+        assert_eq!(config.max_call_depth(), DEFAULT_MAX_CALL_DEPTH);
+    }
+
+    #[test]
+    fn costs_by_instruction_class() {
+        let costs = CostTable {
+            memory: 3,
+            call: 5,
+            float: 2,
+            other: 1,
+        };
+        let load = InstructionKind::F32Load {
+            memarg: wasmparser::MemoryImmediate {
+                align: 2,
+                offset: 0,
+                memory: 0,
+            },
+        };
+        assert_eq!(costs.cost(&load), 3);
+        assert_eq!(costs.cost(&InstructionKind::Call { function_index: 0 }), 5);
+        assert_eq!(costs.cost(&InstructionKind::F64Add), 2);
+        assert_eq!(costs.cost(&InstructionKind::I32Add), 1);
+    }
+
+    #[test]
+    fn feature_toggles_executor_only_proposal() {
+        let config = Config::builder()
+            .feature("extended-const", true)
+            .unwrap()
+            .build();
+        assert!(config.extended_const());
+        assert!(config.feature_enabled("extended-const"));
+        assert!(Config::FEATURE_NAMES.contains(&"extended-const"));
+        assert!(!Config::default().extended_const());
+    }
 }
@@ -25,6 +25,17 @@ impl std::fmt::Display for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Memory region an active data segment was copied to on instantiation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActiveDataSegment {
+    /// Index of the segment in the data section
+    pub segment_index: u32,
+    pub memory_index: u32,
+    pub offset: usize,
+    pub len: usize,
+}
+
+#[derive(Clone)]
 pub struct DataInstance {
     bytes: Vec<u8>,
 }
@@ -27,6 +27,7 @@ impl std::fmt::Display for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
+#[derive(Clone)]
 pub struct ElementInstance {
     _ty: RefType,
     elem: Vec<RefVal>,
@@ -65,6 +66,10 @@ impl ElementInstance {
             .map(|addr| *addr)
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.elem.is_empty()
+    }
+
     pub fn drop_elem(&mut self) {
         self.elem = vec![];
     }
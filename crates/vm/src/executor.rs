@@ -1,4 +1,6 @@
-use crate::address::{DataAddr, ElemAddr, FuncAddr, GlobalAddr, MemoryAddr, TableAddr};
+use crate::address::{
+    DataAddr, ElemAddr, ExecutableFuncAddr, FuncAddr, GlobalAddr, MemoryAddr, TableAddr,
+};
 use crate::config::Config;
 use crate::func::*;
 use crate::inst::{Instruction, InstructionKind};
@@ -7,7 +9,7 @@ use crate::memory::MemoryInstance;
 use crate::module::*;
 use crate::stack::{CallFrame, Label, ProgramCounter, Stack, StackValue};
 use crate::store::*;
-use crate::value::{Copysign, Nearest, RefType, RefVal, TruncSat, TruncTo};
+use crate::value::{Copysign, Nearest, NumVal, RefType, RefVal, TruncSat, TruncTo};
 use crate::value::{
     ExtendInto, FromLittleEndian, IntoLittleEndian, NativeValue, Value, F32, F64, I32, I64, U32,
     U64,
@@ -16,6 +18,7 @@ use crate::{data, elem, memory, stack, table, value};
 use wasmparser::{FuncType, Type, TypeOrFuncType};
 
 use std::convert::TryInto;
+use std::rc::Rc;
 use std::{ops::*, usize};
 
 #[derive(Debug)]
@@ -27,12 +30,19 @@ pub enum Trap {
     Value(value::Error),
     Element(elem::Error),
     Data(data::Error),
+    /// `call_indirect` through slot `slot` of table `table_index` found
+    /// `callee`, whose type isn't the expected one
     IndirectCallTypeMismatch {
+        callee: ExecutableFuncAddr,
         callee_name: String,
+        table_index: u32,
+        slot: usize,
         expected: FuncType,
         actual: FuncType,
     },
+    /// The operand stack didn't have the arguments `callee` expects
     DirectCallTypeMismatch {
+        callee: ExecutableFuncAddr,
         callee_name: String,
         expected: Vec<Type>,
         actual: Vec<Type>,
@@ -45,20 +55,81 @@ pub enum Trap {
         actual: Type,
     },
     UndefinedFunc(usize),
+    UndefinedTag(u32),
+    UndefinedType(u32),
+    UndefinedTable(u32),
+    InvalidLocalIndex(u32),
+    InvalidGlobalIndex(u32),
     ElementTypeMismatch {
         expected: RefType,
         actual: RefVal,
     },
     NoMoreInstruction,
     HostFunctionError(Box<dyn std::error::Error + Send + Sync>),
+    /// `HostFunctionError` returned by the host function imported as
+    /// `module_name.field_name`
+    HostFunctionFailed {
+        module_name: String,
+        field_name: String,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
     MemoryAddrOverflow {
         base: u32,
         offset: u64,
     },
+    OutOfFuel,
+    CallStackExhausted,
+    /// An exception thrown by `throw` which no handler caught
+    UncaughtException {
+        tag: u32,
+        values: Vec<Value>,
+    },
+    /// An instruction the executor doesn't implement, such as SIMD ones
+    UnsupportedInstruction {
+        opcode: String,
+    },
+    /// An instruction from a proposal which is disabled in the `Config`
+    FeatureDisabled {
+        opcode: String,
+        feature: &'static str,
+    },
+}
+
+impl Trap {
+    /// The function which was called with the wrong type, if this is a
+    /// call type mismatch
+    pub fn callee(&self) -> Option<ExecutableFuncAddr> {
+        match self {
+            Self::IndirectCallTypeMismatch { callee, .. }
+            | Self::DirectCallTypeMismatch { callee, .. } => Some(*callee),
+            _ => None,
+        }
+    }
 }
 
 impl std::error::Error for Trap {}
 
+/// The text format name of `ty`, like `i32` or `funcref`
+fn type_name(ty: &Type) -> String {
+    format!("{:?}", ty).to_lowercase()
+}
+
+/// `types` as a parenthesized list, like `(i32, i64)`
+fn format_types(types: &[Type]) -> String {
+    let names: Vec<String> = types.iter().map(type_name).collect();
+    format!("({})", names.join(", "))
+}
+
+/// `ty` as a signature like `(i32, i32) -> i32`
+fn format_signature(ty: &FuncType) -> String {
+    let params = format_types(&ty.params);
+    match &*ty.returns {
+        [] => params,
+        [ret] => format!("{} -> {}", params, type_name(ret)),
+        returns => format!("{} -> {}", params, format_types(returns)),
+    }
+}
+
 impl std::fmt::Display for Trap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -69,18 +140,63 @@ impl std::fmt::Display for Trap {
             Self::Element(e) => write!(f, "{}", e),
             Self::Data(e) => write!(f, "{}", e),
             Self::IndirectCallTypeMismatch {
+                callee_name,
+                table_index,
+                slot,
+                expected,
+                actual,
+                ..
+            } => write!(
+                f,
+                "indirect call type mismatch for '{}' at table[{}][{}]:
+ >> call_indirect instruction expected {}
+ >> but actual implementation has      {}",
+                callee_name,
+                table_index,
+                slot,
+                format_signature(expected),
+                format_signature(actual)
+            ),
+            Self::DirectCallTypeMismatch {
                 callee_name,
                 expected,
                 actual,
+                ..
             } => write!(
                 f,
-                "indirect call type mismatch for '{}':
- >> call_indirect instruction expected {:?}
- >> but actual implementation has      {:?}",
-                callee_name, expected, actual
+                "call type mismatch for '{}': expected arguments {} but found {} on the stack",
+                callee_name,
+                format_types(expected),
+                format_types(actual)
             ),
             Self::UndefinedFunc(addr) => write!(f, "uninitialized element {:?}", addr),
+            Self::UndefinedType(index) => write!(f, "undefined type index {}", index),
+            Self::UndefinedTable(index) => write!(f, "undefined table index {}", index),
+            Self::InvalidLocalIndex(index) => write!(f, "invalid local index {}", index),
+            Self::InvalidGlobalIndex(index) => write!(f, "invalid global index {}", index),
+            Self::HostFunctionFailed {
+                module_name,
+                field_name,
+                error,
+            } => write!(
+                f,
+                "host function {}.{} failed: {}",
+                module_name, field_name, error
+            ),
             Self::Unreachable => write!(f, "unreachable"),
+            Self::OutOfFuel => write!(f, "all fuel consumed"),
+            Self::CallStackExhausted => write!(f, "call stack exhausted"),
+            Self::UncaughtException { tag, values } => {
+                write!(f, "uncaught exception: tag {} with {:?}", tag, values)
+            }
+            Self::UnsupportedInstruction { opcode } => {
+                write!(f, "unsupported instruction: {}", opcode)
+            }
+            Self::FeatureDisabled { opcode, feature } => write!(
+                f,
+                "instruction {} requires the `{}` feature, which is disabled; pass `--enable {}`",
+                opcode, feature, feature
+            ),
             Self::MemoryAddrOverflow { base, offset } => write!(
                 f,
                 "out of bounds memory access: memory address overflow (base: {}, offset: {})",
@@ -140,19 +256,28 @@ impl std::fmt::Display for ReturnValError {
     }
 }
 
+#[derive(Clone)]
 pub struct Executor {
     pub pc: ProgramCounter,
     pub stack: Stack,
+    consumed_fuel: u64,
+    /// Scratch buffer to move block params above a new label
+    block_params: Vec<Value>,
 }
 
 impl Executor {
     pub fn new(initial_frame: CallFrame, initial_arity: usize, pc: ProgramCounter) -> Self {
         let mut stack = Stack::default();
-        let _ = stack.set_frame(initial_frame);
+        stack.set_frame(initial_frame);
         stack.push_label(Label::Return {
             arity: initial_arity,
         });
-        Self { pc, stack }
+        Self {
+            pc,
+            stack,
+            consumed_fuel: 0,
+            block_params: Vec::new(),
+        }
     }
 
     pub fn pop_result(&mut self, return_ty: Vec<Type>) -> ReturnValResult {
@@ -172,6 +297,16 @@ impl Executor {
         Ok(func.defined().unwrap().instructions())
     }
 
+    /// Targets of the structured instruction being executed, paired when
+    /// the function was loaded
+    fn current_block_targets(&self, store: &Store) -> BlockTargets {
+        let index = InstIndex(self.pc.inst_index().0 - 1);
+        let func = store.func_global(self.pc.exec_addr());
+        func.defined().unwrap().block_targets(index).unwrap()
+    }
+
+    /// Execute a single instruction. On trap, `pc` is left pointing at the
+    /// faulting instruction so that the state can still be inspected.
     pub fn execute_step<I: Interceptor>(
         &mut self,
         store: &Store,
@@ -185,8 +320,32 @@ impl Executor {
             None => return Err(Trap::NoMoreInstruction),
         };
 
-        let signal = interceptor.execute_inst(inst)?;
-        let result = self.execute_inst(inst, module_index, store, interceptor, config)?;
+        if config.poison_locals() {
+            let param_count = func.ty().params.len();
+            self.stack
+                .current_frame_mut()
+                .map_err(Trap::Stack)?
+                .track_written_locals(param_count);
+        }
+
+        if let Some(fuel) = config.fuel {
+            let consumed = self
+                .consumed_fuel
+                .saturating_add(config.costs.cost(&inst.kind));
+            if consumed > fuel {
+                return Err(Trap::OutOfFuel);
+            }
+            self.consumed_fuel = consumed;
+        }
+
+        let signal = interceptor.execute_inst(inst, self)?;
+        let pc = self.pc;
+        let result = self
+            .execute_inst(inst, module_index, store, interceptor, config)
+            .map_err(|trap| {
+                self.pc = pc;
+                trap
+            })?;
         Ok(match (signal, result) {
             (_, Signal::End) => Signal::End,
             (signal, Signal::Next) => signal,
@@ -207,52 +366,36 @@ impl Executor {
             InstructionKind::Unreachable => Err(Trap::Unreachable),
             InstructionKind::Nop => Ok(Signal::Next),
             InstructionKind::Block { ty } => {
+                let targets = self.current_block_targets(store);
                 let (params_size, results_size) = self.get_type_arity(ty, store)?;
-                let params = self.stack.pop_values(params_size).map_err(Trap::Stack)?;
-                self.stack.push_label(Label::Block {
+                let label = Label::Block {
                     arity: results_size,
-                });
-                self.stack.push_values(params.into_iter().rev());
+                    end: targets.end_index,
+                };
+                self.push_label_under_params(label, params_size)?;
                 Ok(Signal::Next)
             }
             InstructionKind::Loop { ty } => {
                 let start_loop = InstIndex(self.pc.inst_index().0 - 1);
                 let (params_size, _) = self.get_type_arity(ty, store)?;
-                let params = self.stack.pop_values(params_size).map_err(Trap::Stack)?;
-                self.stack
-                    .push_label(Label::new_loop(start_loop, params_size));
-                self.stack.push_values(params.into_iter().rev());
+                let label = Label::new_loop(start_loop, params_size);
+                self.push_label_under_params(label, params_size)?;
                 Ok(Signal::Next)
             }
             InstructionKind::If { ty } => {
+                let targets = self.current_block_targets(store);
                 let val: i32 = self.pop_as()?;
                 let (params_size, results_size) = self.get_type_arity(ty, store)?;
-                let params = self.stack.pop_values(params_size).map_err(Trap::Stack)?;
-                self.stack.push_label(Label::If {
+                let label = Label::If {
                     arity: results_size,
-                });
-                self.stack.push_values(params.into_iter().rev());
+                    end: targets.end_index,
+                };
+                self.push_label_under_params(label, params_size)?;
                 if val == 0 {
-                    let mut depth = 1;
-                    loop {
-                        let index = self.pc.inst_index().0 as usize;
-                        match self.current_func_insts(store)?[index].kind {
-                            InstructionKind::End => depth -= 1,
-                            InstructionKind::Block { ty: _ } => depth += 1,
-                            InstructionKind::If { ty: _ } => depth += 1,
-                            InstructionKind::Loop { ty: _ } => depth += 1,
-                            InstructionKind::Else => {
-                                if depth == 1 {
-                                    self.pc.inc_inst_index();
-                                    break;
-                                }
-                            }
-                            _ => (),
-                        }
-                        if depth == 0 {
-                            break;
-                        }
-                        self.pc.inc_inst_index();
+                    match targets.else_index {
+                        Some(else_index) => self.pc.jump(InstIndex(else_index.0 + 1)),
+                        // Without `else`, `end` pops the label
+                        None => self.pc.jump(targets.end_index),
                     }
                 }
                 Ok(Signal::Next)
@@ -300,7 +443,8 @@ impl Executor {
             }
             InstructionKind::BrTable { table: payload } => {
                 let val: i32 = self.pop_as()?;
-                let val = val as usize;
+                // The operand is unsigned, so negative values take the default too
+                let val = val as u32 as usize;
                 let depth = if val < payload.table.len() {
                     payload.table[val]
                 } else {
@@ -309,18 +453,40 @@ impl Executor {
                 self.branch(depth, store)
             }
             InstructionKind::Return => self.do_return(store),
+            InstructionKind::Throw { index } => {
+                // Handlers aren't supported yet, so every exception is uncaught
+                let module = store.module(module_index).defined().unwrap();
+                let arity = match module.get_tag_type(*index as usize) {
+                    Some(ty) => ty.params.len(),
+                    None => return Err(Trap::UndefinedTag(*index)),
+                };
+                let mut values = self.stack.pop_values(arity).map_err(Trap::Stack)?;
+                values.reverse();
+                Err(Trap::UncaughtException {
+                    tag: *index,
+                    values,
+                })
+            }
             InstructionKind::Call { function_index } => {
                 let frame = self.stack.current_frame().map_err(Trap::Stack)?;
-                let addr = FuncAddr::new_unsafe(frame.module_index(), *function_index as usize);
-                self.invoke(addr, store, interceptor)
+                let module_index = frame.module_index();
+                let module = store.module(module_index).defined().unwrap();
+                let exec_addr = module
+                    .resolved_func(*function_index as usize)
+                    .ok_or(Trap::UndefinedFunc(*function_index as usize))?;
+                self.invoke(module_index, exec_addr, store, interceptor, config)
             }
             InstructionKind::CallIndirect { index, table_index } => {
                 let frame = self.stack.current_frame().map_err(Trap::Stack)?;
-                let addr = TableAddr::new_unsafe(frame.module_index(), *table_index as usize);
                 let module = store.module(frame.module_index()).defined().unwrap();
-                let ty = module.get_type(*index as usize);
+                let ty = module
+                    .get_type(*index as usize)
+                    .ok_or(Trap::UndefinedType(*index))?;
+                let table_addr = module
+                    .resolved_table(*table_index as usize)
+                    .ok_or(Trap::UndefinedTable(*table_index))?;
                 let buf_index: i32 = self.pop_as()?;
-                let table = store.table(addr);
+                let table = store.table_global(table_addr);
                 let buf_index = buf_index as usize;
                 let func_ref = table.borrow().get_at(buf_index).map_err(Trap::Table)?;
 
@@ -332,14 +498,18 @@ impl Executor {
                         actual: other,
                     }),
                 }?;
-                let (func, _) = store
+                let (func, exec_addr) = store
                     .func(func_addr)
                     .ok_or(Trap::UndefinedFunc(func_addr.1))?;
                 if func.ty() == ty {
-                    self.invoke(func_addr, store, interceptor)
+                    let module_index = func_addr.module_index();
+                    self.invoke(module_index, exec_addr, store, interceptor, config)
                 } else {
                     Err(Trap::IndirectCallTypeMismatch {
+                        callee: exec_addr,
                         callee_name: func.name().clone(),
+                        table_index: *table_index,
+                        slot: buf_index,
                         expected: ty.clone(),
                         actual: func.ty().clone(),
                     })
@@ -353,6 +523,19 @@ impl Executor {
                 let cond: i32 = self.pop_as()?;
                 let val2 = self.stack.pop_value().map_err(Trap::Stack)?;
                 let val1 = self.stack.pop_value().map_err(Trap::Stack)?;
+                // Untyped select only takes numbers, whose operands must share a type
+                let expected = match &inst.kind {
+                    InstructionKind::TypedSelect { ty } => *ty,
+                    _ => val1.value_type(),
+                };
+                for val in [val1, val2].iter() {
+                    if !val.isa(expected) {
+                        return Err(Trap::UnexpectedStackValueType {
+                            expected,
+                            actual: val.value_type(),
+                        });
+                    }
+                }
                 if cond != 0 {
                     self.stack.push_value(val1);
                 } else {
@@ -361,31 +544,39 @@ impl Executor {
                 Ok(Signal::Next)
             }
             InstructionKind::LocalGet { local_index } => {
-                let value = self
-                    .stack
-                    .current_frame()
-                    .map_err(Trap::Stack)?
-                    .local(*local_index as usize);
+                let frame = self.stack.current_frame().map_err(Trap::Stack)?;
+                let value = frame
+                    .local(*local_index as usize)
+                    .ok_or(Trap::InvalidLocalIndex(*local_index))?;
+                let written = frame.is_local_written(*local_index as usize);
                 self.stack.push_value(value);
-                Ok(Signal::Next)
+                if written {
+                    Ok(Signal::Next)
+                } else {
+                    interceptor.uninitialized_local(*local_index, self)
+                }
             }
-            InstructionKind::LocalSet { local_index } => self.set_local(*local_index as usize),
+            InstructionKind::LocalSet { local_index } => self.set_local(*local_index),
             InstructionKind::LocalTee { local_index } => {
                 let val = self.stack.pop_value().map_err(Trap::Stack)?;
                 self.stack.push_value(val);
                 self.stack.push_value(val);
-                self.set_local(*local_index as usize)
+                self.set_local(*local_index)
             }
             InstructionKind::GlobalGet { global_index } => {
                 let addr = GlobalAddr::new_unsafe(module_index, *global_index as usize);
-                let global = store.global(addr);
+                let global = store
+                    .global(addr)
+                    .ok_or(Trap::InvalidGlobalIndex(*global_index))?;
                 self.stack.push_value(global.borrow().value());
                 Ok(Signal::Next)
             }
             InstructionKind::GlobalSet { global_index } => {
                 let addr = GlobalAddr::new_unsafe(module_index, *global_index as usize);
                 let value = self.stack.pop_value().map_err(Trap::Stack)?;
-                let global = store.global(addr);
+                let global = store
+                    .global(addr)
+                    .ok_or(Trap::InvalidGlobalIndex(*global_index))?;
                 global.borrow_mut().set_value(value);
                 Ok(Signal::Next)
             }
@@ -419,7 +610,11 @@ impl Executor {
                 let sz = table.borrow().buffer_len();
                 let n: i32 = self.pop_as()?;
                 let ref_val = self.pop_ref()?;
-                let ret_val = match table.borrow_mut().grow(n as usize, ref_val) {
+                let ret_val = match table.borrow_mut().grow_with_limit(
+                    n as u32 as usize,
+                    ref_val,
+                    config.max_table_elems(),
+                ) {
                     Ok(_) => sz as i32,
                     Err(_) => -1,
                 };
@@ -433,13 +628,7 @@ impl Executor {
                 let n = self.pop_as::<i32>()? as usize;
                 let ref_val = self.pop_ref()?;
                 let index = self.pop_as::<i32>()? as usize;
-
-                table.borrow().validate_region(index, n)?;
-
-                for index in index..(index + n) {
-                    table.borrow_mut().set_at(index, ref_val)?;
-                }
-
+                table.borrow_mut().fill(index, n, ref_val)?;
                 Ok(Signal::Next)
             }
 
@@ -455,15 +644,12 @@ impl Executor {
                 let src_base = self.pop_as::<i32>()? as usize;
                 let dst_base = self.pop_as::<i32>()? as usize;
 
-                let values = (0..n)
-                    .map(|offset| -> ExecResult<_> {
-                        Ok(src_table.borrow().get_at(src_base + offset)?)
-                    })
-                    .collect::<ExecResult<Vec<_>>>()?;
-                src_table.borrow().validate_region(src_base, n)?;
-                dst_table.borrow().validate_region(dst_base, n)?;
-                for (offset, val) in values.into_iter().enumerate().take(n) {
-                    dst_table.borrow_mut().set_at(dst_base + offset, val)?;
+                if Rc::ptr_eq(&dst_table, &src_table) {
+                    dst_table.borrow_mut().copy_within(src_base, dst_base, n)?;
+                } else {
+                    let src_table = src_table.borrow();
+                    let values = src_table.elements(src_base, n)?;
+                    dst_table.borrow_mut().set_elements(dst_base, values)?;
                 }
 
                 Ok(Signal::Next)
@@ -569,15 +755,16 @@ impl Executor {
                 let grow_page: i32 = self.pop_as()?;
                 let mem = self.memory(store)?;
                 let size = mem.borrow().page_count();
-                match mem.borrow_mut().grow(grow_page as usize) {
-                    Ok(_) => {
-                        self.stack.push_value(Value::I32(size as i32));
-                    }
-                    Err(err) => {
-                        println!("[Debug] Failed to grow memory {:?}", err);
-                        self.stack.push_value(Value::I32(-1));
-                    }
-                }
+                // The operand is unsigned. Both kinds of grow failure are -1
+                // per spec, `MemoryInstance::grow` tells them apart for hosts.
+                let result = match mem
+                    .borrow_mut()
+                    .grow_with_limit(grow_page as u32 as usize, config.max_memory_pages())
+                {
+                    Ok(_) => size as i32,
+                    Err(_) => -1,
+                };
+                self.stack.push_value(Value::I32(result));
                 Ok(Signal::Next)
             }
             InstructionKind::MemoryCopy { src, dst } => {
@@ -589,16 +776,13 @@ impl Executor {
                 let src_base = self.pop_as::<i32>()? as usize;
                 let dst_base = self.pop_as::<i32>()? as usize;
 
-                src_mem.borrow().validate_region(src_base, n)?;
-
-                let values = (0..n)
-                    .map(|offset| -> ExecResult<_> {
-                        Ok(src_mem.borrow().load_as::<u8>(src_base + offset)?)
-                    })
-                    .collect::<ExecResult<Vec<_>>>()?;
-
-                dst_mem.borrow().validate_region(dst_base, n)?;
-                dst_mem.borrow_mut().store(dst_base, &values)?;
+                if Rc::ptr_eq(&dst_mem, &src_mem) {
+                    dst_mem.borrow_mut().copy_within(src_base, dst_base, n)?;
+                } else {
+                    let src_mem = src_mem.borrow();
+                    let values = src_mem.bytes(src_base, n)?;
+                    dst_mem.borrow_mut().store(dst_base, values)?;
+                }
 
                 Ok(Signal::Next)
             }
@@ -609,12 +793,7 @@ impl Executor {
                 let val = self.pop_as::<i32>()?;
                 let val = val.to_le_bytes()[0];
                 let offset = self.pop_as::<i32>()? as usize;
-
-                mem.borrow().validate_region(offset, n)?;
-
-                mem.borrow_mut()
-                    .store(offset, &std::iter::repeat(val).take(n).collect::<Vec<_>>())?;
-
+                mem.borrow_mut().fill(offset, n, val)?;
                 Ok(Signal::Next)
             }
             InstructionKind::MemoryInit { segment, mem } => {
@@ -832,8 +1011,20 @@ impl Executor {
             InstructionKind::I64TruncSatF32U => self.unop::<F32, _, _>(TruncSat::<u64>::trunc_sat),
             InstructionKind::I64TruncSatF64S => self.unop::<F64, _, _>(TruncSat::<i64>::trunc_sat),
             InstructionKind::I64TruncSatF64U => self.unop::<F64, _, _>(TruncSat::<u64>::trunc_sat),
-            other => unimplemented!("{:?}", other),
+            other => match other.required_feature() {
+                Some(feature) if !config.feature_enabled(feature) => Err(Trap::FeatureDisabled {
+                    opcode: other.mnemonic().to_string(),
+                    feature,
+                }),
+                _ => Err(Trap::UnsupportedInstruction {
+                    opcode: other.mnemonic().to_string(),
+                }),
+            },
         };
+        if config.canonicalize_nans && result.is_ok() && is_float_arithmetic(&inst.kind) {
+            let value = self.stack.pop_value().map_err(Trap::Stack)?;
+            self.stack.push_value(value.canonicalize_nan());
+        }
         if self.stack.is_over_top_level() {
             Ok(Signal::End)
         } else {
@@ -887,23 +1078,7 @@ impl Executor {
             Label::Return { .. } => {
                 return self.do_return(store);
             }
-            Label::If { .. } | Label::Block { .. } => {
-                let mut depth = depth + 1;
-                loop {
-                    let index = self.pc.inst_index().0 as usize;
-                    match self.current_func_insts(store)?[index].kind {
-                        InstructionKind::End => depth -= 1,
-                        InstructionKind::Block { ty: _ } => depth += 1,
-                        InstructionKind::If { ty: _ } => depth += 1,
-                        InstructionKind::Loop { ty: _ } => depth += 1,
-                        _ => (),
-                    }
-                    self.pc.inc_inst_index();
-                    if depth == 0 {
-                        break;
-                    }
-                }
-            }
+            Label::If { end, .. } | Label::Block { end, .. } => self.pc.jump(InstIndex(end.0 + 1)),
         }
         Ok(Signal::Next)
     }
@@ -956,13 +1131,34 @@ impl Executor {
         Ok(Signal::Next)
     }
 
+    /// Push `label` below the top `params_size` values, which become the
+    /// params of the entered block
+    fn push_label_under_params(&mut self, label: Label, params_size: usize) -> ExecResult<()> {
+        if params_size == 0 {
+            self.stack.push_label(label);
+            return Ok(());
+        }
+        self.block_params.clear();
+        for _ in 0..params_size {
+            let value = self.stack.pop_value().map_err(Trap::Stack)?;
+            self.block_params.push(value);
+        }
+        self.stack.push_label(label);
+        self.stack.push_values(self.block_params.drain(..).rev());
+        Ok(())
+    }
+
+    /// Call the function at `exec_addr`, referenced from the module
+    /// `module_index`
     fn invoke<I: Interceptor>(
         &mut self,
-        addr: FuncAddr,
+        module_index: ModuleIndex,
+        exec_addr: ExecutableFuncAddr,
         store: &Store,
         interceptor: &I,
+        config: &Config,
     ) -> ExecResult<Signal> {
-        let (func, exec_addr) = store.func(addr).ok_or(Trap::UndefinedFunc(addr.1))?;
+        let func = store.func_global(exec_addr);
 
         let mut args = Vec::new();
         let mut found_mismatch = false;
@@ -975,6 +1171,7 @@ impl Executor {
 
         if found_mismatch {
             return Err(Trap::DirectCallTypeMismatch {
+                callee: exec_addr,
                 callee_name: func.name().to_string(),
                 actual: args.iter().map(|v| v.value_type()).collect(),
                 expected: func.ty().params.to_vec(),
@@ -986,16 +1183,29 @@ impl Executor {
         match func {
             FunctionInstance::Defined(func) => {
                 let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
+                if self.stack.frame_depth() >= config.max_call_depth {
+                    return Err(Trap::CallStackExhausted);
+                }
                 let frame = CallFrame::new_from_func(exec_addr, func, args, Some(self.pc));
-                self.stack.set_frame(frame).map_err(Trap::Stack)?;
+                self.stack.set_frame(frame);
                 self.stack.push_label(Label::Return { arity });
                 self.pc = pc;
                 interceptor.invoke_func(func.name(), self, store)
             }
             FunctionInstance::Native(func) => {
+                // Host functions run within the calling step without a frame,
+                // leaving the pc at the instruction after the call
                 let mut result = Vec::new();
                 func.code()
-                    .call(&args, &mut result, store, addr.module_index())?;
+                    .call(&args, &mut result, store, module_index)
+                    .map_err(|trap| match trap {
+                        Trap::HostFunctionError(error) => Trap::HostFunctionFailed {
+                            module_name: func.module_name().clone(),
+                            field_name: func.field_name().clone(),
+                            error,
+                        },
+                        trap => trap,
+                    })?;
                 assert_eq!(result.len(), arity);
                 for v in result {
                     self.stack.push_value(v);
@@ -1028,15 +1238,22 @@ impl Executor {
             TypeOrFuncType::FuncType(type_id) => {
                 let frame = self.stack.current_frame().map_err(Trap::Stack)?;
                 let module = store.module(frame.module_index()).defined().unwrap();
-                let ty = module.get_type(*type_id as usize);
+                let ty = module
+                    .get_type(*type_id as usize)
+                    .ok_or(Trap::UndefinedType(*type_id))?;
                 (ty.params.len(), ty.returns.len())
             }
         })
     }
 
-    fn set_local(&mut self, index: usize) -> ExecResult<Signal> {
+    fn set_local(&mut self, index: u32) -> ExecResult<Signal> {
         let value = self.stack.pop_value().map_err(Trap::Stack)?;
-        self.stack.set_local(index, value).map_err(Trap::Stack)?;
+        let frame = self.stack.current_frame_mut().map_err(Trap::Stack)?;
+        let local = frame
+            .local_mut(index as usize)
+            .ok_or(Trap::InvalidLocalIndex(index))?;
+        *local = value;
+        frame.mark_local_written(index as usize);
 
         Ok(Signal::Next)
     }
@@ -1147,30 +1364,102 @@ pub fn eval_const_expr(
     init_expr: &InitExpr,
     store: &Store,
     module_index: ModuleIndex,
+    config: &Config,
 ) -> anyhow::Result<Value> {
     use crate::inst::transform_inst;
     let mut reader = init_expr.get_operators_reader();
     let base_offset = reader.original_position();
-    let inst = transform_inst(&mut reader, base_offset)?;
-    let val = match inst.kind {
-        InstructionKind::I32Const { value } => Value::I32(value),
-        InstructionKind::I64Const { value } => Value::I64(value),
-        InstructionKind::F32Const { value } => Value::F32(value.bits()),
-        InstructionKind::F64Const { value } => Value::F64(value.bits()),
-        InstructionKind::RefNull { ty } => match Value::null_ref(ty) {
-            Some(v) => v,
-            None => panic!("unsupported ref type"),
-        },
-        InstructionKind::RefFunc { function_index } => Value::Ref(RefVal::FuncRef(
-            FuncAddr::new_unsafe(module_index, function_index as usize),
+    // Extended constant expressions compute their value on a stack of their own
+    let mut stack = Vec::new();
+    loop {
+        let inst = transform_inst(&mut reader, base_offset)?;
+        let val = match inst.kind {
+            InstructionKind::End => break,
+            InstructionKind::I32Const { value } => Value::I32(value),
+            InstructionKind::I64Const { value } => Value::I64(value),
+            InstructionKind::F32Const { value } => Value::F32(value.bits()),
+            InstructionKind::F64Const { value } => Value::F64(value.bits()),
+            InstructionKind::RefNull { ty } => match Value::null_ref(ty) {
+                Some(v) => v,
+                None => return Err(anyhow::anyhow!("unsupported ref type {:?}", ty)),
+            },
+            InstructionKind::RefFunc { function_index } => Value::Ref(RefVal::FuncRef(
+                FuncAddr::new_unsafe(module_index, function_index as usize),
+            )),
+            InstructionKind::GlobalGet { global_index } => {
+                let addr = GlobalAddr::new_unsafe(module_index, global_index as usize);
+                let global = store
+                    .global(addr)
+                    .ok_or_else(|| anyhow::anyhow!("undefined global {}", global_index))?;
+                let value = global.borrow().value();
+                value
+            }
+            kind @ (InstructionKind::I32Add
+            | InstructionKind::I32Sub
+            | InstructionKind::I32Mul
+            | InstructionKind::I64Add
+            | InstructionKind::I64Sub
+            | InstructionKind::I64Mul) => {
+                if !config.extended_const() {
+                    return Err(anyhow::anyhow!(
+                        "{} in init_expr requires the `extended-const` feature, which is disabled; pass `--enable extended-const`",
+                        kind.mnemonic()
+                    ));
+                }
+                let rhs = stack.pop();
+                let lhs = stack.pop();
+                eval_const_binop(kind, lhs, rhs)?
+            }
+            other => return Err(anyhow::anyhow!("unsupported init_expr {:?}", other)),
+        };
+        stack.push(val);
+    }
+    match stack.as_slice() {
+        [val] => Ok(*val),
+        values => Err(anyhow::anyhow!(
+            "init_expr must produce exactly one value, got {}",
+            values.len()
         )),
-        InstructionKind::GlobalGet { global_index } => {
-            let addr = GlobalAddr::new_unsafe(module_index, global_index as usize);
-            store.global(addr).borrow().value()
+    }
+}
+
+fn eval_const_binop(
+    kind: InstructionKind,
+    lhs: Option<Value>,
+    rhs: Option<Value>,
+) -> anyhow::Result<Value> {
+    use InstructionKind::{I32Add, I32Mul, I32Sub, I64Add, I64Mul, I64Sub};
+    let (lhs, rhs) = lhs
+        .zip(rhs)
+        .ok_or_else(|| anyhow::anyhow!("missing operand of {:?} in init_expr", kind))?;
+    Ok(match (kind, lhs, rhs) {
+        (I32Add, Value::Num(NumVal::I32(a)), Value::Num(NumVal::I32(b))) => {
+            Value::I32(a.wrapping_add(b))
         }
-        _ => panic!("Unsupported init_expr {:?}", inst.kind),
-    };
-    Ok(val)
+        (I32Sub, Value::Num(NumVal::I32(a)), Value::Num(NumVal::I32(b))) => {
+            Value::I32(a.wrapping_sub(b))
+        }
+        (I32Mul, Value::Num(NumVal::I32(a)), Value::Num(NumVal::I32(b))) => {
+            Value::I32(a.wrapping_mul(b))
+        }
+        (I64Add, Value::Num(NumVal::I64(a)), Value::Num(NumVal::I64(b))) => {
+            Value::I64(a.wrapping_add(b))
+        }
+        (I64Sub, Value::Num(NumVal::I64(a)), Value::Num(NumVal::I64(b))) => {
+            Value::I64(a.wrapping_sub(b))
+        }
+        (I64Mul, Value::Num(NumVal::I64(a)), Value::Num(NumVal::I64(b))) => {
+            Value::I64(a.wrapping_mul(b))
+        }
+        (kind, lhs, rhs) => {
+            return Err(anyhow::anyhow!(
+                "{:?} can't be applied to {:?} and {:?} in init_expr",
+                kind,
+                lhs,
+                rhs
+            ))
+        }
+    })
 }
 
 #[derive(Debug)]
@@ -1179,8 +1468,14 @@ pub enum WasmError {
     EntryFunctionNotFound(String),
     ReturnValueError(ReturnValError),
     HostExecutionError,
+    ArgumentTypeMismatch {
+        expected: Vec<Type>,
+        actual: Vec<Type>,
+    },
 }
 
+impl std::error::Error for WasmError {}
+
 impl std::fmt::Display for WasmError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -1192,6 +1487,43 @@ impl std::fmt::Display for WasmError {
                 write!(f, "Failed to get returned value: {:?}", err)
             }
             WasmError::HostExecutionError => write!(f, "Failed to execute host func"),
+            WasmError::ArgumentTypeMismatch { expected, actual } => write!(
+                f,
+                "Argument type mismatch, expected {:?} but got {:?}",
+                expected, actual
+            ),
         }
     }
 }
+
+/// Float operations whose NaN results may carry any payload
+fn is_float_arithmetic(kind: &InstructionKind) -> bool {
+    use InstructionKind::*;
+    matches!(
+        kind,
+        F32Ceil
+            | F32Floor
+            | F32Trunc
+            | F32Nearest
+            | F32Sqrt
+            | F32Add
+            | F32Sub
+            | F32Mul
+            | F32Div
+            | F32Min
+            | F32Max
+            | F64Ceil
+            | F64Floor
+            | F64Trunc
+            | F64Nearest
+            | F64Sqrt
+            | F64Add
+            | F64Sub
+            | F64Mul
+            | F64Div
+            | F64Min
+            | F64Max
+            | F32DemoteF64
+            | F64PromoteF32
+    )
+}
@@ -5,7 +5,7 @@ use crate::host::HostFuncBody;
 use crate::inst::*;
 use crate::module::*;
 use crate::value::Value;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::iter;
 use wasmparser::{FuncType, FunctionBody, Type};
 
@@ -18,6 +18,52 @@ impl InstIndex {
     }
 }
 
+/// The matching `else` and `end` of a `block`, `loop`, `if` or `try`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BlockTargets {
+    pub else_index: Option<InstIndex>,
+    pub end_index: InstIndex,
+}
+
+/// Pair every structured instruction with its `else` and `end` so that
+/// branches can jump without scanning the body.
+/// Entries of other instructions are `None`.
+fn block_targets(instructions: &[Instruction]) -> Result<Vec<Option<BlockTargets>>> {
+    let mut targets = vec![None; instructions.len()];
+    let mut open_blocks = Vec::new();
+    let mut body_closed = false;
+    for (index, inst) in instructions.iter().enumerate() {
+        let inst_index = InstIndex(index as u32);
+        match inst.kind {
+            InstructionKind::Block { .. }
+            | InstructionKind::Loop { .. }
+            | InstructionKind::If { .. }
+            | InstructionKind::Try { .. } => open_blocks.push((index, None)),
+            InstructionKind::Else => match open_blocks.last_mut() {
+                Some((_, else_index)) => *else_index = Some(inst_index),
+                None => return Err(anyhow!("else without if at 0x{:x}", inst.offset)),
+            },
+            // `delegate` closes its `try` in place of `end`
+            InstructionKind::End | InstructionKind::Delegate { .. } => match open_blocks.pop() {
+                Some((start, else_index)) => {
+                    targets[start] = Some(BlockTargets {
+                        else_index,
+                        end_index: inst_index,
+                    })
+                }
+                // The last `end` closes the function body itself
+                None if index + 1 == instructions.len() => body_closed = true,
+                None => return Err(anyhow!("unmatched end at 0x{:x}", inst.offset)),
+            },
+            _ => (),
+        }
+    }
+    if !body_closed {
+        return Err(anyhow!("function body is not terminated"));
+    }
+    Ok(targets)
+}
+
 pub enum FunctionInstance {
     Defined(DefinedFunctionInstance),
     Native(NativeFunctionInstance),
@@ -51,6 +97,7 @@ pub struct DefinedFunctionInstance {
     ty: FuncType,
     module_index: ModuleIndex,
     instructions: Vec<Instruction>,
+    block_targets: Vec<Option<BlockTargets>>,
     default_locals: Vec<Value>,
 }
 
@@ -75,6 +122,7 @@ impl DefinedFunctionInstance {
             let inst = transform_inst(&mut reader, base_offset)?;
             instructions.push(inst);
         }
+        let block_targets = block_targets(&instructions)?;
 
         // Compute default local values here instead of frame initialization
         // to avoid re-computation
@@ -89,7 +137,7 @@ impl DefinedFunctionInstance {
                 Type::F64 => Value::F64(0),
                 Type::ExternRef => Value::Ref(RefVal::NullRef(RefType::ExternRef)),
                 Type::FuncRef => Value::Ref(RefVal::NullRef(RefType::FuncRef)),
-                _ => unimplemented!("local initialization of type {:?}", ty),
+                _ => return Err(anyhow!("unsupported local of type {:?}", ty)),
             };
             default_locals.push(v);
         }
@@ -99,6 +147,7 @@ impl DefinedFunctionInstance {
             ty,
             module_index,
             instructions,
+            block_targets,
             default_locals,
         })
     }
@@ -115,7 +164,7 @@ impl DefinedFunctionInstance {
         self.module_index
     }
 
-    pub(crate) fn instructions(&self) -> &[Instruction] {
+    pub fn instructions(&self) -> &[Instruction] {
         &self.instructions
     }
 
@@ -123,6 +172,11 @@ impl DefinedFunctionInstance {
         self.instructions.get(index.0 as usize)
     }
 
+    /// Targets of the structured instruction at `index`
+    pub(crate) fn block_targets(&self, index: InstIndex) -> Option<BlockTargets> {
+        self.block_targets.get(index.0 as usize).copied().flatten()
+    }
+
     pub(crate) fn default_locals(&self) -> &[Value] {
         &self.default_locals
     }
@@ -161,3 +215,67 @@ impl NativeFunctionInstance {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmparser::TypeOrFuncType;
+
+    fn inst(kind: InstructionKind) -> Instruction {
+        Instruction {
+            kind,
+            offset: 0,
+            len: 1,
+        }
+    }
+
+    #[test]
+    fn block_targets_pair_nested_blocks() {
+        let ty = TypeOrFuncType::Type(Type::EmptyBlockType);
+        let instructions = vec![
+            inst(InstructionKind::Block { ty }),
+            inst(InstructionKind::If { ty }),
+            inst(InstructionKind::Loop { ty }),
+            inst(InstructionKind::End),
+            inst(InstructionKind::Else),
+            inst(InstructionKind::Nop),
+            inst(InstructionKind::End),
+            inst(InstructionKind::If { ty }),
+            inst(InstructionKind::End),
+            inst(InstructionKind::End),
+            // The function body
+            inst(InstructionKind::End),
+        ];
+        let targets = block_targets(&instructions).unwrap();
+        let pairs: Vec<_> = targets
+            .iter()
+            .map(|t| t.map(|t| (t.else_index.map(|i| i.0), t.end_index.0)))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                Some((None, 9)),
+                Some((Some(4), 6)),
+                Some((None, 3)),
+                None,
+                None,
+                None,
+                None,
+                Some((None, 8)),
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn block_targets_reject_unterminated_blocks() {
+        let ty = TypeOrFuncType::Type(Type::EmptyBlockType);
+        let instructions = vec![
+            inst(InstructionKind::Block { ty }),
+            inst(InstructionKind::End),
+        ];
+        assert!(block_targets(&instructions).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::value::Value;
 use wasmparser::GlobalType;
 
+#[derive(Clone)]
 pub struct GlobalInstance {
     ty: GlobalType,
     value: Value,
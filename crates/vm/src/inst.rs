@@ -1,10 +1,12 @@
 use std::convert::TryFrom;
-use wasminspect_vm_macro::TryFromWasmParserOperator;
+use wasminspect_vm_macro::{InstructionInfo, TryFromWasmParserOperator};
 use wasmparser::*;
 #[derive(Debug, Clone)]
 pub struct Instruction {
     pub kind: InstructionKind,
     pub offset: usize,
+    /// Size of the instruction's encoding in bytes, starting at `offset`
+    pub len: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -41,7 +43,7 @@ impl WasmInstPayloadFrom<BrTable<'_>> for BrTableData {
 type I8x16ShuffleLanes = Vec<u8>;
 pub type SIMDLaneIndex = u8;
 
-#[derive(Debug, Clone, TryFromWasmParserOperator)]
+#[derive(Debug, Clone, TryFromWasmParserOperator, InstructionInfo)]
 pub enum InstructionKind {
     Unreachable,
     Nop,
@@ -923,6 +925,74 @@ pub enum InstructionKind {
     F64x2PromoteLowF32x4,
 }
 
+/// Coarse kinds of instructions, which `CostTable` assigns fuel costs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstructionClass {
+    /// Loads, stores, atomics and the `memory.*` and `data.drop` instructions
+    Memory,
+    /// Direct, indirect and tail calls
+    Call,
+    /// Instructions on `f32` and `f64` values other than loads and stores
+    Float,
+    Other,
+}
+
+impl InstructionKind {
+    /// The class this instruction is charged as by `CostTable`
+    pub fn class(&self) -> InstructionClass {
+        use InstructionKind::*;
+        match self {
+            Call { .. } | CallIndirect { .. } | ReturnCall { .. } | ReturnCallIndirect { .. } => {
+                InstructionClass::Call
+            }
+            MemorySize { .. }
+            | MemoryGrow { .. }
+            | MemoryInit { .. }
+            | DataDrop { .. }
+            | MemoryCopy { .. }
+            | MemoryFill { .. } => InstructionClass::Memory,
+            _ if self.accesses_memory() => InstructionClass::Memory,
+            _ if self.is_float() => InstructionClass::Float,
+            _ => InstructionClass::Other,
+        }
+    }
+
+    /// Name of the proposal in `Config::FEATURE_NAMES` which introduced this
+    /// instruction, or `None` for MVP instructions
+    pub fn required_feature(&self) -> Option<&'static str> {
+        use InstructionKind::*;
+        let feature = match self {
+            Try { .. }
+            | Catch { .. }
+            | Throw { .. }
+            | Rethrow { .. }
+            | Delegate { .. }
+            | CatchAll => "exceptions",
+            ReturnCall { .. } | ReturnCallIndirect { .. } => "tail-call",
+            MemoryInit { .. }
+            | DataDrop { .. }
+            | MemoryCopy { .. }
+            | MemoryFill { .. }
+            | TableInit { .. }
+            | ElemDrop { .. }
+            | TableCopy { .. } => "bulk-memory",
+            RefNull { .. }
+            | RefIsNull
+            | RefFunc { .. }
+            | TypedSelect { .. }
+            | TableFill { .. }
+            | TableGet { .. }
+            | TableSet { .. }
+            | TableGrow { .. }
+            | TableSize { .. } => "reference-types",
+            _ if self.is_atomic() => "threads",
+            _ if self.is_simd() => "simd",
+            _ => return None,
+        };
+        Some(feature)
+    }
+}
+
 pub fn transform_inst(
     reader: &mut OperatorsReader,
     base_offset: usize,
@@ -932,5 +1002,6 @@ pub fn transform_inst(
     Ok(Instruction {
         kind,
         offset: offset - base_offset,
+        len: reader.original_position() - offset,
     })
 }
@@ -1,18 +1,71 @@
+use crate::address::FuncAddr;
 use crate::config::Config;
 use crate::executor::WasmError;
+use crate::export::ExternalValue;
 use crate::host::HostValue;
-use crate::invoke_func_ignoring_break;
-use crate::module::ModuleIndex;
+use crate::interceptor::{Interceptor, Interceptors};
+use crate::invoke_func_with_interceptor;
+use crate::module::{HostExport, ModuleIndex, ModuleInstance};
 use crate::store::Store;
 use crate::value::Value;
 use std::collections::HashMap;
+use wasmparser::FuncType;
 
 use anyhow::Result;
 use std::io::Read;
 
+/// An export of a module, as listed by `WasmInstance::exports`
+#[derive(Debug, Clone)]
+pub struct ExportInfo {
+    pub name: String,
+    pub kind: ExportKind,
+}
+
+/// What an export refers to. Functions come with their signature.
+#[derive(Debug, Clone)]
+pub enum ExportKind {
+    Func(FuncType),
+    Global,
+    Memory,
+    Table,
+}
+
+/// A module loaded into a `WasmInstance`, as passed to its
+/// `on_instantiate` hook
+#[derive(Debug, Clone)]
+pub struct ModuleSummary {
+    pub module_index: ModuleIndex,
+    /// The name the module was loaded under, if any
+    pub name: Option<String>,
+    /// `(module, field)` of each import. Host modules import nothing.
+    pub imports: Vec<(String, String)>,
+    pub exports: Vec<ExportInfo>,
+}
+
+type InstantiateHook = Box<dyn FnMut(&ModuleSummary)>;
+
+/// A `Store` with its modules, and the entry point to run them
+///
+/// # Threads
+///
+/// Instances are neither `Send` nor `Sync`. Memories, tables and globals are
+/// shared with the modules importing them through `Rc<RefCell<_>>`, and host
+/// functions are plain closures, so an instance has to stay on the thread
+/// which created it. Embedders serving several clients should give each one
+/// its own thread and pass requests to it over a channel, like
+/// `wasminspect-debugger-server` does for each connection.
+///
+/// Moving an instance to another thread doesn't compile:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<wasminspect_vm::WasmInstance>();
+/// ```
 #[derive(Default)]
 pub struct WasmInstance {
     pub store: Store,
+    on_instantiate: Option<InstantiateHook>,
+    interceptors: Interceptors,
 }
 
 impl WasmInstance {
@@ -32,11 +85,64 @@ impl WasmInstance {
         name: Option<String>,
         reader: &mut [u8],
     ) -> Result<ModuleIndex> {
-        self.store.load_module(name, reader)
+        self.load_module_from_module_with_config(name, reader, &Config::default())
     }
 
-    pub fn load_host_module(&mut self, name: String, module: HashMap<String, HostValue>) {
-        self.store.load_host_module(name, module)
+    /// Like `load_module_from_module`, but refusing memories and tables
+    /// whose initial size is over the limits of `config`
+    pub fn load_module_from_module_with_config(
+        &mut self,
+        name: Option<String>,
+        reader: &mut [u8],
+        config: &Config,
+    ) -> Result<ModuleIndex> {
+        let module_index = self
+            .store
+            .load_module_with_config(name.clone(), reader, config)?;
+        self.notify_instantiated(module_index, name);
+        Ok(module_index)
+    }
+
+    pub fn load_host_module(
+        &mut self,
+        name: String,
+        module: HashMap<String, HostValue>,
+    ) -> ModuleIndex {
+        let module_index = self.store.load_host_module(name.clone(), module);
+        self.notify_instantiated(module_index, Some(name));
+        module_index
+    }
+
+    /// Call `hook` with a summary of each module loaded from now on, wasm
+    /// and host modules alike. Modules which fail to link aren't reported.
+    pub fn on_instantiate(&mut self, hook: impl FnMut(&ModuleSummary) + 'static) {
+        self.on_instantiate = Some(Box::new(hook));
+    }
+
+    fn notify_instantiated(&mut self, module_index: ModuleIndex, name: Option<String>) {
+        if self.on_instantiate.is_none() {
+            return;
+        }
+        let imports = match self.store.module(module_index).defined() {
+            Some(module) => module.imports().to_vec(),
+            None => Vec::new(),
+        };
+        let summary = ModuleSummary {
+            module_index,
+            name,
+            imports,
+            exports: self.exports(module_index),
+        };
+        if let Some(hook) = self.on_instantiate.as_mut() {
+            hook(&summary);
+        }
+    }
+
+    /// Consult `interceptor` on every call, instruction and store of
+    /// `invoke` and `run`, together with the ones added before. Breakpoints
+    /// it signals don't stop the call, but traps it returns abort it.
+    pub fn add_interceptor(&mut self, interceptor: Box<dyn Interceptor>) {
+        self.interceptors.push(interceptor);
     }
 
     pub fn register_name(&mut self, name: String, module_index: ModuleIndex) {
@@ -59,6 +165,77 @@ impl WasmInstance {
             .map(|g| g.borrow().value())
     }
 
+    /// Exports of the module at `module_index` in the order they are declared.
+    /// Host modules have no export section, so theirs are sorted by name.
+    pub fn exports(&self, module_index: ModuleIndex) -> Vec<ExportInfo> {
+        let module = match self.store.module(module_index) {
+            ModuleInstance::Defined(module) => module,
+            ModuleInstance::Host(host) => {
+                let mut exports: Vec<_> = host
+                    .exports()
+                    .map(|(name, export)| {
+                        let kind = match export {
+                            HostExport::Func(addr) => {
+                                ExportKind::Func(self.store.func_global(*addr).ty().clone())
+                            }
+                            HostExport::Global(_) => ExportKind::Global,
+                            HostExport::Mem(_) => ExportKind::Memory,
+                            HostExport::Table(_) => ExportKind::Table,
+                        };
+                        ExportInfo {
+                            name: name.clone(),
+                            kind,
+                        }
+                    })
+                    .collect();
+                exports.sort_by(|a, b| a.name.cmp(&b.name));
+                return exports;
+            }
+        };
+        module
+            .exports
+            .iter()
+            .filter_map(|export| {
+                let kind = match export.value() {
+                    ExternalValue::Func(addr) => {
+                        let (func, _) = self.store.func(*addr)?;
+                        ExportKind::Func(func.ty().clone())
+                    }
+                    ExternalValue::Global(_) => ExportKind::Global,
+                    ExternalValue::Memory(_) => ExportKind::Memory,
+                    ExternalValue::Table(_) => ExportKind::Table,
+                };
+                Some(ExportInfo {
+                    name: export.name().clone(),
+                    kind,
+                })
+            })
+            .collect()
+    }
+
+    /// The function exported as `name` by the module at `module_index`
+    pub fn get_func(&self, module_index: ModuleIndex, name: &str) -> Option<FuncAddr> {
+        let module = self.store.module(module_index).defined()?;
+        module.exported_func(name).ok().flatten()
+    }
+
+    /// Call the function at `func_addr` until it returns. Breakpoints don't
+    /// stop the call, as there is no debugger to stop in.
+    pub fn invoke(
+        &mut self,
+        func_addr: FuncAddr,
+        arguments: Vec<Value>,
+        config: &Config,
+    ) -> Result<Vec<Value>, WasmError> {
+        invoke_func_with_interceptor(
+            func_addr,
+            arguments,
+            &mut self.store,
+            &self.interceptors,
+            config,
+        )
+    }
+
     pub fn run(
         &mut self,
         module_index: ModuleIndex,
@@ -66,7 +243,13 @@ impl WasmInstance {
         arguments: Vec<Value>,
         config: &Config,
     ) -> Result<Vec<Value>, WasmError> {
-        let module = self.store.module(module_index).defined().unwrap();
+        let module = match self.store.module(module_index).defined() {
+            Some(module) => module,
+            None => {
+                let func_name = func_name.unwrap_or_else(|| "_start".to_string());
+                return Err(WasmError::EntryFunctionNotFound(func_name));
+            }
+        };
         let func_addr = if let Some(func_name) = func_name {
             if let Ok(Some(func_addr)) = module.exported_func(&func_name) {
                 func_addr
@@ -80,6 +263,6 @@ impl WasmInstance {
         } else {
             return Err(WasmError::EntryFunctionNotFound("_start".to_string()));
         };
-        invoke_func_ignoring_break(func_addr, arguments, &mut self.store, config)
+        self.invoke(func_addr, arguments, config)
     }
 }
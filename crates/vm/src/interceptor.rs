@@ -2,10 +2,17 @@ use crate::executor::{ExecResult, Signal};
 use crate::inst::Instruction;
 use crate::{Executor, Store};
 
+/// Hooks the executor calls while it runs, which can stop the run at a
+/// breakpoint with `Signal::Breakpoint` or abort it with a `Trap`
 pub trait Interceptor {
     fn invoke_func(&self, name: &str, executor: &Executor, store: &Store) -> ExecResult<Signal>;
-    fn execute_inst(&self, inst: &Instruction) -> ExecResult<Signal>;
+    fn execute_inst(&self, inst: &Instruction, executor: &Executor) -> ExecResult<Signal>;
     fn after_store(&self, addr: usize, bytes: &[u8]) -> ExecResult<Signal>;
+    /// A declared local was read before being written, reported only with
+    /// `Config::poison_locals`
+    fn uninitialized_local(&self, _local_index: u32, _executor: &Executor) -> ExecResult<Signal> {
+        Ok(Signal::Next)
+    }
 }
 
 #[derive(Default)]
@@ -19,7 +26,7 @@ impl Interceptor for NopInterceptor {
     fn invoke_func(&self, _name: &str, _executor: &Executor, _store: &Store) -> ExecResult<Signal> {
         Ok(Signal::Next)
     }
-    fn execute_inst(&self, _inst: &Instruction) -> ExecResult<Signal> {
+    fn execute_inst(&self, _inst: &Instruction, _executor: &Executor) -> ExecResult<Signal> {
         Ok(Signal::Next)
     }
 
@@ -27,3 +34,46 @@ impl Interceptor for NopInterceptor {
         Ok(Signal::Next)
     }
 }
+
+/// Interceptors consulted together, as registered with
+/// `WasmInstance::add_interceptor`. Every interceptor sees every event, and
+/// the most restrictive result wins: a trap over `End` over a breakpoint
+/// over `Next`.
+#[derive(Default)]
+pub struct Interceptors {
+    interceptors: Vec<Box<dyn Interceptor>>,
+}
+
+impl Interceptors {
+    pub fn push(&mut self, interceptor: Box<dyn Interceptor>) {
+        self.interceptors.push(interceptor);
+    }
+
+    fn consult(&self, f: impl Fn(&dyn Interceptor) -> ExecResult<Signal>) -> ExecResult<Signal> {
+        let results: Vec<_> = self.interceptors.iter().map(|i| f(i.as_ref())).collect();
+        let mut signal = Signal::Next;
+        for result in results {
+            signal = match (signal, result?) {
+                (Signal::Next, other) | (other, Signal::Next) => other,
+                (Signal::End, _) | (_, Signal::End) => Signal::End,
+                (Signal::Breakpoint, Signal::Breakpoint) => Signal::Breakpoint,
+            };
+        }
+        Ok(signal)
+    }
+}
+
+impl Interceptor for Interceptors {
+    fn invoke_func(&self, name: &str, executor: &Executor, store: &Store) -> ExecResult<Signal> {
+        self.consult(|i| i.invoke_func(name, executor, store))
+    }
+    fn execute_inst(&self, inst: &Instruction, executor: &Executor) -> ExecResult<Signal> {
+        self.consult(|i| i.execute_inst(inst, executor))
+    }
+    fn after_store(&self, addr: usize, bytes: &[u8]) -> ExecResult<Signal> {
+        self.consult(|i| i.after_store(addr, bytes))
+    }
+    fn uninitialized_local(&self, local_index: u32, executor: &Executor) -> ExecResult<Signal> {
+        self.consult(|i| i.uninitialized_local(local_index, executor))
+    }
+}
@@ -10,74 +10,142 @@ mod host;
 mod inst;
 mod instance;
 mod interceptor;
+mod limits;
 mod linker;
 mod memory;
 mod module;
 mod stack;
 mod store;
 mod table;
+mod validation;
 mod value;
 
 pub use self::address::*;
-pub use self::config::Config;
+pub use self::config::{Config, ConfigBuilder, CostTable, DEFAULT_MAX_CALL_DEPTH};
+pub use self::data::ActiveDataSegment;
 pub use self::executor::{Executor, Signal, Trap, WasmError};
 pub use self::func::{FunctionInstance, InstIndex};
 pub use self::global::GlobalInstance;
 pub use self::host::{HostContext, HostFuncBody, HostValue};
-pub use self::inst::{Instruction, InstructionKind};
-pub use self::instance::WasmInstance;
-pub use self::interceptor::{Interceptor, NopInterceptor};
-pub use self::memory::MemoryInstance as HostMemory;
+pub use self::inst::{Instruction, InstructionClass, InstructionKind};
+pub use self::instance::{ExportInfo, ExportKind, ModuleSummary, WasmInstance};
+pub use self::interceptor::{Interceptor, Interceptors, NopInterceptor};
+pub use self::limits::Limits;
+pub use self::memory::{GrowError, MemoryInstance as HostMemory};
 pub use self::module::{DefinedModuleInstance, ModuleIndex};
-pub use self::stack::{CallFrame, ProgramCounter};
-pub use self::store::Store;
+pub use self::stack::{CallFrame, Label, ProgramCounter, StackValue};
+pub use self::store::{Store, StoreSnapshot};
 pub use self::table::TableInstance as HostTable;
+pub use self::validation::{validate, ValidationError};
 pub use self::value::*;
 pub use self::value::Value as WasmValue;
 
 pub const WASM_PAGE_SIZE: usize = 0x10000;
 
+/// Where `invoke_func` or `resume_func` returned
+pub enum Invocation {
+    Finished(Vec<WasmValue>),
+    /// The interceptor returned `Signal::Breakpoint`. Pass the executor to
+    /// `resume_func` to continue from there.
+    Breakpoint(Executor),
+}
+
+/// Call the function at `func_addr` until it returns, continuing past
+/// breakpoints
 pub fn invoke_func_ignoring_break(
     func_addr: FuncAddr,
     arguments: Vec<WasmValue>,
     store: &mut Store,
     config: &Config,
 ) -> Result<Vec<WasmValue>, WasmError> {
-    match store
+    let interceptor = NopInterceptor::new();
+    invoke_func_with_interceptor(func_addr, arguments, store, &interceptor, config)
+}
+
+/// Call the function at `func_addr` until it returns, consulting
+/// `interceptor` but continuing past the breakpoints it signals
+pub fn invoke_func_with_interceptor<I: Interceptor>(
+    func_addr: FuncAddr,
+    arguments: Vec<WasmValue>,
+    store: &mut Store,
+    interceptor: &I,
+    config: &Config,
+) -> Result<Vec<WasmValue>, WasmError> {
+    let mut invocation = invoke_func(func_addr, arguments, store, interceptor, config)?;
+    loop {
+        match invocation {
+            Invocation::Finished(values) => return Ok(values),
+            Invocation::Breakpoint(executor) => {
+                invocation = resume_func(executor, store, interceptor, config)?;
+            }
+        }
+    }
+}
+
+/// Call the function at `func_addr`, stopping when `interceptor` signals a
+/// breakpoint
+pub fn invoke_func<I: Interceptor>(
+    func_addr: FuncAddr,
+    arguments: Vec<WasmValue>,
+    store: &mut Store,
+    interceptor: &I,
+    config: &Config,
+) -> Result<Invocation, WasmError> {
+    let (func, exec_addr) = store
         .func(func_addr)
-        .ok_or(WasmError::ExecutionError(Trap::UndefinedFunc(func_addr.1)))?
+        .ok_or(WasmError::ExecutionError(Trap::UndefinedFunc(func_addr.1)))?;
+    let params = &func.ty().params;
+    if arguments.len() != params.len()
+        || !arguments.iter().zip(params.iter()).all(|(arg, ty)| arg.isa(*ty))
     {
-        (FunctionInstance::Native(host), _) => {
+        return Err(WasmError::ArgumentTypeMismatch {
+            expected: params.to_vec(),
+            actual: arguments.iter().map(|arg| arg.value_type()).collect(),
+        });
+    }
+    match func {
+        FunctionInstance::Native(host) => {
             let mut results = Vec::new();
             match host
                 .code()
                 .call(&arguments, &mut results, store, func_addr.module_index())
             {
-                Ok(_) => Ok(results),
+                Ok(_) => Ok(Invocation::Finished(results)),
                 Err(_) => Err(WasmError::HostExecutionError),
             }
         }
-        (FunctionInstance::Defined(func), exec_addr) => {
-            let (frame, ret_types) = {
-                let ret_types = &func.ty().returns;
-                let frame = CallFrame::new_from_func(exec_addr, func, arguments, None);
-                (frame, ret_types)
-            };
+        FunctionInstance::Defined(func) => {
+            let ret_types = &func.ty().returns;
+            let frame = CallFrame::new_from_func(exec_addr, func, arguments, None);
             let pc = ProgramCounter::new(func.module_index(), exec_addr, InstIndex::zero());
-            let interceptor = NopInterceptor::new();
-            let mut executor = Executor::new(frame, ret_types.len(), pc);
-            loop {
-                let result = executor.execute_step(store, &interceptor, config);
-                match result {
-                    Ok(Signal::Next) => continue,
-                    Ok(Signal::Breakpoint) => continue,
-                    Ok(Signal::End) => match executor.pop_result(ret_types.to_vec()) {
-                        Ok(values) => return Ok(values),
-                        Err(err) => return Err(WasmError::ReturnValueError(err)),
-                    },
-                    Err(err) => return Err(WasmError::ExecutionError(err)),
-                }
+            let executor = Executor::new(frame, ret_types.len(), pc);
+            resume_func(executor, store, interceptor, config)
+        }
+    }
+}
+
+/// Continue a call stopped at a breakpoint by `invoke_func`
+pub fn resume_func<I: Interceptor>(
+    mut executor: Executor,
+    store: &Store,
+    interceptor: &I,
+    config: &Config,
+) -> Result<Invocation, WasmError> {
+    loop {
+        let result = executor.execute_step(store, interceptor, config);
+        match result {
+            Ok(Signal::Next) => continue,
+            Ok(Signal::Breakpoint) => return Ok(Invocation::Breakpoint(executor)),
+            Ok(Signal::End) => {
+                // The pc is left in the function which was called first
+                let func = store.func_global(executor.pc.exec_addr());
+                let ret_types = func.ty().returns.to_vec();
+                return match executor.pop_result(ret_types) {
+                    Ok(values) => Ok(Invocation::Finished(values)),
+                    Err(err) => Err(WasmError::ReturnValueError(err)),
+                };
             }
+            Err(err) => return Err(WasmError::ExecutionError(err)),
         }
     }
 }
@@ -0,0 +1,32 @@
+/// Current size and maximum of a memory (in pages) or a table (in elements)
+/// https://webassembly.github.io/spec/core/syntax/types.html#limits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Limits {
+    /// Whether a provided memory or table with these limits can satisfy an
+    /// import declaring `expected`
+    /// https://webassembly.github.io/spec/core/valid/types.html#match-limits
+    pub fn matches(&self, expected: &Limits) -> bool {
+        if self.min < expected.min {
+            return false;
+        }
+        match (self.max, expected.max) {
+            (_, None) => true,
+            (Some(max), Some(expected)) => max <= expected,
+            (None, Some(_)) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Limits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max {
+            Some(max) => write!(f, "{}..{}", self.min, max),
+            None => write!(f, "{}..", self.min),
+        }
+    }
+}
@@ -5,8 +5,20 @@ use std::hash::Hash;
 
 /// An address value which points an `Item` in `LinkableCollection`
 /// The pointee item must be exists in the collection.
-#[derive(PartialEq, Eq, Hash)]
-pub struct GlobalAddress<Item>(usize, std::marker::PhantomData<Item>);
+pub struct GlobalAddress<Item>(usize, std::marker::PhantomData<fn() -> Item>);
+
+impl<Item> PartialEq for GlobalAddress<Item> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<Item> Eq for GlobalAddress<Item> {}
+impl<Item> Hash for GlobalAddress<Item> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_usize(self.0);
+    }
+}
 
 impl<Item> Clone for GlobalAddress<Item> {
     fn clone(&self) -> Self {
@@ -145,6 +157,11 @@ impl<Item> LinkableCollection<Item> {
         LinkableAddress::new_unsafe(dist, index)
     }
 
+    /// Every item in the order of their `GlobalAddress`
+    pub(crate) fn global_items(&self) -> &[Item] {
+        &self.items
+    }
+
     pub(crate) fn get_global(&self, address: GlobalAddress<Item>) -> &Item {
         // Never panic because GlobalAddress is always valid
         self.items.get(address.0).unwrap()
@@ -1,16 +1,28 @@
+use crate::limits::Limits;
 use crate::value::FromLittleEndian;
 use crate::WASM_PAGE_SIZE;
 
+/// Linear memory, backed by a single contiguous buffer
+///
+/// The buffer is allocated zeroed instead of being filled with zeros. For
+/// large sizes the allocator maps fresh pages from the OS, which backs them
+/// lazily, so pages the module never writes don't take host RAM. The buffer
+/// may be longer than the memory, see `grow` for how it's sized.
+#[derive(Clone)]
 pub struct MemoryInstance {
+    /// Zeroed past `len`
     data: Vec<u8>,
+    /// Size of the memory in bytes
+    len: usize,
     pub max: Option<usize>,
     pub initial: usize,
 }
 
+/// The most pages a 32-bit memory can have
+pub const MAX_PAGES: usize = 65536;
+
 #[derive(Debug)]
 pub enum Error {
-    GrowOverMaximumSize(usize),
-    GrowOverMaximumPageSize(usize),
     AccessOutOfBounds {
         try_to_access: Option<usize>,
         memory_size: usize,
@@ -30,19 +42,47 @@ impl std::fmt::Display for Error {
                 "out of bounds memory access, try to access over size of usize but size of memory is {}",
                 memory_size
             ),
-            _ => write!(f, "{:?}", self),
         }
     }
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Why `MemoryInstance::grow` refused to grow
+#[derive(Debug, Clone, PartialEq)]
+pub enum GrowError {
+    /// The grown size would exceed the maximum the memory declares
+    OverDeclaredMaximum { requested: usize, maximum: usize },
+    /// The grown size would exceed what the engine provides, either
+    /// `MAX_PAGES` or the configured `Config::max_memory_pages`, whatever
+    /// the memory declares
+    OverEngineLimit { requested: usize, limit: usize },
+}
+
+impl std::fmt::Display for GrowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OverDeclaredMaximum { requested, maximum } => write!(
+                f,
+                "can't grow memory to {} pages over its declared maximum of {} pages",
+                requested, maximum
+            ),
+            Self::OverEngineLimit { requested, limit } => write!(
+                f,
+                "can't grow memory to {} pages over the engine limit of {} pages",
+                requested, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GrowError {}
+
 impl MemoryInstance {
     pub fn new(initial: usize, maximum: Option<usize>) -> Self {
         Self {
-            data: std::iter::repeat(0)
-                .take(initial * WASM_PAGE_SIZE)
-                .collect(),
+            data: vec![0; initial * WASM_PAGE_SIZE],
+            len: initial * WASM_PAGE_SIZE,
             initial,
             max: maximum,
         }
@@ -66,14 +106,42 @@ impl MemoryInstance {
     }
 
     pub fn store(&mut self, offset: usize, data: &[u8]) -> Result<()> {
-        self.validate_region(offset, data.len())?;
-        for (index, byte) in data.iter().enumerate() {
-            self.data[offset + index] = *byte;
-        }
+        self.bytes_mut(offset, data.len())?.copy_from_slice(data);
         Ok(())
     }
+    pub fn load(&self, offset: usize, size: usize) -> Result<&[u8]> {
+        self.bytes(offset, size)
+    }
+
+    /// Borrow `len` bytes at `addr` in place, for host functions moving
+    /// large buffers without an intermediate copy
+    pub fn bytes(&self, addr: usize, len: usize) -> Result<&[u8]> {
+        self.validate_region(addr, len)?;
+        Ok(&self.data[addr..addr + len])
+    }
+
+    /// Mutable counterpart of `bytes`
+    pub fn bytes_mut(&mut self, addr: usize, len: usize) -> Result<&mut [u8]> {
+        self.validate_region(addr, len)?;
+        Ok(&mut self.data[addr..addr + len])
+    }
+
+    /// Copy `len` bytes from `src` to `dst`, the regions may overlap
+    pub fn copy_within(&mut self, src: usize, dst: usize, len: usize) -> Result<()> {
+        self.validate_region(src, len)?;
+        self.validate_region(dst, len)?;
+        self.data[..self.len].copy_within(src..src + len, dst);
+        Ok(())
+    }
+
+    /// Set `len` bytes at `addr` to `val`
+    pub fn fill(&mut self, addr: usize, len: usize, val: u8) -> Result<()> {
+        self.bytes_mut(addr, len)?.fill(val);
+        Ok(())
+    }
+
     pub fn data_len(&self) -> usize {
-        self.data.len()
+        self.len
     }
 
     pub fn load_as<T: FromLittleEndian>(&self, offset: usize) -> Result<T> {
@@ -86,27 +154,205 @@ impl MemoryInstance {
         self.data_len() / WASM_PAGE_SIZE
     }
 
-    pub fn grow(&mut self, n: usize) -> Result<()> {
-        let len = self.page_count() + n;
-        if len > 65536 {
-            return Err(Error::GrowOverMaximumPageSize(len));
+    /// The current size in pages and the maximum, as import matching sees them
+    pub fn limits(&self) -> Limits {
+        Limits {
+            min: self.page_count(),
+            max: self.max,
         }
+    }
+
+    /// Grow by `n` pages, up to the declared maximum and `MAX_PAGES`
+    ///
+    /// When the buffer is too short, it's replaced by a zeroed one of at
+    /// least twice the size, capped at the limits, so that growing page by
+    /// page copies the memory a logarithmic number of times. Growing within
+    /// the buffer only moves the end of the memory, the bytes past it are
+    /// still zero since nothing can write there.
+    pub fn grow(&mut self, n: usize) -> std::result::Result<(), GrowError> {
+        self.grow_with_limit(n, None)
+    }
 
+    /// Like `grow`, but also not beyond `limit` pages if given. The declared
+    /// maximum is checked first, so growing over both reports that.
+    pub fn grow_with_limit(
+        &mut self,
+        n: usize,
+        limit: Option<usize>,
+    ) -> std::result::Result<(), GrowError> {
+        let engine_limit = limit.map_or(MAX_PAGES, |limit| limit.min(MAX_PAGES));
+        let len = self.page_count().saturating_add(n);
         if let Some(max) = self.max {
             if len > max {
-                return Err(Error::GrowOverMaximumSize(max));
+                return Err(GrowError::OverDeclaredMaximum {
+                    requested: len,
+                    maximum: max,
+                });
             }
         }
-        let zero_len = n * WASM_PAGE_SIZE;
-        self.data.resize(self.data.len() + zero_len, 0);
+        if len > engine_limit {
+            return Err(GrowError::OverEngineLimit {
+                requested: len,
+                limit: engine_limit,
+            });
+        }
+        let bytes = len * WASM_PAGE_SIZE;
+        if bytes > self.data.len() {
+            let cap = self.max.map_or(engine_limit, |max| max.min(engine_limit));
+            let pages = len.max(self.page_count().saturating_mul(2).min(cap));
+            let mut data = vec![0; pages * WASM_PAGE_SIZE];
+            data[..self.len].copy_from_slice(&self.data[..self.len]);
+            self.data = data;
+        }
+        self.len = bytes;
         self.initial = len;
         Ok(())
     }
     pub fn raw_data_mut(&mut self) -> &mut [u8] {
-        &mut self.data
+        &mut self.data[..self.len]
     }
 
     pub fn raw_data(&self) -> &[u8] {
-        &self.data
+        &self.data[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_borrow_memory_in_place() {
+        let mut memory = MemoryInstance::new(1, None);
+        memory.bytes_mut(8, 4).unwrap().copy_from_slice(b"wasm");
+        assert_eq!(memory.bytes(8, 4).unwrap(), b"wasm");
+        assert_eq!(memory.load(9, 2).unwrap(), b"as");
+
+        let end = WASM_PAGE_SIZE;
+        assert!(memory.bytes(end - 4, 4).is_ok());
+        assert!(matches!(
+            memory.bytes(end - 3, 4),
+            Err(Error::AccessOutOfBounds {
+                try_to_access: Some(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            memory.bytes_mut(usize::MAX, 2),
+            Err(Error::AccessOutOfBounds {
+                try_to_access: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn copy_within_handles_overlapping_regions() {
+        let mut memory = MemoryInstance::new(1, None);
+        memory.store(0, b"abcdef").unwrap();
+        memory.copy_within(0, 2, 4).unwrap();
+        assert_eq!(memory.bytes(0, 6).unwrap(), b"ababcd");
+        memory.copy_within(2, 0, 4).unwrap();
+        assert_eq!(memory.bytes(0, 6).unwrap(), b"abcdcd");
+
+        // Nothing is written unless both regions are in bounds
+        assert!(memory.copy_within(0, WASM_PAGE_SIZE - 1, 2).is_err());
+        assert!(memory.copy_within(WASM_PAGE_SIZE, 0, 1).is_err());
+        assert_eq!(memory.bytes(0, 6).unwrap(), b"abcdcd");
+        assert!(memory.copy_within(WASM_PAGE_SIZE, 0, 0).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    fn resident_kib() -> usize {
+        let status = std::fs::read_to_string("/proc/self/status").unwrap();
+        let line = status.lines().find(|l| l.starts_with("VmRSS:")).unwrap();
+        line.split_whitespace().nth(1).unwrap().parse().unwrap()
+    }
+
+    // Resident memory is shared with whatever else the process is doing,
+    // run alone with `--ignored`
+    #[test]
+    #[ignore]
+    #[cfg(target_os = "linux")]
+    fn grow_leaves_untouched_pages_unbacked() {
+        // 1 GiB, of which only the first and last pages are written
+        let pages = 16384;
+        let before = resident_kib();
+        let mut memory = MemoryInstance::new(1, None);
+        memory.store(0, b"kept").unwrap();
+        memory.grow(pages - 1).unwrap();
+        let last = pages * WASM_PAGE_SIZE - 1;
+        memory.store(last, &[1]).unwrap();
+
+        assert_eq!(memory.page_count(), pages);
+        assert_eq!(memory.bytes(0, 4).unwrap(), b"kept");
+        assert_eq!(memory.load_as::<u8>(last).unwrap(), 1);
+        // Leave headroom for tests running on other threads
+        let grown = resident_kib().saturating_sub(before);
+        assert!(grown < 256 * 1024, "grew resident memory by {} KiB", grown);
+    }
+
+    #[test]
+    fn grow_page_by_page_reallocates_logarithmically() {
+        let mut memory = MemoryInstance::new(1, None);
+        memory.store(0, b"kept").unwrap();
+        let mut reallocations = 0;
+        for _ in 1..1024 {
+            let buffer = memory.data.as_ptr();
+            memory.grow(1).unwrap();
+            if memory.data.as_ptr() != buffer {
+                reallocations += 1;
+            }
+        }
+        assert_eq!(memory.page_count(), 1024);
+        assert_eq!(reallocations, 10);
+        assert_eq!(memory.bytes(0, 4).unwrap(), b"kept");
+        assert_eq!(memory.raw_data().len(), 1024 * WASM_PAGE_SIZE);
+        // The memory ends at its size, not at the buffer's
+        assert!(memory.bytes(1024 * WASM_PAGE_SIZE, 1).is_err());
+    }
+
+    #[test]
+    fn grow_reports_which_limit_was_hit() {
+        let mut memory = MemoryInstance::new(1, Some(2));
+        memory.grow(1).unwrap();
+        assert_eq!(
+            memory.grow(1),
+            Err(GrowError::OverDeclaredMaximum {
+                requested: 3,
+                maximum: 2
+            })
+        );
+
+        let mut memory = MemoryInstance::new(0, None);
+        assert_eq!(
+            memory.grow(MAX_PAGES + 1),
+            Err(GrowError::OverEngineLimit {
+                requested: MAX_PAGES + 1,
+                limit: MAX_PAGES
+            })
+        );
+        assert_eq!(memory.page_count(), 0);
+
+        // The configured limit applies under the declared maximum too
+        let mut memory = MemoryInstance::new(1, Some(10));
+        assert_eq!(
+            memory.grow_with_limit(2, Some(2)),
+            Err(GrowError::OverEngineLimit {
+                requested: 3,
+                limit: 2
+            })
+        );
+        memory.grow_with_limit(1, Some(2)).unwrap();
+        assert_eq!(memory.page_count(), 2);
+    }
+
+    #[test]
+    fn fill_checks_the_whole_region() {
+        let mut memory = MemoryInstance::new(1, None);
+        memory.fill(1, 3, 0xff).unwrap();
+        assert_eq!(memory.bytes(0, 5).unwrap(), [0, 0xff, 0xff, 0xff, 0]);
+        assert!(memory.fill(WASM_PAGE_SIZE - 1, 2, 1).is_err());
+        assert_eq!(memory.load_as::<u8>(WASM_PAGE_SIZE - 1).unwrap(), 0);
     }
 }
@@ -1,4 +1,5 @@
 use crate::address::*;
+use crate::data::ActiveDataSegment;
 use crate::export::{ExportInstance, ExternalValue};
 
 use std::collections::HashMap;
@@ -19,12 +20,28 @@ impl ModuleInstance {
             _ => None,
         }
     }
+
+    pub(crate) fn defined_mut(&mut self) -> Option<&mut DefinedModuleInstance> {
+        match self {
+            ModuleInstance::Defined(defined) => Some(defined),
+            _ => None,
+        }
+    }
 }
 
 pub struct DefinedModuleInstance {
     types: Vec<wasmparser::FuncType>,
+    /// Type indices of the tags defined by the module
+    tags: Vec<u32>,
     pub exports: Vec<ExportInstance>,
+    /// `(module, field)` of each import in the order they are declared
+    imports: Vec<(String, String)>,
     start_func: Option<FuncAddr>,
+    /// Function and table addresses resolved once the module is loaded,
+    /// so that calls don't have to go through the linker
+    resolved_funcs: Vec<ExecutableFuncAddr>,
+    resolved_tables: Vec<ResolvedTableAddr>,
+    active_data_segments: Vec<ActiveDataSegment>,
 }
 
 #[derive(Debug)]
@@ -52,19 +69,57 @@ impl DefinedModuleInstance {
     pub fn new_from_module(
         module_index: ModuleIndex,
         types: Vec<wasmparser::FuncType>,
+        tags: Vec<u32>,
         exports: Vec<wasmparser::Export>,
+        imports: Vec<(String, String)>,
         start_func: Option<FuncAddr>,
     ) -> Self {
         Self {
             types,
+            tags,
             exports: exports
                 .iter()
                 .map(|e| ExportInstance::new_from_entry(*e, module_index))
                 .collect(),
+            imports,
             start_func,
+            resolved_funcs: Vec::new(),
+            resolved_tables: Vec::new(),
+            active_data_segments: Vec::new(),
         }
     }
 
+    /// `(module, field)` of each import in the order they are declared
+    pub fn imports(&self) -> &[(String, String)] {
+        &self.imports
+    }
+
+    /// Active data segments in the order they were written to memory
+    pub fn active_data_segments(&self) -> &[ActiveDataSegment] {
+        &self.active_data_segments
+    }
+
+    pub(crate) fn push_active_data_segment(&mut self, segment: ActiveDataSegment) {
+        self.active_data_segments.push(segment);
+    }
+
+    pub(crate) fn set_resolved_addrs(
+        &mut self,
+        funcs: Vec<ExecutableFuncAddr>,
+        tables: Vec<ResolvedTableAddr>,
+    ) {
+        self.resolved_funcs = funcs;
+        self.resolved_tables = tables;
+    }
+
+    pub(crate) fn resolved_func(&self, index: usize) -> Option<ExecutableFuncAddr> {
+        self.resolved_funcs.get(index).copied()
+    }
+
+    pub(crate) fn resolved_table(&self, index: usize) -> Option<ResolvedTableAddr> {
+        self.resolved_tables.get(index).copied()
+    }
+
     pub fn exported_by_name(&self, name: &str) -> Option<&ExportInstance> {
         self.exports.iter().find(|e| *e.name() == name)
     }
@@ -129,8 +184,14 @@ impl DefinedModuleInstance {
         &self.start_func
     }
 
-    pub fn get_type(&self, index: usize) -> &wasmparser::FuncType {
-        &self.types[index]
+    /// The function type at `index`, `None` if it's out of range
+    pub fn get_type(&self, index: usize) -> Option<&wasmparser::FuncType> {
+        self.types.get(index)
+    }
+
+    /// The function type describing the values carried by the tag
+    pub fn get_tag_type(&self, index: usize) -> Option<&wasmparser::FuncType> {
+        self.tags.get(index).and_then(|ty| self.get_type(*ty as usize))
     }
 }
 
@@ -178,6 +239,11 @@ impl HostModuleInstance {
     pub fn new(values: HashMap<String, HostExport>) -> Self {
         Self { values }
     }
+
+    /// Exports by name, in no particular order
+    pub(crate) fn exports(&self) -> impl Iterator<Item = (&String, &HostExport)> {
+        self.values.iter()
+    }
 }
 
 impl HostModuleInstance {
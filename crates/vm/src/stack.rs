@@ -10,8 +10,6 @@ pub enum StackValueType {
     Activation,
 }
 
-const DEFAULT_CALL_STACK_LIMIT: usize = 1024;
-
 #[derive(Debug)]
 pub enum Error {
     PopEmptyStack,
@@ -21,15 +19,11 @@ pub enum Error {
     },
     NoCallFrame,
     NotEnoughFrames,
-    Overflow,
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Overflow => write!(f, "call stack exhausted"),
-            _ => write!(f, "{:?}", self),
-        }
+        write!(f, "{:?}", self)
     }
 }
 
@@ -37,10 +31,23 @@ type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Label {
-    If { arity: usize },
-    Block { arity: usize },
-    Loop { arity: usize, label: LoopLabel },
-    Return { arity: usize },
+    /// `end` is the index of the matching `end` instruction, the continuation
+    /// is the instruction after it
+    If {
+        arity: usize,
+        end: InstIndex,
+    },
+    Block {
+        arity: usize,
+        end: InstIndex,
+    },
+    Loop {
+        arity: usize,
+        label: LoopLabel,
+    },
+    Return {
+        arity: usize,
+    },
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -58,8 +65,8 @@ impl Label {
 
     pub fn arity(&self) -> usize {
         match self {
-            Label::If { arity } => *arity,
-            Label::Block { arity } => *arity,
+            Label::If { arity, .. } => *arity,
+            Label::Block { arity, .. } => *arity,
             Label::Loop { arity, .. } => *arity,
             Label::Return { arity } => *arity,
         }
@@ -105,6 +112,10 @@ impl ProgramCounter {
     pub fn loop_jump(&mut self, loop_label: &LoopLabel) {
         self.inst_index = loop_label.inst_index;
     }
+
+    pub fn jump(&mut self, inst_index: InstIndex) {
+        self.inst_index = inst_index;
+    }
 }
 
 #[derive(Clone)]
@@ -115,6 +126,8 @@ pub struct CallFrame {
 
     // Only for debug use
     pub exec_addr: ExecutableFuncAddr,
+    /// Which locals were written, once `Config::poison_locals` tracks them
+    written_locals: Option<Vec<bool>>,
 }
 
 impl CallFrame {
@@ -134,6 +147,7 @@ impl CallFrame {
             exec_addr,
             locals,
             ret_pc: pc,
+            written_locals: None,
         }
     }
 
@@ -152,12 +166,36 @@ impl CallFrame {
         )
     }
 
-    pub fn set_local(&mut self, index: usize, value: Value) {
-        self.locals[index] = value;
+    /// `None` if the frame has no local at `index`
+    pub fn local(&self, index: usize) -> Option<Value> {
+        self.locals.get(index).copied()
     }
 
-    pub fn local(&self, index: usize) -> Value {
-        self.locals[index]
+    pub fn local_mut(&mut self, index: usize) -> Option<&mut Value> {
+        self.locals.get_mut(index)
+    }
+
+    /// Start tracking which locals are written, counting the first
+    /// `param_count` as written already. Nothing changes if tracking started.
+    pub fn track_written_locals(&mut self, param_count: usize) {
+        if self.written_locals.is_none() {
+            let written = (0..self.locals.len()).map(|i| i < param_count).collect();
+            self.written_locals = Some(written);
+        }
+    }
+
+    /// Whether local `index` was written, always `true` without tracking
+    pub fn is_local_written(&self, index: usize) -> bool {
+        match &self.written_locals {
+            Some(written) => written.get(index).copied().unwrap_or(true),
+            None => true,
+        }
+    }
+
+    pub fn mark_local_written(&mut self, index: usize) {
+        if let Some(written) = self.written_locals.as_mut().and_then(|w| w.get_mut(index)) {
+            *written = true;
+        }
     }
 
     pub fn module_index(&self) -> ModuleIndex {
@@ -239,7 +277,7 @@ impl std::fmt::Display for StackValue {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Stack {
     stack: Vec<StackValue>,
     frame_index: Vec<usize>,
@@ -257,6 +295,23 @@ impl Stack {
             .collect()
     }
 
+    /// All entries from the bottom of the stack, including labels and frames
+    pub fn peek_entries(&self) -> &[StackValue] {
+        &self.stack
+    }
+
+    /// Up to `count` values from the top of the stack, top first
+    pub fn peek_top_values(&self, count: usize) -> impl Iterator<Item = &Value> {
+        self.stack
+            .iter()
+            .rev()
+            .filter_map(|v| match v {
+                StackValue::Value(v) => Some(v),
+                _ => None,
+            })
+            .take(count)
+    }
+
     pub fn peek_values(&self) -> Vec<&Value> {
         self.stack
             .iter()
@@ -343,13 +398,14 @@ impl Stack {
         }
     }
 
-    pub fn set_frame(&mut self, frame: CallFrame) -> Result<()> {
-        if self.frame_index.len() > DEFAULT_CALL_STACK_LIMIT {
-            return Err(Error::Overflow);
-        }
+    pub fn set_frame(&mut self, frame: CallFrame) {
         self.frame_index.push(self.stack.len());
         self.stack.push(StackValue::Activation(frame));
-        Ok(())
+    }
+
+    /// Number of call frames on the stack
+    pub fn frame_depth(&self) -> usize {
+        self.frame_index.len()
     }
 
     pub fn current_frame(&self) -> Result<&CallFrame> {
@@ -370,14 +426,11 @@ impl Stack {
         self.frame_index.is_empty()
     }
 
-    pub fn set_local(&mut self, index: usize, value: Value) -> Result<()> {
-        let size = self.current_frame_index()?;
-        if let Some(stack) = self.stack.get_mut(size) {
-            let frame = stack.as_activation_mut()?;
-            frame.set_local(index, value);
-            Ok(())
-        } else {
-            Err(Error::NoCallFrame)
+    pub fn current_frame_mut(&mut self) -> Result<&mut CallFrame> {
+        let index = self.current_frame_index()?;
+        match self.stack.get_mut(index) {
+            Some(frame) => frame.as_activation_mut(),
+            None => Err(Error::NoCallFrame),
         }
     }
 }
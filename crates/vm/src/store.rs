@@ -1,10 +1,12 @@
 use crate::address::*;
-use crate::data::DataInstance;
+use crate::config::Config;
+use crate::data::{ActiveDataSegment, DataInstance};
 use crate::elem::ElementInstance;
 use crate::executor::eval_const_expr;
 use crate::func::{DefinedFunctionInstance, FunctionInstance, NativeFunctionInstance};
 use crate::global::GlobalInstance;
 use crate::host::HostValue;
+use crate::limits::Limits;
 use crate::linker::LinkableCollection;
 use crate::memory::{self, MemoryInstance};
 use crate::module::{
@@ -21,6 +23,17 @@ use wasmparser::{
     Import, MemoryType, NameSectionReader, TableType, Type, TypeDef,
 };
 
+/// Copy of the mutable state of a `Store`, taken by `Store::snapshot`
+pub struct StoreSnapshot {
+    mems: Vec<MemoryInstance>,
+    tables: Vec<TableInstance>,
+    globals: Vec<GlobalInstance>,
+    elems: Vec<ElementInstance>,
+    data: Vec<DataInstance>,
+}
+
+/// Owns every instance created by loading modules. Like `WasmInstance`, it
+/// can't leave the thread which created it.
 #[derive(Default)]
 pub struct Store {
     funcs: LinkableCollection<FunctionInstance>,
@@ -35,11 +48,48 @@ pub struct Store {
     embedded_contexts: HashMap<std::any::TypeId, Box<dyn std::any::Any>>,
 }
 
+fn snapshot_items<T: Clone>(collection: &LinkableCollection<Rc<RefCell<T>>>) -> Vec<T> {
+    collection
+        .global_items()
+        .iter()
+        .map(|item| item.borrow().clone())
+        .collect()
+}
+
+fn restore_items<T: Clone>(collection: &LinkableCollection<Rc<RefCell<T>>>, saved: &[T]) {
+    for (item, saved) in collection.global_items().iter().zip(saved) {
+        item.borrow_mut().clone_from(saved);
+    }
+}
+
 impl Store {
     pub fn new() -> Self {
         Default::default()
     }
 
+    /// Deep copy the memories, tables, globals and element and data segments.
+    /// The operand and call stacks belong to the `Executor`, which is `Clone`.
+    pub fn snapshot(&self) -> StoreSnapshot {
+        StoreSnapshot {
+            mems: snapshot_items(&self.mems),
+            tables: snapshot_items(&self.tables),
+            globals: snapshot_items(&self.globals),
+            elems: snapshot_items(&self.elems),
+            data: snapshot_items(&self.data),
+        }
+    }
+
+    /// Put back the state saved by `snapshot`. Instances are restored in
+    /// place, so the modules importing them see the restored state too.
+    /// Instances created after the snapshot are left as they are.
+    pub fn restore(&self, snapshot: &StoreSnapshot) {
+        restore_items(&self.mems, &snapshot.mems);
+        restore_items(&self.tables, &snapshot.tables);
+        restore_items(&self.globals, &snapshot.globals);
+        restore_items(&self.elems, &snapshot.elems);
+        restore_items(&self.data, &snapshot.data);
+    }
+
     pub fn func_global(&self, addr: ExecutableFuncAddr) -> &FunctionInstance {
         self.funcs.get_global(addr)
     }
@@ -48,8 +98,9 @@ impl Store {
         self.funcs.get(addr)
     }
 
-    pub fn global(&self, addr: GlobalAddr) -> Rc<RefCell<GlobalInstance>> {
-        self.globals.get(addr).unwrap().0.clone()
+    /// `None` if `addr` doesn't point to a global of its module
+    pub fn global(&self, addr: GlobalAddr) -> Option<Rc<RefCell<GlobalInstance>>> {
+        self.globals.get(addr).map(|(global, _)| global.clone())
     }
 
     pub fn scan_global_by_name(
@@ -59,21 +110,33 @@ impl Store {
     ) -> Option<Rc<RefCell<GlobalInstance>>> {
         let module = self.module(module_index).defined().unwrap();
         let global_addr = module.exported_global(field).ok().unwrap();
-        global_addr.map(|addr| self.global(addr))
+        global_addr.and_then(|addr| self.global(addr))
     }
 
     pub fn table(&self, addr: TableAddr) -> Rc<RefCell<TableInstance>> {
         self.tables.get(addr).unwrap().0.clone()
     }
 
+    pub(crate) fn table_global(&self, addr: ResolvedTableAddr) -> &Rc<RefCell<TableInstance>> {
+        self.tables.get_global(addr)
+    }
+
     pub fn memory(&self, addr: MemoryAddr) -> Rc<RefCell<MemoryInstance>> {
         self.mems.get(addr).unwrap().0.clone()
     }
 
+    pub fn module_funcs(&self, module_index: ModuleIndex) -> Vec<ExecutableFuncAddr> {
+        self.funcs.items(module_index).unwrap_or_default()
+    }
+
     pub fn memory_count(&self, addr: ModuleIndex) -> usize {
         self.mems.items(addr).map(|c| c.len()).unwrap_or(0)
     }
 
+    pub fn table_count(&self, addr: ModuleIndex) -> usize {
+        self.tables.items(addr).map(|c| c.len()).unwrap_or(0)
+    }
+
     pub fn elem(&self, addr: ElemAddr) -> Rc<RefCell<ElementInstance>> {
         self.elems.get(addr).unwrap().0.clone()
     }
@@ -86,12 +149,9 @@ impl Store {
         &self.modules[module_index.0 as usize]
     }
 
-    pub(crate) fn module_by_name(&self, name: String) -> &ModuleInstance {
-        if let Some(index) = self.module_index_by_name.get(&name) {
-            self.module(*index)
-        } else {
-            panic!("Module {} was not loaded", name);
-        }
+    pub(crate) fn module_by_name(&self, name: &str) -> Option<&ModuleInstance> {
+        let index = self.module_index_by_name.get(name)?;
+        Some(self.module(*index))
     }
 
     pub fn register_name(&mut self, name: String, module_index: ModuleIndex) {
@@ -100,7 +160,11 @@ impl Store {
 }
 
 impl Store {
-    pub fn load_host_module(&mut self, name: String, module: HashMap<String, HostValue>) {
+    pub fn load_host_module(
+        &mut self,
+        name: String,
+        module: HashMap<String, HostValue>,
+    ) -> ModuleIndex {
         let module_index = ModuleIndex(self.modules.len() as u32);
         let mut values = HashMap::new();
         for (field, entry) in module {
@@ -128,6 +192,7 @@ impl Store {
         let instance = HostModuleInstance::new(values);
         self.modules.push(ModuleInstance::Host(instance));
         self.module_index_by_name.insert(name, module_index);
+        module_index
     }
 
     pub fn add_embed_context<T: std::any::Any>(&mut self, ctx: Box<T>) {
@@ -169,15 +234,36 @@ pub enum StoreError {
         field: Option<String>,
     },
     IncompatibleImportFuncType(String, FuncType, FuncType),
-    IncompatibleImportGlobalType(Type, Type),
-    IncompatibleImportGlobalMutability,
-    IncompatibleImportTableType,
+    IncompatibleImportGlobalType {
+        actual: Type,
+        expected: Type,
+    },
+    IncompatibleImportGlobalMutability {
+        expected_mutable: bool,
+    },
+    IncompatibleImportTableElementType {
+        actual: Type,
+        expected: Type,
+    },
+    IncompatibleImportTableType {
+        actual: Limits,
+        expected: Limits,
+    },
     IncompatibleImportMemoryType {
-        message: String,
+        actual: Limits,
+        expected: Limits,
     },
     InvalidElementSegmentsType {
         ty: Type,
     },
+    MemoryOverLimit {
+        initial: usize,
+        limit: usize,
+    },
+    TableOverLimit {
+        initial: usize,
+        limit: usize,
+    },
 }
 impl std::error::Error for StoreError {}
 
@@ -215,26 +301,96 @@ impl std::fmt::Display for StoreError {
             ),
             Self::IncompatibleImportFuncType(name, expected, actual) => write!(
                 f,
-                "incompatible import type, \"{}\" expected {:?} but got {:?}",
-                name, expected, actual
+                "incompatible import type, \"{}\" expected {} but got {}",
+                name,
+                format_func_type(expected),
+                format_func_type(actual)
             ),
-            Self::IncompatibleImportGlobalType(expected, actual) => write!(
+            Self::IncompatibleImportGlobalType { actual, expected } => write!(
                 f,
-                "incompatible import type, expected {:?} but got {:?}",
-                expected, actual
+                "incompatible import type, expected {} global but got {}",
+                format!("{:?}", expected).to_lowercase(),
+                format!("{:?}", actual).to_lowercase()
             ),
-            Self::IncompatibleImportGlobalMutability => {
-                write!(f, "incompatible import type in global mutability")
-            }
-            Self::IncompatibleImportTableType => write!(f, "incompatible import type in table"),
-            Self::IncompatibleImportMemoryType { message } => {
-                write!(f, "incompatible import type in memory: {}", message)
+            Self::IncompatibleImportGlobalMutability { expected_mutable } => {
+                let (expected, actual) = if *expected_mutable {
+                    ("mutable", "immutable")
+                } else {
+                    ("immutable", "mutable")
+                };
+                write!(
+                    f,
+                    "incompatible import type, expected {} global but got {}",
+                    expected, actual
+                )
             }
+            Self::IncompatibleImportTableElementType { actual, expected } => write!(
+                f,
+                "incompatible import type, expected {} table, got {}",
+                format!("{:?}", expected).to_lowercase(),
+                format!("{:?}", actual).to_lowercase()
+            ),
+            Self::IncompatibleImportTableType { actual, expected } => write!(
+                f,
+                "incompatible import type in table, expected limits {} but got {}",
+                expected, actual
+            ),
+            Self::IncompatibleImportMemoryType { actual, expected } => write!(
+                f,
+                "incompatible import type in memory, expected limits {} but got {}",
+                expected, actual
+            ),
             Self::InvalidElementSegmentsType { ty } => {
                 write!(f, "invalid element segments type {:?}", ty)
             }
+            Self::MemoryOverLimit { initial, limit } => write!(
+                f,
+                "memory of {} pages exceeds the configured limit of {} pages",
+                initial, limit
+            ),
+            Self::TableOverLimit { initial, limit } => write!(
+                f,
+                "table of {} elements exceeds the configured limit of {} elements",
+                initial, limit
+            ),
+        }
+    }
+}
+
+/// Checked before anything is instantiated, so a refused module leaves
+/// nothing behind in the store
+fn check_resource_limits(tables: &[TableType], mems: &[MemoryType], config: &Config) -> Result<()> {
+    if let Some(limit) = config.max_table_elems() {
+        if let Some(table) = tables.iter().find(|t| t.initial as usize > limit) {
+            return Err(StoreError::TableOverLimit {
+                initial: table.initial as usize,
+                limit,
+            }
+            .into());
+        }
+    }
+    if let Some(limit) = config.max_memory_pages() {
+        if let Some(mem) = mems.iter().find(|m| m.initial as usize > limit) {
+            return Err(StoreError::MemoryOverLimit {
+                initial: mem.initial as usize,
+                limit,
+            }
+            .into());
         }
     }
+    Ok(())
+}
+
+/// `(i32 i32) -> (i64)`, as written in error messages
+fn format_func_type(ty: &FuncType) -> String {
+    let types = |types: &[Type]| {
+        types
+            .iter()
+            .map(|ty| format!("{:?}", ty).to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    format!("({}) -> ({})", types(&ty.params), types(&ty.returns))
 }
 
 fn read_name_section(mut reader: wasmparser::NameSectionReader) -> Result<HashMap<u32, String>> {
@@ -273,6 +429,7 @@ impl Store {
         name: Option<String>,
         reader: &[u8],
         module_index: ModuleIndex,
+        config: &Config,
     ) -> Result<ModuleIndex> {
         let mut types = Vec::new();
         let mut elem_segs = Vec::new();
@@ -284,6 +441,7 @@ impl Store {
         let mut tables = Vec::new();
         let mut globals = Vec::new();
         let mut mems = Vec::new();
+        let mut tags = Vec::new();
         let mut func_names = HashMap::new();
 
         let mut start_func = None;
@@ -360,6 +518,12 @@ impl Store {
                         globals.push(entry?);
                     }
                 }
+                Payload::TagSection(section) => {
+                    tags.reserve_exact(section.get_count() as usize);
+                    for entry in section {
+                        tags.push(entry?.type_index);
+                    }
+                }
                 Payload::StartSection { func, .. } => {
                     start_func = Some(FuncAddr::new_unsafe(module_index, func as usize));
                 }
@@ -385,10 +549,21 @@ impl Store {
             // buf.drain(..consumed);
         }
 
+        check_resource_limits(&tables, &mems, config)?;
+
+        let import_names = imports
+            .iter()
+            .map(|import| {
+                let field = import.field.unwrap_or_default();
+                (import.module.to_string(), field.to_string())
+            })
+            .collect();
         let instance = DefinedModuleInstance::new_from_module(
             module_index,
             types.clone(),
+            tags,
             exports,
+            import_names,
             start_func,
         );
         self.modules.push(ModuleInstance::Defined(instance));
@@ -398,7 +573,7 @@ impl Store {
         }
 
         self.load_imports(imports, module_index, &types)?;
-        self.load_globals(globals, module_index)?;
+        self.load_globals(globals, module_index, config)?;
         if let Some(base_offset) = code_section_base_offset {
             self.load_functions(
                 module_index,
@@ -409,21 +584,45 @@ impl Store {
                 base_offset,
             )?;
         }
-        self.load_tables_and_elems(tables, module_index, elem_segs)?;
-        self.load_mems(mems, module_index, data_segs)?;
+        self.load_tables_and_elems(tables, module_index, elem_segs, config)?;
+        self.load_mems(mems, module_index, data_segs, config)?;
 
         Ok(module_index)
     }
     pub fn load_module(&mut self, name: Option<String>, reader: &[u8]) -> Result<ModuleIndex> {
+        self.load_module_with_config(name, reader, &Config::default())
+    }
+
+    /// Instantiate a module, refusing memories and tables whose initial size
+    /// is over the limits of `config`
+    pub fn load_module_with_config(
+        &mut self,
+        name: Option<String>,
+        reader: &[u8],
+        config: &Config,
+    ) -> Result<ModuleIndex> {
         let module_index = ModuleIndex(self.modules.len() as u32);
 
-        let result: Result<ModuleIndex> = self.load_module_internal(name, reader, module_index);
+        let result: Result<ModuleIndex> =
+            self.load_module_internal(name, reader, module_index, config);
+        // Resolve even if instantiation failed part way, since functions
+        // already written into imported tables stay callable
+        self.resolve_module_addrs(module_index);
         match result {
             Ok(ok) => Ok(ok),
             Err(err) => Err(err),
         }
     }
 
+    fn resolve_module_addrs(&mut self, module_index: ModuleIndex) {
+        let funcs = self.funcs.items(module_index).unwrap_or_default();
+        let tables = self.tables.items(module_index).unwrap_or_default();
+        let module = self.modules.get_mut(module_index.0 as usize);
+        if let Some(module) = module.and_then(ModuleInstance::defined_mut) {
+            module.set_resolved_addrs(funcs, tables);
+        }
+    }
+
     fn load_imports(
         &mut self,
         imports: Vec<Import>,
@@ -469,11 +668,11 @@ impl Store {
             .field
             .with_context(|| "expect non-nil field name in function import")?
             .to_string();
-        let module = self.module_by_name(import.module.to_string());
         let err = || StoreError::UndefinedFunction {
             module: import.module.to_string(),
             field: import.field.map(String::from),
         };
+        let module = self.module_by_name(import.module).ok_or_else(err)?;
         let exec_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let func_addr = defined
@@ -515,7 +714,7 @@ impl Store {
             .field
             .with_context(|| "expect non-nil field name in memory import")?
             .to_string();
-        let module = self.module_by_name(import.module.to_string());
+        let module = self.module_by_name(import.module).ok_or_else(err)?;
         let resolved_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let addr = defined
@@ -533,34 +732,13 @@ impl Store {
         // Validation
         {
             let memory = self.mems.get_global(resolved_addr);
-            let limit_initial = memory_ty.initial;
-            let limit_max = memory_ty.maximum;
-            if memory.borrow().initial < limit_initial as usize {
-                return Err(StoreError::IncompatibleImportMemoryType {
-                    message: String::from("actual initial size is less than expected initial size"),
-                }
-                .into());
-            }
-            match (memory.borrow().max, limit_max) {
-                (Some(found), Some(expected)) => {
-                    if found > expected as usize {
-                        return Err(StoreError::IncompatibleImportMemoryType {
-                            message: String::from(
-                                "actual limit size is bigger than expected limit size",
-                            ),
-                        }
-                        .into());
-                    }
-                }
-                (None, Some(_)) => {
-                    return Err(StoreError::IncompatibleImportMemoryType {
-                        message: String::from(
-                            "actual memory doesn't have limit but expected limit size",
-                        ),
-                    }
-                    .into())
-                }
-                _ => (),
+            let actual = memory.borrow().limits();
+            let expected = Limits {
+                min: memory_ty.initial as usize,
+                max: memory_ty.maximum.map(|max| max as usize),
+            };
+            if !actual.matches(&expected) {
+                return Err(StoreError::IncompatibleImportMemoryType { actual, expected }.into());
             }
         }
         self.mems.link(resolved_addr, module_index);
@@ -577,11 +755,11 @@ impl Store {
             .field
             .with_context(|| "expect non-nil field name in table import")?
             .to_string();
-        let module = self.module_by_name(import.module.to_string());
         let err = || StoreError::UndefinedTable {
             module: import.module.to_string(),
             field: import.field.map(String::from),
         };
+        let module = self.module_by_name(import.module).ok_or_else(err)?;
         let resolved_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let addr = defined
@@ -598,20 +776,21 @@ impl Store {
         let found = self.tables.get_global(resolved_addr);
         // Validation
         {
-            if Into::<Type>::into(found.borrow().ty) != table_ty.element_type {
-                return Err(StoreError::IncompatibleImportTableType.into());
-            }
-            if found.borrow().initial < table_ty.initial as usize {
-                return Err(StoreError::IncompatibleImportTableType.into());
-            }
-            match (found.clone().borrow().max, table_ty.maximum) {
-                (Some(found), Some(expected)) => {
-                    if found > expected as usize {
-                        return Err(StoreError::IncompatibleImportTableType.into());
-                    }
+            let element_type = found.borrow().element_type();
+            if Into::<Type>::into(element_type) != table_ty.element_type {
+                return Err(StoreError::IncompatibleImportTableElementType {
+                    actual: element_type.into(),
+                    expected: table_ty.element_type,
                 }
-                (None, Some(_)) => return Err(StoreError::IncompatibleImportTableType.into()),
-                _ => (),
+                .into());
+            }
+            let actual = found.borrow().limits();
+            let expected = Limits {
+                min: table_ty.initial as usize,
+                max: table_ty.maximum.map(|max| max as usize),
+            };
+            if !actual.matches(&expected) {
+                return Err(StoreError::IncompatibleImportTableType { actual, expected }.into());
             }
         }
 
@@ -629,11 +808,11 @@ impl Store {
             .field
             .with_context(|| "expect non-nil field name in global import")?
             .to_string();
-        let module = self.module_by_name(import.module.to_string());
         let err = || StoreError::UndefinedGlobal {
             module: import.module.to_string(),
             field: import.field.map(String::from),
         };
+        let module = self.module_by_name(import.module).ok_or_else(err)?;
         let resolved_addr = match module {
             ModuleInstance::Defined(defined) => {
                 let addr = defined
@@ -653,13 +832,16 @@ impl Store {
             let actual_global_ty = actual_global.borrow().ty().content_type;
             let expected_global_ty = global_ty.content_type;
             if actual_global.borrow().is_mutable() != global_ty.mutable {
-                return Err(StoreError::IncompatibleImportGlobalMutability.into());
+                return Err(StoreError::IncompatibleImportGlobalMutability {
+                    expected_mutable: global_ty.mutable,
+                }
+                .into());
             }
             if actual_global_ty != expected_global_ty {
-                return Err(StoreError::IncompatibleImportGlobalType(
-                    actual_global_ty,
-                    expected_global_ty,
-                )
+                return Err(StoreError::IncompatibleImportGlobalType {
+                    actual: actual_global_ty,
+                    expected: expected_global_ty,
+                }
                 .into());
             }
         };
@@ -687,10 +869,10 @@ impl Store {
                     type_index: func_sig,
                 })?
                 .clone();
-            let name = names.get(&index).cloned().unwrap_or(format!(
-                "<module #{} defined func #{}>",
-                module_index.0, index
-            ));
+            let name = names
+                .get(&index)
+                .cloned()
+                .unwrap_or_else(|| format!("func[{}]", index));
             let defined =
                 DefinedFunctionInstance::new(name, func_type, module_index, body, base_offset)?;
             let instance = FunctionInstance::Defined(defined);
@@ -701,9 +883,14 @@ impl Store {
         Ok(func_addrs)
     }
 
-    fn load_globals(&mut self, globals: Vec<Global>, module_index: ModuleIndex) -> Result<()> {
+    fn load_globals(
+        &mut self,
+        globals: Vec<Global>,
+        module_index: ModuleIndex,
+        config: &Config,
+    ) -> Result<()> {
         for entry in globals {
-            let value = eval_const_expr(&entry.init_expr, self, module_index)?;
+            let value = eval_const_expr(&entry.init_expr, self, module_index, config)?;
             let instance = GlobalInstance::new(value, entry.ty);
             self.globals
                 .push(module_index, Rc::new(RefCell::new(instance)));
@@ -716,6 +903,7 @@ impl Store {
         tables: Vec<TableType>,
         module_index: ModuleIndex,
         element_segments: Vec<Element>,
+        config: &Config,
     ) -> Result<Vec<TableAddr>> {
         let mut table_addrs = Vec::new();
         let mut elem_addrs = Vec::new();
@@ -755,11 +943,11 @@ impl Store {
                         index as usize,
                     ))),
                     ElementItem::Expr(init_expr) => {
-                        match eval_const_expr(&init_expr, self, module_index)? {
-                            Value::Num(n) => unreachable!(
+                        match eval_const_expr(&init_expr, self, module_index, config)? {
+                            Value::Num(n) => Err(anyhow::anyhow!(
                                 "unexpected num value returned by init_expr in segment: {:?}",
                                 n
-                            ),
+                            )),
                             Value::Ref(r) => Ok(r),
                         }
                     }
@@ -776,9 +964,14 @@ impl Store {
                         Some(addr) => addr,
                         None => break,
                     };
-                    let offset = match eval_const_expr(&init_expr, self, module_index)? {
+                    let offset = match eval_const_expr(&init_expr, self, module_index, config)? {
                         Value::Num(NumVal::I32(v)) => v,
-                        other => panic!("unexpected result value of const init expr {:?}", other),
+                        other => {
+                            return Err(anyhow::anyhow!(
+                                "unexpected result value of const init expr {:?}",
+                                other
+                            ))
+                        }
                     };
                     let table = self.tables.get_global(*table_addr);
                     table
@@ -803,6 +996,7 @@ impl Store {
         mems: Vec<MemoryType>,
         module_index: ModuleIndex,
         data_segments: Vec<Data>,
+        config: &Config,
     ) -> Result<Vec<MemoryAddr>> {
         let mut mem_addrs = Vec::new();
         if mems.is_empty() && self.mems.is_empty(module_index) {
@@ -818,7 +1012,7 @@ impl Store {
         }
 
         let mems = self.mems.items(module_index).unwrap();
-        for seg in data_segments {
+        for (segment_index, seg) in data_segments.into_iter().enumerate() {
             let instance = match seg.kind {
                 DataKind::Active {
                     memory_index,
@@ -828,9 +1022,14 @@ impl Store {
                         Some(addr) => addr,
                         None => continue,
                     };
-                    let offset = match eval_const_expr(&init_expr, self, module_index)? {
+                    let offset = match eval_const_expr(&init_expr, self, module_index, config)? {
                         Value::Num(NumVal::I32(v)) => v,
-                        other => panic!("unexpected result value of const init expr {:?}", other),
+                        other => {
+                            return Err(anyhow::anyhow!(
+                                "unexpected result value of const init expr {:?}",
+                                other
+                            ))
+                        }
                     };
                     let mem = self.mems.get_global(*mem_addr);
                     mem.borrow()
@@ -840,6 +1039,15 @@ impl Store {
                     mem.borrow_mut()
                         .store(offset as usize, seg.data)
                         .map_err(StoreError::InvalidDataSegments)?;
+                    let segment = ActiveDataSegment {
+                        segment_index: segment_index as u32,
+                        memory_index,
+                        offset: offset as usize,
+                        len: seg.data.len(),
+                    };
+                    if let Some(module) = self.modules[module_index.0 as usize].defined_mut() {
+                        module.push_active_data_segment(segment);
+                    }
                     DataInstance::new(vec![])
                 }
                 DataKind::Passive => DataInstance::new(seg.data.to_vec()),
@@ -850,3 +1058,39 @@ impl Store {
         Ok(mem_addrs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const EXTENDED_CONST_GLOBAL: [u8; 24] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // (global i32 (i32.const 8))
+        // (global i32 (i32.add (global.get 0) (i32.const 16)))
+        0x06, 0x0e, 0x02,
+        0x7f, 0x00, 0x41, 0x08, 0x0b,
+        0x7f, 0x00, 0x23, 0x00, 0x41, 0x10, 0x6a, 0x0b,
+    ];
+
+    #[test]
+    fn evaluates_extended_const_initializers() {
+        let mut store = Store::new();
+        let config = Config::builder().extended_const(true).build();
+        let module_index = store
+            .load_module_with_config(None, &EXTENDED_CONST_GLOBAL, &config)
+            .unwrap();
+        let global = store
+            .global(GlobalAddr::new_unsafe(module_index, 1))
+            .unwrap();
+        assert_eq!(global.borrow().value(), Value::I32(24));
+    }
+
+    #[test]
+    fn rejects_extended_const_initializers_when_disabled() {
+        let mut store = Store::new();
+        let err = store.load_module(None, &EXTENDED_CONST_GLOBAL).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("`extended-const`"), "{}", message);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::limits::Limits;
 use crate::value::{RefType, RefVal};
 
 #[derive(Debug)]
@@ -38,6 +39,7 @@ type Result<T> = std::result::Result<T, Error>;
 
 /// Runtime representation of a table. It records its type and holds a vector of `RefVal`
 /// https://webassembly.github.io/spec/core/exec/runtime.html#table-instances
+#[derive(Clone)]
 pub struct TableInstance {
     buffer: Vec<RefVal>,
     pub max: Option<usize>,
@@ -82,10 +84,51 @@ impl TableInstance {
         Ok(())
     }
 
+    /// Borrow `len` elements at `offset`
+    pub fn elements(&self, offset: usize, len: usize) -> Result<&[RefVal]> {
+        self.validate_region(offset, len)?;
+        Ok(&self.buffer[offset..offset + len])
+    }
+
+    /// Overwrite the elements at `offset` with `values`
+    pub fn set_elements(&mut self, offset: usize, values: &[RefVal]) -> Result<()> {
+        self.validate_region(offset, values.len())?;
+        self.buffer[offset..offset + values.len()].copy_from_slice(values);
+        Ok(())
+    }
+
+    /// Copy `len` elements from `src` to `dst`, the regions may overlap
+    pub fn copy_within(&mut self, src: usize, dst: usize, len: usize) -> Result<()> {
+        self.validate_region(src, len)?;
+        self.validate_region(dst, len)?;
+        self.buffer.copy_within(src..src + len, dst);
+        Ok(())
+    }
+
+    /// Set `len` elements at `offset` to `val`
+    pub fn fill(&mut self, offset: usize, len: usize, val: RefVal) -> Result<()> {
+        self.validate_region(offset, len)?;
+        self.buffer[offset..offset + len].fill(val);
+        Ok(())
+    }
+
     pub fn buffer_len(&self) -> usize {
         self.buffer.len()
     }
 
+    /// The type of references the table holds
+    pub fn element_type(&self) -> RefType {
+        self.ty
+    }
+
+    /// The current size in elements and the maximum, as import matching sees them
+    pub fn limits(&self) -> Limits {
+        Limits {
+            min: self.buffer_len(),
+            max: self.max,
+        }
+    }
+
     pub fn get_at(&self, index: usize) -> Result<RefVal> {
         self.buffer
             .get(index)
@@ -108,13 +151,18 @@ impl TableInstance {
 
     /// https://webassembly.github.io/spec/core/exec/modules.html#growing-tables
     pub fn grow(&mut self, n: usize, val: RefVal) -> Result<()> {
+        self.grow_with_limit(n, val, None)
+    }
+
+    /// Like `grow`, but also not beyond `limit` elements if given
+    pub fn grow_with_limit(&mut self, n: usize, val: RefVal, limit: Option<usize>) -> Result<()> {
         let base_len = self.buffer_len();
         let len = base_len.checked_add(n).ok_or(Error::GrowOverMaximumSize {
             base: base_len,
             growing: n,
         })?;
 
-        if let Some(max) = self.max {
+        if let Some(max) = self.max.into_iter().chain(limit).min() {
             if len > max {
                 return Err(Error::GrowOverMaximumSize {
                     base: base_len,
@@ -127,3 +175,50 @@ impl TableInstance {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_within_and_fill_check_bounds() {
+        let mut table = TableInstance::new(4, None, RefType::ExternRef);
+        let values: Vec<_> = (0..3).map(RefVal::ExternRef).collect();
+        table.set_elements(0, &values).unwrap();
+        table.copy_within(0, 1, 3).unwrap();
+        assert_eq!(
+            table.elements(0, 4).unwrap(),
+            [0, 0, 1, 2]
+                .iter()
+                .map(|v| RefVal::ExternRef(*v))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(table.copy_within(0, 2, 3).is_err());
+        assert!(table
+            .fill(3, 2, RefVal::NullRef(RefType::ExternRef))
+            .is_err());
+        table
+            .fill(1, 3, RefVal::NullRef(RefType::ExternRef))
+            .unwrap();
+        assert_eq!(table.get_at(0).unwrap(), RefVal::ExternRef(0));
+        assert_eq!(
+            table.get_at(3).unwrap(),
+            RefVal::NullRef(RefType::ExternRef)
+        );
+    }
+
+    #[test]
+    fn limits_follow_growth() {
+        let mut table = TableInstance::new(1, Some(3), RefType::FuncRef);
+        assert_eq!(table.element_type(), RefType::FuncRef);
+        table.grow(1, RefVal::NullRef(RefType::FuncRef)).unwrap();
+        assert_eq!(
+            table.limits(),
+            Limits {
+                min: 2,
+                max: Some(3)
+            }
+        );
+    }
+}
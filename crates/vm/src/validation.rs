@@ -0,0 +1,275 @@
+use crate::config::Config;
+use crate::inst::InstructionKind;
+use std::convert::TryFrom;
+use std::ops::Range;
+use wasmparser::{
+    BinaryReader, BinaryReaderError, Data, DataKind, DataSectionReader, Element, ElementKind,
+    ElementSectionReader, GlobalSectionReader, InitExpr, Validator,
+};
+
+/// A module validation failure pointing at the offending bytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Byte offset in the module binary where validation failed
+    pub offset: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at offset 0x{:x})", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<BinaryReaderError> for ValidationError {
+    fn from(err: BinaryReaderError) -> Self {
+        Self {
+            offset: err.offset(),
+            message: err.message().to_string(),
+        }
+    }
+}
+
+/// Validate a module binary with the features enabled in `config`
+pub fn validate(bytes: &[u8], config: &Config) -> Result<(), ValidationError> {
+    let result = validate_with_features(bytes, config).or_else(|err| {
+        if !err.message.contains(MULTI_OPERATOR_INIT_EXPR) {
+            return Err(err);
+        }
+        if !config.extended_const() {
+            return Err(ValidationError {
+                message: "init expressions of more than one instruction require the \
+                          `extended-const` feature, which is disabled; pass `--enable extended-const`"
+                    .to_string(),
+                ..err
+            });
+        }
+        // The VM's constant evaluator checks the full expressions instead
+        let (truncated, offsets) = truncate_init_exprs(bytes)?;
+        validate_with_features(&truncated, config).map_err(|mut err| {
+            err.offset = offsets.original(err.offset);
+            err
+        })
+    });
+    result.map_err(|mut err| {
+        if let Some((mnemonic, feature)) = disabled_feature_at(bytes, err.offset, config) {
+            err.message = format!(
+                "instruction {} requires the `{}` feature, which is disabled; pass `--enable {}`",
+                mnemonic, feature, feature
+            );
+        }
+        err
+    })
+}
+
+fn validate_with_features(bytes: &[u8], config: &Config) -> Result<(), ValidationError> {
+    let mut validator = Validator::new();
+    validator.wasm_features(config.features);
+    Ok(validator.validate_all(bytes)?)
+}
+
+/// How wasmparser rejects init expressions of more than one operator, which
+/// the extended constant expressions proposal allows
+const MULTI_OPERATOR_INIT_EXPR: &str = "only one init_expr operator is expected";
+
+/// Maps offsets in a module with bytes dropped back to the original module
+#[derive(Default)]
+struct OffsetMap {
+    /// Offsets in the new module and the original one where the bytes line
+    /// up again, in increasing order
+    anchors: Vec<(usize, usize)>,
+}
+
+impl OffsetMap {
+    fn original(&self, offset: usize) -> usize {
+        match self.anchors.iter().rev().find(|(new, _)| *new <= offset) {
+            Some((new, original)) => original + (offset - new),
+            None => offset,
+        }
+    }
+}
+
+/// A copy of the module with each init expression cut down to its first
+/// operator. That operator already has the type of the whole expression,
+/// so the copy validates like the original would with extended constant
+/// expressions supported.
+fn truncate_init_exprs(bytes: &[u8]) -> Result<(Vec<u8>, OffsetMap), BinaryReaderError> {
+    let mut reader = BinaryReader::new(bytes);
+    let mut output = reader.read_bytes(8)?.to_vec();
+    let mut offsets = OffsetMap::default();
+    while !reader.eof() {
+        let start = reader.original_position();
+        let id = reader.read_u8()?;
+        let size = reader.read_var_u32()? as usize;
+        let content_start = reader.original_position();
+        let content = reader.read_bytes(size)?;
+        let dropped = init_expr_tails(id, content, content_start)?;
+        if dropped.is_empty() {
+            offsets.anchors.push((output.len(), start));
+            output.extend_from_slice(&bytes[start..content_start + size]);
+            continue;
+        }
+        let mut kept = vec![];
+        let mut anchors = vec![(0, content_start)];
+        let mut pos = content_start;
+        for range in dropped {
+            kept.extend_from_slice(&bytes[pos..range.start]);
+            pos = range.end;
+            anchors.push((kept.len(), pos));
+        }
+        kept.extend_from_slice(&bytes[pos..content_start + size]);
+        output.push(id as u8);
+        write_var_u32(&mut output, kept.len() as u32);
+        let base = output.len();
+        offsets.anchors.extend(
+            anchors
+                .into_iter()
+                .map(|(new, original)| (base + new, original)),
+        );
+        output.extend(kept);
+    }
+    Ok((output, offsets))
+}
+
+/// Byte ranges of the operators after the first one, up to the closing
+/// `end`, of the init expressions in section `id`
+fn init_expr_tails(
+    id: u32,
+    content: &[u8],
+    offset: usize,
+) -> Result<Vec<Range<usize>>, BinaryReaderError> {
+    let exprs: Vec<InitExpr> = match id {
+        GLOBAL_SECTION => GlobalSectionReader::new(content, offset)?
+            .into_iter()
+            .map(|global| global.map(|global| global.init_expr))
+            .collect::<Result<_, _>>()?,
+        ELEMENT_SECTION => ElementSectionReader::new(content, offset)?
+            .into_iter()
+            .filter_map(|elem| match elem {
+                Ok(Element {
+                    kind: ElementKind::Active { init_expr, .. },
+                    ..
+                }) => Some(Ok(init_expr)),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<Result<_, _>>()?,
+        DATA_SECTION => DataSectionReader::new(content, offset)?
+            .into_iter()
+            .filter_map(|data| match data {
+                Ok(Data {
+                    kind: DataKind::Active { init_expr, .. },
+                    ..
+                }) => Some(Ok(init_expr)),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Ok(vec![]),
+    };
+    let mut tails = vec![];
+    for expr in exprs {
+        let offsets = expr
+            .get_operators_reader()
+            .into_iter_with_offsets()
+            .map(|op| op.map(|(_, offset)| offset))
+            .collect::<Result<Vec<_>, _>>()?;
+        // The last operator is the `end`
+        if let [_, second, .., end] = offsets.as_slice() {
+            tails.push(*second..*end);
+        }
+    }
+    Ok(tails)
+}
+
+const GLOBAL_SECTION: u32 = 6;
+const ELEMENT_SECTION: u32 = 9;
+const DATA_SECTION: u32 = 11;
+
+fn write_var_u32(output: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+/// The instruction at `offset` and its proposal, if the proposal is disabled
+fn disabled_feature_at(
+    bytes: &[u8],
+    offset: usize,
+    config: &Config,
+) -> Option<(&'static str, &'static str)> {
+    let mut reader = BinaryReader::new_with_offset(bytes.get(offset..)?, offset);
+    let kind = InstructionKind::try_from(reader.read_operator().ok()?).ok()?;
+    let feature = kind.required_feature()?;
+    if config.feature_enabled(feature) {
+        return None;
+    }
+    Some((kind.mnemonic(), feature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_disabled_feature() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00,
+            // (func (return_call 0))
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x12, 0x00, 0x0b,
+        ];
+        let err = validate(&bytes, &Config::default()).unwrap_err();
+        assert_eq!(
+            err.message,
+            "instruction return_call requires the `tail-call` feature, which is disabled; pass `--enable tail-call`"
+        );
+        let config = Config::builder().tail_call(true).build();
+        assert!(validate(&bytes, &config).is_ok());
+    }
+
+    /// A module with `global` and then a function with `body`
+    fn extended_const_module(body: &[u8]) -> Vec<u8> {
+        #[rustfmt::skip]
+        let mut bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00,
+            // (global i32 (i32.add (i32.const 1) (i32.const 2)))
+            0x06, 0x09, 0x01, 0x7f, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x0b,
+        ];
+        bytes.extend_from_slice(&[0x0a, body.len() as u8 + 2, 0x01, body.len() as u8]);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn accepts_extended_const_when_enabled() {
+        let bytes = extended_const_module(&[0x00, 0x0b]);
+        let err = validate(&bytes, &Config::default()).unwrap_err();
+        assert!(err.message.contains("--enable extended-const"), "{}", err);
+        let config = Config::builder().extended_const(true).build();
+        assert_eq!(validate(&bytes, &config), Ok(()));
+    }
+
+    #[test]
+    fn reports_offsets_past_extended_const_in_the_original_module() {
+        // (func i32.add), which underflows the stack
+        let bytes = extended_const_module(&[0x00, 0x6a, 0x0b]);
+        let config = Config::builder().extended_const(true).build();
+        let err = validate(&bytes, &config).unwrap_err();
+        // The `i32.add`, after the code section header and the body's size
+        // and locals
+        assert_eq!(err.offset, 34);
+    }
+}
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::ops::{BitAnd, BitOr, Not};
 
 #[derive(Debug)]
@@ -45,12 +46,23 @@ pub enum NumVal {
 pub struct F32(u32);
 
 impl F32 {
+    /// Positive quiet NaN with an empty payload
+    pub const CANONICAL_NAN_BITS: u32 = 0x7fc00000;
+
     pub fn to_bits(&self) -> u32 {
         self.0
     }
     pub fn to_float(&self) -> f32 {
         f32::from_bits(self.0)
     }
+    /// Replace any NaN with the canonical one
+    pub fn canonicalize_nan(self) -> Self {
+        if self.to_float().is_nan() {
+            F32(Self::CANONICAL_NAN_BITS)
+        } else {
+            self
+        }
+    }
 }
 
 /// A wrapper to represent f64 (64-bit IEEE 754-2008) in WebAssembly runtime, used to keep internal bit pattern.
@@ -58,12 +70,23 @@ impl F32 {
 pub struct F64(u64);
 
 impl F64 {
+    /// Positive quiet NaN with an empty payload
+    pub const CANONICAL_NAN_BITS: u64 = 0x7ff8000000000000;
+
     pub fn to_bits(&self) -> u64 {
         self.0
     }
     pub fn to_float(&self) -> f64 {
         f64::from_bits(self.0)
     }
+    /// Replace any NaN with the canonical one
+    pub fn canonicalize_nan(self) -> Self {
+        if self.to_float().is_nan() {
+            F64(Self::CANONICAL_NAN_BITS)
+        } else {
+            self
+        }
+    }
 }
 
 /// Runtime representation of a reference type
@@ -73,6 +96,15 @@ pub enum RefType {
     ExternRef,
 }
 
+impl std::fmt::Display for RefType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RefType::FuncRef => write!(f, "funcref"),
+            RefType::ExternRef => write!(f, "externref"),
+        }
+    }
+}
+
 impl From<RefType> for wasmparser::Type {
     fn from(from: RefType) -> Self {
         match from {
@@ -108,6 +140,15 @@ impl Value {
         Value::Num(NumVal::F64(F64(v)))
     }
 
+    /// Replace a NaN float with the canonical NaN, leaving other values as they are
+    pub fn canonicalize_nan(self) -> Value {
+        match self {
+            Value::Num(NumVal::F32(v)) => Value::Num(NumVal::F32(v.canonicalize_nan())),
+            Value::Num(NumVal::F64(v)) => Value::Num(NumVal::F64(v.canonicalize_nan())),
+            other => other,
+        }
+    }
+
     pub fn null_ref(ty: wasmparser::Type) -> Option<Value> {
         let r = match ty {
             wasmparser::Type::FuncRef => RefVal::NullRef(RefType::FuncRef),
@@ -139,7 +180,7 @@ impl Value {
             Value::Num(NumVal::I64(_)) => wasmparser::Type::I64,
             Value::Num(NumVal::F32(_)) => wasmparser::Type::F32,
             Value::Num(NumVal::F64(_)) => wasmparser::Type::F64,
-            Value::Ref(RefVal::NullRef(_)) => wasmparser::Type::FuncRef,
+            Value::Ref(RefVal::NullRef(ty)) => (*ty).into(),
             Value::Ref(RefVal::FuncRef(_)) => wasmparser::Type::FuncRef,
             Value::Ref(RefVal::ExternRef(_)) => wasmparser::Type::ExternRef,
         }
@@ -172,6 +213,69 @@ impl Value {
             _ => None,
         }
     }
+
+    /// The bits of an `i32` as unsigned
+    pub fn as_u32(self) -> Option<u32> {
+        self.as_i32().map(|v| v as u32)
+    }
+
+    /// The bits of an `i64` as unsigned
+    pub fn as_u64(self) -> Option<u64> {
+        self.as_i64().map(|v| v as u64)
+    }
+
+    pub fn as_ref_val(self) -> Option<RefVal> {
+        match self {
+            Value::Ref(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// A value converted to a Rust type which its type doesn't match
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueTypeMismatch {
+    pub expected: wasmparser::Type,
+    pub actual: wasmparser::Type,
+}
+
+impl std::fmt::Display for ValueTypeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected a value of type {:?} but got {:?}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ValueTypeMismatch {}
+
+macro_rules! impl_try_from_value {
+    ($type:ty, $as:ident, $expected:ident) => {
+        impl TryFrom<Value> for $type {
+            type Error = ValueTypeMismatch;
+            fn try_from(val: Value) -> Result<Self, Self::Error> {
+                val.$as().ok_or(ValueTypeMismatch {
+                    expected: wasmparser::Type::$expected,
+                    actual: val.value_type(),
+                })
+            }
+        }
+    };
+}
+
+impl_try_from_value!(i32, as_i32, I32);
+impl_try_from_value!(i64, as_i64, I64);
+impl_try_from_value!(u32, as_u32, I32);
+impl_try_from_value!(u64, as_u64, I64);
+impl_try_from_value!(f32, as_f32, F32);
+impl_try_from_value!(f64, as_f64, F64);
+
+impl From<RefVal> for Value {
+    fn from(val: RefVal) -> Self {
+        Self::Ref(val)
+    }
 }
 
 impl From<i32> for Value {
@@ -708,6 +812,13 @@ macro_rules! impl_nearest {
         impl Nearest for $type {
             fn nearest(&self) -> Self {
                 let this = self.to_float();
+                // Rounding may keep a signaling NaN as it is, but the result
+                // has to be quiet
+                if this.is_nan() {
+                    return Self::from_bits(self.to_bits() | Self::arithmetic_bits());
+                }
+                // `round` rounds half away from zero, while wasm rounds half
+                // to even, so ties are corrected below
                 let round = this.round();
                 #[allow(clippy::float_cmp)]
                 if this.fract().abs() != 0.5 {
@@ -748,9 +859,9 @@ impl I64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::value::IEEE754;
+    use crate::value::{Nearest, IEEE754};
 
-    use crate::F32;
+    use crate::{F32, F64};
 
     #[test]
     fn floating_value_min() {
@@ -759,4 +870,61 @@ mod tests {
             (-0.0_f32).to_bits()
         );
     }
+
+    #[test]
+    fn nearest_rounds_half_to_even() {
+        let cases = [
+            (0.5, 0.0),
+            (1.5, 2.0),
+            (2.5, 2.0),
+            (-0.5, -0.0),
+            (-1.5, -2.0),
+            (-2.5, -2.0),
+            (0.4, 0.0),
+            (-0.4, -0.0),
+            (2.6, 3.0),
+            (4503599627370495.5, 4503599627370496.0),
+        ];
+        for (input, expected) in cases {
+            let result = F64::from_native(input).nearest().to_float();
+            assert_eq!(
+                result.to_bits(),
+                f64::to_bits(expected),
+                "nearest({})",
+                input
+            );
+        }
+        for (input, expected) in [(0.5f32, 0.0f32), (2.5, 2.0), (3.5, 4.0), (-0.5, -0.0)] {
+            let result = F32::from_native(input).nearest().to_float();
+            assert_eq!(result.to_bits(), expected.to_bits(), "nearest({})", input);
+        }
+    }
+
+    #[test]
+    fn nearest_quiets_nan() {
+        let signaling = F32::from_bits(0x7fa0_0000);
+        assert_eq!(signaling.nearest().to_bits(), 0x7fe0_0000);
+    }
+
+    #[test]
+    fn converts_to_and_from_rust_types() {
+        use super::{Value, ValueTypeMismatch};
+        use std::convert::{TryFrom, TryInto};
+
+        let args: Vec<Value> = vec![1i32.into(), 2i64.into(), 1.5f32.into(), 2.5f64.into()];
+        assert_eq!(i32::try_from(args[0]), Ok(1));
+        assert_eq!(args[1].try_into(), Ok(2i64));
+        assert_eq!(f32::try_from(args[2]), Ok(1.5));
+        assert_eq!(args[3].as_f64(), Some(2.5));
+        assert_eq!(Value::from(u32::MAX).as_u32(), Some(u32::MAX));
+        assert_eq!(Value::I32(-1).as_u32(), Some(u32::MAX));
+        assert_eq!(
+            i32::try_from(args[1]),
+            Err(ValueTypeMismatch {
+                expected: wasmparser::Type::I32,
+                actual: wasmparser::Type::I64,
+            })
+        );
+        assert_eq!(args[0].as_ref_val(), None);
+    }
 }
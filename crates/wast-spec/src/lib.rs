@@ -3,19 +3,40 @@ use anyhow::{anyhow, bail, Context as _, Result};
 use std::collections::HashMap;
 use std::path::Path;
 use std::str;
+use std::time::{Duration, Instant};
 use wast::HeapType;
+mod runner;
 mod spectest;
+pub use runner::{run_dir, FileResult, Summary};
 pub use spectest::instantiate_spectest;
 use wasminspect_vm::{
-    invoke_func_ignoring_break, FuncAddr, ModuleIndex, NumVal, RefType, RefVal, WasmInstance, WasmValue,
-    F32, F64,
+    invoke_func_ignoring_break, validate, FuncAddr, ModuleIndex, NumVal, RefType, RefVal, Store,
+    Trap, ValidationError, WasmError, WasmInstance, WasmValue, F32, F64,
 };
 
+/// Where `run_buffer` has gotten to, reported to the progress callback.
+/// Lines are 1-based like in failure messages.
+#[derive(Debug, Clone)]
+pub enum Progress {
+    /// About to run a directive, like `module` or `assert_return`
+    Directive {
+        kind: &'static str,
+        line: usize,
+        col: usize,
+    },
+    /// The module defined at `line` took `duration` to instantiate
+    Instantiated { line: usize, duration: Duration },
+}
+
+type ProgressCallback = Box<dyn FnMut(&Progress)>;
+
 pub struct WastContext {
     module_index_by_name: HashMap<String, ModuleIndex>,
     instance: WasmInstance,
     current: Option<ModuleIndex>,
     config: wasminspect_vm::Config,
+    continue_on_failure: bool,
+    progress: Option<ProgressCallback>,
 }
 
 impl WastContext {
@@ -27,9 +48,30 @@ impl WastContext {
             instance,
             current: None,
             config,
+            continue_on_failure: false,
+            progress: None,
+        }
+    }
+
+    /// Call `progress` before each directive and after each module is
+    /// instantiated, to see how far a run has gotten
+    pub fn set_progress(&mut self, progress: impl FnMut(&Progress) + 'static) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    fn report(&mut self, event: Progress) {
+        if let Some(progress) = &mut self.progress {
+            progress(&event);
         }
     }
-    pub fn run_file(&mut self, path: &Path) -> Result<()> {
+
+    /// Keep running after an `assert_return` mismatch, collecting it in the
+    /// failures `run_buffer` returns instead of bailing
+    pub fn set_continue_on_failure(&mut self, enable: bool) {
+        self.continue_on_failure = enable;
+    }
+
+    pub fn run_file(&mut self, path: &Path) -> Result<Vec<String>> {
         let bytes = std::fs::read(path).unwrap();
         self.run_buffer(path.to_str().unwrap(), &bytes)
     }
@@ -53,7 +95,11 @@ impl WastContext {
         let start_section = Self::extract_start_section(&bytes)?;
         let module_index = self
             .instance
-            .load_module_from_module(module_name.map(|n| n.to_string()), &mut bytes)
+            .load_module_from_module_with_config(
+                module_name.map(|n| n.to_string()),
+                &mut bytes,
+                &self.config,
+            )
             .map_err(|e| anyhow!("Failed to instantiate: {}", e))?;
         if let Some(start_section) = start_section {
             let func_addr = FuncAddr::new_unsafe(module_index, start_section as usize);
@@ -68,7 +114,9 @@ impl WastContext {
         Ok(())
     }
 
-    pub fn run_buffer(&mut self, filename: &str, wast: &[u8]) -> Result<()> {
+    /// Run every directive in `wast`. Returns the `assert_return` mismatches
+    /// when continuing on failure, which are empty otherwise.
+    pub fn run_buffer(&mut self, filename: &str, wast: &[u8]) -> Result<Vec<String>> {
         use wast::WastDirective::*;
 
         let wast = str::from_utf8(wast)?;
@@ -78,20 +126,37 @@ impl WastContext {
             err.set_text(wast);
             err
         };
-        let context = |sp: wast::Span| {
+        // 1-based lines, like editors show
+        let linecol = |sp: wast::Span| {
             let (line, col) = sp.linecol_in(wast);
-            format!("for directive on {}:{}:{}", filename, line + 1, col)
+            (line + 1, col)
+        };
+        let context = |sp: wast::Span| {
+            let (line, col) = linecol(sp);
+            format!("for directive on {}:{}:{}", filename, line, col)
         };
 
         let buf = wast::parser::ParseBuffer::new(wast).map_err(adjust_wast)?;
         let wast = wast::parser::parse::<wast::Wast>(&buf).map_err(adjust_wast)?;
 
+        let mut failures = Vec::new();
         for directive in wast.directives {
+            let (line, col) = linecol(directive.span());
+            self.report(Progress::Directive {
+                kind: directive_kind(&directive),
+                line,
+                col,
+            });
             match directive {
                 Module(mut module) => {
                     let bytes = module.encode().map_err(adjust_wast)?;
+                    let started = Instant::now();
                     self.module(module.id, bytes)
                         .map_err(|err| anyhow!("{}, {}", err, context(module.span)))?;
+                    self.report(Progress::Instantiated {
+                        line,
+                        duration: started.elapsed(),
+                    });
                 }
                 Register {
                     span: _,
@@ -103,6 +168,7 @@ impl WastContext {
                 }
                 Invoke(i) => {
                     self.invoke(i.module, i.name, &i.args)
+                        .with_context(|| context(i.span))?
                         .map_err(|err| anyhow!("Failed to invoke {}", err))
                         .with_context(|| context(i.span))?;
                 }
@@ -110,18 +176,32 @@ impl WastContext {
                     span,
                     exec,
                     results,
-                } => match self.perform_execute(exec).with_context(|| context(span)) {
-                    Ok(Ok(values)) => {
-                        for (v, e) in values.iter().zip(results) {
-                            if val_matches(v, &e)? {
-                                continue;
+                } => {
+                    let module_id = match &exec {
+                        wast::WastExecute::Invoke(i) => i.module,
+                        wast::WastExecute::Get { module, .. } => *module,
+                        wast::WastExecute::Module(_) => None,
+                    };
+                    match self.perform_execute(exec).with_context(|| context(span)) {
+                        Ok(Ok(values)) => {
+                            let module_index = self.get_instance(module_id)?;
+                            for (v, e) in values.iter().zip(results) {
+                                if val_matches(v, &e, &self.instance.store, module_index)? {
+                                    continue;
+                                }
+                                let failure =
+                                    format!("expected {:?}, got {:?} {}", e, v, context(span));
+                                if !self.continue_on_failure {
+                                    bail!(failure)
+                                }
+                                failures.push(failure);
+                                break;
                             }
-                            bail!("expected {:?}, got {:?} {}", e, v, context(span))
                         }
+                        Ok(Err(e)) => panic!("unexpected err: {}, {}", e, context(span)),
+                        Err(e) => return Err(e),
                     }
-                    Ok(Err(e)) => panic!("unexpected err: {}, {}", e, context(span)),
-                    Err(e) => panic!("unexpected err: {}", e),
-                },
+                }
                 AssertTrap {
                     span,
                     exec,
@@ -135,7 +215,7 @@ impl WastContext {
                         }
                         panic!("{}\nexpected {}, got {}", context(span), message, result,)
                     }
-                    Err(err) => panic!("got wast level exception: {}", err),
+                    Err(err) => return Err(err),
                 },
                 AssertMalformed {
                     span,
@@ -177,7 +257,10 @@ impl WastContext {
                     span,
                     call,
                     message,
-                } => match self.invoke(call.module, call.name, &call.args) {
+                } => match self
+                    .invoke(call.module, call.name, &call.args)
+                    .with_context(|| context(span))?
+                {
                     Ok(values) => panic!("{}\nexpected trap, got {:?}", context(span), values),
                     Err(t) => {
                         let result = format!("{}", t);
@@ -225,23 +308,35 @@ impl WastContext {
                         e
                     })?;
                     let binary = wat.module.encode().map_err(adjust_wast)?;
+                    let started = Instant::now();
                     self.module(wat.module.id, binary)
                         .with_context(|| context(span))?;
+                    self.report(Progress::Instantiated {
+                        line,
+                        duration: started.elapsed(),
+                    });
                 }
                 AssertException { span, exec } => {
                     match self.perform_execute(exec).with_context(|| context(span)) {
                         Ok(Ok(values)) => {
                             panic!("{}\nexpected trap, got {:?}", context(span), values)
                         }
-                        Ok(Err(_)) => {
-                            todo!()
-                        }
-                        Err(err) => panic!("{}", err),
+                        // The directive doesn't name the tag or the thrown values,
+                        // so only the kind of the error can be checked
+                        Ok(Err(err)) => match err.downcast_ref::<WasmError>() {
+                            Some(WasmError::ExecutionError(Trap::UncaughtException { .. })) => {}
+                            _ => panic!(
+                                "{}\nexpected uncaught exception, got {}",
+                                context(span),
+                                err
+                            ),
+                        },
+                        Err(err) => return Err(err),
                     }
                 }
             }
         }
-        Ok(())
+        Ok(failures)
     }
 
     fn get_instance(&self, module_id: Option<wast::Id>) -> Result<ModuleIndex> {
@@ -252,10 +347,9 @@ impl WastContext {
                 .get(name)
                 .copied()
                 .ok_or_else(|| anyhow!("module not found with name {}", name)),
-            None => match self.current {
-                Some(current) => Ok(current),
-                None => panic!(),
-            },
+            None => self
+                .current
+                .ok_or_else(|| anyhow!("no module has been instantiated")),
         }
     }
 
@@ -277,9 +371,9 @@ impl WastContext {
         module_id: Option<wast::Id>,
         func_name: &str,
         args: &[wast::Expression],
-    ) -> Result<Vec<WasmValue>> {
+    ) -> Result<Result<Vec<WasmValue>>> {
         let module_index = self.get_instance(module_id)?;
-        let args = args.iter().map(const_expr).collect();
+        let args = args.iter().map(const_expr).collect::<Result<_>>()?;
         let result = self
             .instance
             .run(
@@ -288,18 +382,22 @@ impl WastContext {
                 args,
                 &self.config,
             )
-            .map_err(|e| anyhow!("{}", e))?;
+            .map_err(anyhow::Error::new);
         Ok(result)
     }
 
     fn perform_execute(&mut self, exec: wast::WastExecute<'_>) -> Result<Result<Vec<WasmValue>>> {
         match exec {
-            wast::WastExecute::Invoke(i) => Ok(self.invoke(i.module, i.name, &i.args)),
+            wast::WastExecute::Invoke(i) => self.invoke(i.module, i.name, &i.args),
             wast::WastExecute::Module(mut module) => {
                 let mut binary = module.encode()?;
                 self.validate(&binary)?;
                 let start_section = Self::extract_start_section(&binary)?;
-                let module_index = match self.instance.load_module_from_module(None, &mut binary) {
+                let module_index = match self.instance.load_module_from_module_with_config(
+                    None,
+                    &mut binary,
+                    &self.config,
+                ) {
                     Ok(idx) => idx,
                     Err(e) => return Ok(Err(anyhow!("while instntiation: {}", e))),
                 };
@@ -319,14 +417,37 @@ impl WastContext {
         }
     }
 
-    fn validate(&self, bytes: &[u8]) -> wasmparser::Result<()> {
-        let mut validator = wasmparser::Validator::new();
-        validator.wasm_features(self.config.features);
-        validator.validate_all(bytes)
+    fn validate(&self, bytes: &[u8]) -> std::result::Result<(), ValidationError> {
+        validate(bytes, &self.config)
+    }
+}
+
+/// Name of the directive as written in `.wast` files
+fn directive_kind(directive: &wast::WastDirective) -> &'static str {
+    use wast::WastDirective::*;
+    match directive {
+        Module(_) => "module",
+        QuoteModule { .. } => "module quote",
+        AssertMalformed { .. } => "assert_malformed",
+        AssertInvalid { .. } => "assert_invalid",
+        Register { .. } => "register",
+        Invoke(_) => "invoke",
+        AssertTrap { .. } => "assert_trap",
+        AssertReturn { .. } => "assert_return",
+        AssertExhaustion { .. } => "assert_exhaustion",
+        AssertUnlinkable { .. } => "assert_unlinkable",
+        AssertException { .. } => "assert_exception",
     }
 }
 
-fn val_matches(actual: &WasmValue, expected: &wast::AssertExpression) -> Result<bool> {
+/// `module_index` is the module whose function indices `ref.func`
+/// expectations refer to
+fn val_matches(
+    actual: &WasmValue,
+    expected: &wast::AssertExpression,
+    store: &Store,
+    module_index: ModuleIndex,
+) -> Result<bool> {
     Ok(match (actual, expected) {
         (WasmValue::Num(NumVal::I32(a)), wast::AssertExpression::I32(x)) => a == x,
         (WasmValue::Num(NumVal::I64(a)), wast::AssertExpression::I64(x)) => a == x,
@@ -344,7 +465,25 @@ fn val_matches(actual: &WasmValue, expected: &wast::AssertExpression) -> Result<
         (WasmValue::Ref(RefVal::NullRef(a)), wast::AssertExpression::RefNull(Some(x))) => {
             Some(*a) == to_ref_type(x)
         }
-        (_, wast::AssertExpression::V128(_)) => bail!("V128 is not supported yet"),
+        (WasmValue::Ref(RefVal::FuncRef(a)), wast::AssertExpression::RefFunc(x)) => match x {
+            // `(ref.func)` matches any function reference
+            None => true,
+            Some(wast::Index::Num(index, _)) => {
+                // Compare instances, an exported function is the same one
+                // whichever module the reference was taken in
+                let expected = FuncAddr::new_unsafe(module_index, *index as usize);
+                match (store.func(*a), store.func(expected)) {
+                    (Some((_, a)), Some((_, x))) => a == x,
+                    _ => false,
+                }
+            }
+            Some(wast::Index::Id(id)) => bail!("ref.func by name ${} is not supported", id.name()),
+        },
+        // The VM has no v128 values, so there is nothing to compare lanes of
+        // until it executes SIMD instructions
+        (_, wast::AssertExpression::V128(_)) => {
+            bail!("v128 results are not supported, SIMD is not executed")
+        }
         _ => bail!("unexpected comparing for {:?} and {:?}", actual, expected),
     })
 }
@@ -357,17 +496,27 @@ fn to_ref_type(heap_ty: &HeapType) -> Option<RefType> {
     }
 }
 
-fn const_expr(expr: &wast::Expression) -> WasmValue {
-    match &expr.instrs[0] {
+fn const_expr(expr: &wast::Expression) -> Result<WasmValue> {
+    let inst = match expr.instrs.first() {
+        Some(inst) => inst,
+        None => bail!("empty const expr"),
+    };
+    let value = match inst {
         wast::Instruction::I32Const(x) => WasmValue::I32(*x),
         wast::Instruction::I64Const(x) => WasmValue::I64(*x),
         wast::Instruction::F32Const(x) => WasmValue::F32(x.bits),
         wast::Instruction::F64Const(x) => WasmValue::F64(x.bits),
-        wast::Instruction::V128Const(_) => panic!(),
+        wast::Instruction::V128Const(_) => {
+            bail!("v128 arguments are not supported, SIMD is not executed")
+        }
         wast::Instruction::RefExtern(x) => WasmValue::Ref(RefVal::ExternRef(*x)),
-        wast::Instruction::RefNull(ty) => WasmValue::Ref(RefVal::NullRef(to_ref_type(ty).unwrap())),
-        other => panic!("unsupported const expr inst {:?}", other),
-    }
+        wast::Instruction::RefNull(ty) => match to_ref_type(ty) {
+            Some(ty) => WasmValue::Ref(RefVal::NullRef(ty)),
+            None => bail!("unsupported null reference type {:?}", ty),
+        },
+        other => bail!("unsupported const expr inst {:?}", other),
+    };
+    Ok(value)
 }
 
 fn is_canonical_f32_nan(f: &F32) -> bool {
@@ -385,3 +534,154 @@ fn is_arithmetic_f32_nan(f: &F32) -> bool {
 fn is_arithmetic_f64_nan(f: &F64) -> bool {
     (f.to_bits() & 0x0008000000000000) == 0x0008000000000000
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUNCREF_MODULE: &str = r#"
+        (module
+          (func (export "f") (result funcref) (ref.func $g))
+          (func $g)
+          (elem declare func $g))
+    "#;
+
+    #[test]
+    fn assert_return_compares_function_references() {
+        let wast = format!(
+            "{}\n(assert_return (invoke \"f\") (ref.func))\n\
+             (assert_return (invoke \"f\") (ref.func 1))",
+            FUNCREF_MODULE
+        );
+        let mut context = WastContext::new(wasminspect_vm::Config::default());
+        context.run_buffer("funcref.wast", wast.as_bytes()).unwrap();
+
+        let wast = format!(
+            "{}\n(assert_return (invoke \"f\") (ref.func 0))",
+            FUNCREF_MODULE
+        );
+        let mut context = WastContext::new(wasminspect_vm::Config::default());
+        let err = context
+            .run_buffer("funcref.wast", wast.as_bytes())
+            .unwrap_err();
+        assert!(err.to_string().starts_with("expected RefFunc"), "{}", err);
+    }
+
+    #[test]
+    fn imported_mutable_global_is_shared() {
+        let wast = r#"
+            (module $A
+              (global (export "g") (mut i32) (i32.const 1))
+              (func (export "get") (result i32) (global.get 0)))
+            (register "A" $A)
+            (module $B
+              (import "A" "g" (global $g (mut i32)))
+              (func (export "set") (param i32) (global.set $g (local.get 0)))
+              (func (export "get") (result i32) (global.get $g)))
+            (invoke $B "set" (i32.const 42))
+            (assert_return (invoke $B "get") (i32.const 42))
+            (assert_return (invoke $A "get") (i32.const 42))
+        "#;
+        let mut context = WastContext::new(wasminspect_vm::Config::default());
+        context.run_buffer("globals.wast", wast.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn imported_global_offsets_data_segment() {
+        let wast = r#"
+            (module $A
+              (global i32 (i32.const 1))
+              (global (export "base") i32 (i32.const 8)))
+            (register "A" $A)
+            (module $B
+              (import "A" "base" (global $base i32))
+              (memory 1)
+              (data (global.get $base) "\2a")
+              (func (export "load") (param i32) (result i32)
+                (i32.load8_u (local.get 0))))
+            (assert_return (invoke $B "load" (i32.const 8)) (i32.const 42))
+            (assert_return (invoke $B "load" (i32.const 1)) (i32.const 0))
+        "#;
+        let mut context = WastContext::new(wasminspect_vm::Config::default());
+        context.run_buffer("imports.wast", wast.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn applies_configured_limits_to_modules() {
+        let wast = r#"
+            (module
+              (memory 1)
+              (func (export "grow") (result i32) (memory.grow (i32.const 1))))
+            (assert_return (invoke "grow") (i32.const -1))
+        "#;
+        let config = wasminspect_vm::Config::builder()
+            .max_memory_pages(Some(1))
+            .build();
+        let mut context = WastContext::new(config);
+        context.run_buffer("limits.wast", wast.as_bytes()).unwrap();
+
+        let config = wasminspect_vm::Config::builder()
+            .max_memory_pages(Some(0))
+            .build();
+        let mut context = WastContext::new(config);
+        let err = context
+            .run_buffer("limits.wast", wast.as_bytes())
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("exceeds the configured limit"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn continue_on_failure_collects_mismatches() {
+        let wast = r#"
+            (module (func (export "f") (result i32) (i32.const 1)))
+            (assert_return (invoke "f") (i32.const 2))
+            (assert_return (invoke "f") (i32.const 1))
+            (assert_return (invoke "f") (i32.const 3))
+        "#;
+        let mut context = WastContext::new(wasminspect_vm::Config::default());
+        context.set_continue_on_failure(true);
+        let failures = context.run_buffer("fail.wast", wast.as_bytes()).unwrap();
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].contains("fail.wast:3:"), "{}", failures[0]);
+        assert!(failures[1].contains("fail.wast:5:"), "{}", failures[1]);
+    }
+
+    #[test]
+    fn reports_progress_per_directive() {
+        let wast = r#"
+            (module (func (export "f") (result i32) (i32.const 1)))
+            (assert_return (invoke "f") (i32.const 1))
+        "#;
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut context = WastContext::new(wasminspect_vm::Config::default());
+        let recorded = events.clone();
+        context.set_progress(move |event| recorded.borrow_mut().push(event.clone()));
+        context
+            .run_buffer("progress.wast", wast.as_bytes())
+            .unwrap();
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            events[0],
+            Progress::Directive {
+                kind: "module",
+                line: 2,
+                ..
+            }
+        ));
+        assert!(matches!(events[1], Progress::Instantiated { line: 2, .. }));
+        assert!(matches!(
+            events[2],
+            Progress::Directive {
+                kind: "assert_return",
+                line: 3,
+                ..
+            }
+        ));
+    }
+}
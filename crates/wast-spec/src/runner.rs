@@ -0,0 +1,165 @@
+use crate::WastContext;
+use anyhow::Result;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wasminspect_vm::Config;
+
+/// Outcome of running a single `.wast` file
+pub struct FileResult {
+    pub path: PathBuf,
+    pub duration: Duration,
+    /// `None` if every directive passed
+    pub error: Option<String>,
+}
+
+/// Results of `run_dir`, ordered by path
+pub struct Summary {
+    pub results: Vec<FileResult>,
+    pub duration: Duration,
+}
+
+impl Summary {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.error.is_none()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+}
+
+/// Lists failures in path order followed by the totals. Timings are left
+/// out so that the report is stable across runs.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            if let Some(err) = &result.error {
+                writeln!(f, "FAIL {}: {}", result.path.display(), err)?;
+            }
+        }
+        write!(f, "{} passed; {} failed", self.passed(), self.failed())
+    }
+}
+
+/// Run every `.wast` file under `dir` on a pool of threads, one fresh
+/// `WastContext` per file
+pub fn run_dir(dir: &Path, config: &Config) -> Result<Summary> {
+    let started = Instant::now();
+    let mut paths = Vec::new();
+    collect_wast_files(dir, &mut paths)?;
+    paths.sort();
+
+    let paths = Arc::new(paths);
+    let next = Arc::new(AtomicUsize::new(0));
+    let workers = num_cpus::get().min(paths.len()).max(1);
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let paths = paths.clone();
+            let next = next.clone();
+            let config = config.clone();
+            thread::spawn(move || {
+                let mut results = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    match paths.get(index) {
+                        Some(path) => results.push(run_one(path, config.clone())),
+                        None => break results,
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(paths.len());
+    for handle in handles {
+        results.extend(handle.join().expect("runner thread panicked"));
+    }
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Summary {
+        results,
+        duration: started.elapsed(),
+    })
+}
+
+fn run_one(path: &Path, config: Config) -> FileResult {
+    let started = Instant::now();
+    // Failed assertions panic, so catch them to keep the other files running
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut context = WastContext::new(config);
+        context.set_continue_on_failure(true);
+        context.run_file(path)
+    }));
+    let error = match result {
+        Ok(Ok(failures)) if failures.is_empty() => None,
+        Ok(Ok(failures)) => Some(format!(
+            "{} assertions failed\n{}",
+            failures.len(),
+            failures.join("\n")
+        )),
+        Ok(Err(err)) => Some(format!("{:?}", err)),
+        Err(payload) => Some(
+            payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "panicked".to_string()),
+        ),
+    };
+    FileResult {
+        path: path.to_path_buf(),
+        duration: started.elapsed(),
+        error,
+    }
+}
+
+fn collect_wast_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_wast_files(&path, paths)?;
+        } else if path.extension().map_or(false, |ext| ext == "wast") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_dir_reports_failures() {
+        let dir = std::env::temp_dir().join(format!("wast-spec-run-dir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(
+            dir.join("pass.wast"),
+            "(module (func (export \"f\") (result i32) (i32.const 1)))\n\
+             (assert_return (invoke \"f\") (i32.const 1))",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("nested").join("fail.wast"),
+            "(module (func (export \"f\") (result i32) (i32.const 1)))\n\
+             (assert_return (invoke \"f\") (i32.const 2))",
+        )
+        .unwrap();
+        std::fs::write(dir.join("ignored.txt"), "").unwrap();
+
+        let summary = run_dir(&dir, &Config::default()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let paths: Vec<_> = summary.results.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![dir.join("nested").join("fail.wast"), dir.join("pass.wast")]
+        );
+        assert_eq!((summary.passed(), summary.failed()), (1, 1));
+        assert!(summary.to_string().ends_with("1 passed; 1 failed"));
+    }
+}
@@ -2,7 +2,8 @@ use anyhow::anyhow;
 
 use std::io::Read;
 use structopt::StructOpt;
-use wasminspect_debugger::{self, ModuleInput};
+use wasminspect_debugger::{self, ModuleInput, OutputFormat};
+use wasminspect_vm::Config;
 
 fn parse_env_var(s: &str) -> anyhow::Result<(String, String)> {
     let parts: Vec<_> = s.splitn(2, '=').collect();
@@ -22,12 +23,15 @@ fn parse_map_dirs(s: &str) -> anyhow::Result<(String, String)> {
 
 #[derive(StructOpt)]
 struct Opts {
-    /// The wasm binary file
+    /// The wasm binary file, or `-` to read it from stdin
     #[structopt(name = "FILE")]
     filepath: Option<String>,
     /// Tells the debugger to read in and execute the debugger commands in given file, after wasm file has been loaded
     #[structopt(short, long)]
     source: Option<String>,
+    /// Run the debugger commands in given file and exit, with a failure status if any of them fails
+    #[structopt(long, value_name = "FILE", conflicts_with = "source")]
+    batch: Option<String>,
     /// Grant access to a guest directory mapped as a host directory
     #[structopt(long = "mapdir", number_of_values = 1, value_name = "GUEST_DIR::HOST_DIR", parse(try_from_str = parse_map_dirs))]
     map_dirs: Vec<(String, String)>,
@@ -35,35 +39,146 @@ struct Opts {
     /// Pass an environment variable to the program
     #[structopt(long = "env", number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
     envs: Vec<(String, String)>,
+
+    /// Write every executed instruction to the given file as JSON Lines
+    #[structopt(long, value_name = "FILE")]
+    trace: Option<String>,
+
+    /// Enable a WebAssembly proposal
+    #[structopt(long, number_of_values = 1, value_name = "FEATURE", possible_values = Config::FEATURE_NAMES)]
+    enable: Vec<String>,
+
+    /// Disable a WebAssembly proposal, even one enabled by default
+    #[structopt(long, number_of_values = 1, value_name = "FEATURE", possible_values = Config::FEATURE_NAMES)]
+    disable: Vec<String>,
+
+    /// Print command results as text, or as one JSON object per line for tools
+    #[structopt(long, value_name = "FORMAT", default_value = "text", possible_values = OutputFormat::NAMES)]
+    output: OutputFormat,
+
+    /// Don't read or write the command history file
+    #[structopt(long)]
+    no_history: bool,
+
+    /// Log more details, like the enabled proposals
+    #[structopt(short, long)]
+    verbose: bool,
+
+    #[structopt(subcommand)]
+    subcommand: Option<Subcommand>,
+}
+
+#[derive(StructOpt)]
+enum Subcommand {
+    /// Load the wasm binary file, run debugger commands and exit without prompting
+    Eval {
+        /// The wasm binary file, or `-` to read it from stdin
+        #[structopt(name = "FILE")]
+        filepath: String,
+        /// A command to run before the ones given with `-c`
+        #[structopt(name = "COMMAND")]
+        command: Option<String>,
+        /// A command to run, in the order given
+        #[structopt(short = "c", long = "command", number_of_values = 1)]
+        commands: Vec<String>,
+    },
+}
+
+/// Apply `--enable` and then `--disable`, so disabling wins
+fn build_config(enable: &[String], disable: &[String]) -> Config {
+    let toggles = enable
+        .iter()
+        .map(|name| (name, true))
+        .chain(disable.iter().map(|name| (name, false)));
+    toggles
+        .fold(Config::builder(), |builder, (name, enable)| {
+            // Names were checked against `possible_values`
+            builder.feature(name, enable).expect("unknown feature")
+        })
+        .build()
+}
+
+/// Read the module at `filepath`, or from stdin for `-`
+fn read_module(filepath: String) -> anyhow::Result<ModuleInput> {
+    let mut buffer = Vec::new();
+    if filepath == "-" {
+        std::io::stdin().read_to_end(&mut buffer)?;
+        return Ok(ModuleInput {
+            bytes: buffer,
+            basename: "<stdin>".to_string(),
+        });
+    }
+    let filepath = std::path::Path::new(&filepath);
+    let basename = filepath
+        .file_name()
+        .expect("invalid file path")
+        .to_str()
+        .expect("invalid file name encoding")
+        .to_string();
+    let mut f = std::fs::File::open(filepath)?;
+    f.read_to_end(&mut buffer)?;
+    Ok(ModuleInput {
+        bytes: buffer,
+        basename,
+    })
 }
 
 fn main() -> anyhow::Result<()> {
-    env_logger::init_from_env(env_logger::Env::default().default_filter_or("warn"));
+    let opts = Opts::from_args_safe().unwrap_or_else(|err| {
+        if err.use_stderr() {
+            eprintln!("{}", err.message);
+            std::process::exit(wasminspect_debugger::EXIT_FAILURE);
+        }
+        err.exit()
+    });
+    let log_level = if opts.verbose { "info" } else { "warn" };
+    env_logger::init_from_env(env_logger::Env::default().default_filter_or(log_level));
 
-    let opts = Opts::from_args();
-    let module_input = match opts.filepath {
-        Some(filepath) => {
-            let mut buffer = Vec::new();
-            let filepath = std::path::Path::new(&filepath);
-            let basename = filepath
-                .file_name()
-                .expect("invalid file path")
-                .to_str()
-                .expect("invalid file name encoding")
-                .to_string();
-            let mut f = std::fs::File::open(filepath)?;
-            f.read_to_end(&mut buffer)?;
-            Some(ModuleInput {
-                bytes: buffer,
-                basename,
-            })
+    let config = build_config(&opts.enable, &opts.disable);
+    if opts.verbose {
+        let features: Vec<_> = config
+            .feature_states()
+            .into_iter()
+            .map(|(name, enabled)| format!("{}{}", if enabled { "+" } else { "-" }, name))
+            .collect();
+        eprintln!("WebAssembly features: {}", features.join(" "));
+    }
+    let options = wasminspect_debugger::RunOptions {
+        preopen_dirs: opts.map_dirs,
+        envs: opts.envs,
+        trace: opts.trace,
+        config,
+        output: opts.output,
+    };
+    if let Some(Subcommand::Eval {
+        filepath,
+        command,
+        commands,
+    }) = opts.subcommand
+    {
+        let commands = command.into_iter().chain(commands).collect();
+        if let Err(err) =
+            wasminspect_debugger::run_eval(Some(read_module(filepath)?), commands, options)
+        {
+            std::process::exit(wasminspect_debugger::exit_status(&err));
         }
+        return Ok(());
+    }
+    let module_input = match opts.filepath {
+        Some(filepath) => Some(read_module(filepath)?),
         None => None,
     };
+    if let Some(batch) = opts.batch {
+        if let Err(err) = wasminspect_debugger::run_batch(module_input, batch, options) {
+            std::process::exit(wasminspect_debugger::exit_status(&err));
+        }
+        return Ok(());
+    }
     if let Err(err) =
-        wasminspect_debugger::run_loop(module_input, opts.source, opts.map_dirs, opts.envs)
+        wasminspect_debugger::run_loop(module_input, opts.source, !opts.no_history, options)
     {
-        println!("{:?}", err)
+        eprintln!("{:?}", err);
+        std::process::exit(wasminspect_debugger::exit_status(&err));
     }
     Ok(())
 }
@@ -7,6 +7,9 @@ struct Opts {
     /// The wasm binary file
     #[structopt(default_value = "127.0.0.1:4000")]
     listen_addr: String,
+    /// Speak the Debug Adapter Protocol over stdio instead of serving websocket
+    #[structopt(long)]
+    dap: bool,
 }
 
 #[tokio::main]
@@ -14,6 +17,9 @@ async fn main() -> anyhow::Result<()> {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("warn"));
 
     let opts = Opts::from_args();
+    if opts.dap {
+        return wasminspect_debugger_server::start_dap();
+    }
     let addr = SocketAddr::from_str(&opts.listen_addr)?;
     wasminspect_debugger_server::start(addr).await;
     Ok(())
@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasminspect_vm::Config;
+use wast_spec::{Progress, WastContext};
+
+#[derive(StructOpt)]
+struct Opts {
+    /// The .wast files to run
+    #[structopt(name = "FILE", required = true)]
+    files: Vec<PathBuf>,
+    /// Keep running after a failed assert_return and report all of them
+    #[structopt(long)]
+    keep_going: bool,
+    /// Print each directive before running it and how long modules take to instantiate
+    #[structopt(short, long)]
+    verbose: bool,
+}
+
+fn main() {
+    let opts = Opts::from_args();
+    let mut failed = false;
+    for file in opts.files {
+        let mut context = WastContext::new(Config::default());
+        context.set_continue_on_failure(opts.keep_going);
+        if opts.verbose {
+            let filename = file.display().to_string();
+            context.set_progress(move |event| match event {
+                Progress::Directive {
+                    kind: "module",
+                    line,
+                    ..
+                } => eprintln!("{}: instantiating module at line {}", filename, line),
+                Progress::Directive { kind, line, .. } => {
+                    eprintln!("{}: running {} at line {}", filename, kind, line)
+                }
+                Progress::Instantiated { line, duration } => eprintln!(
+                    "{}: instantiated module at line {} in {:?}",
+                    filename, line, duration
+                ),
+            });
+        }
+        match context.run_file(&file) {
+            Ok(failures) if failures.is_empty() => {}
+            Ok(failures) => {
+                failed = true;
+                for failure in &failures {
+                    eprintln!("{}", failure);
+                }
+                eprintln!("{}: {} assertions failed", file.display(), failures.len());
+            }
+            Err(err) => {
+                failed = true;
+                eprintln!("{}: {:?}", file.display(), err);
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
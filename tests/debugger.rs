@@ -12,6 +12,51 @@ fn load_file(filename: &str) -> anyhow::Result<Vec<u8>> {
     Ok(buffer)
 }
 
+/// A path in the temp dir which parallel test runs don't share
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("wasminspect-{}-{}", std::process::id(), name))
+}
+
+/// Start a debugger with `calc.wasm` from the simple examples instantiated
+fn start_calc_debugger() -> anyhow::Result<(Process<MainDebugger>, CommandContext)> {
+    let (mut process, context) = start_debugger(None, vec![], vec![])?;
+    let example_dir = std::path::Path::new(file!())
+        .parent()
+        .unwrap()
+        .join("simple-example");
+    let bytes = load_file(example_dir.join("calc.wasm").to_str().unwrap())?;
+    let mut host_modules = HashMap::new();
+    host_modules.insert("spectest".to_string(), instantiate_spectest());
+    process
+        .debugger
+        .load_main_module(&bytes, String::from("calc.wasm"))?;
+    process.debugger.instantiate(host_modules, &[])?;
+    Ok((process, context))
+}
+
+/// Start a debugger with the text module `wat` instantiated. The spectest
+/// host module is there for it to import from.
+fn start_wat_debugger(wat: &str) -> anyhow::Result<(Process<MainDebugger>, CommandContext)> {
+    start_wat_debugger_with_config(wat, Config::default())
+}
+
+/// `start_wat_debugger` validating and running the module with `config`
+fn start_wat_debugger_with_config(
+    wat: &str,
+    config: Config,
+) -> anyhow::Result<(Process<MainDebugger>, CommandContext)> {
+    let module_input = ModuleInput {
+        bytes: wat.as_bytes().to_vec(),
+        basename: String::from("test.wat"),
+    };
+    let (mut process, context) =
+        start_debugger_with_config(Some(module_input), vec![], vec![], config)?;
+    let mut host_modules = HashMap::new();
+    host_modules.insert("spectest".to_string(), instantiate_spectest());
+    process.debugger.instantiate(host_modules, &[])?;
+    Ok((process, context))
+}
+
 #[test]
 fn test_load_and_execute() -> anyhow::Result<()> {
     let (mut process, _) = start_debugger(None, vec![], vec![])?;
@@ -33,3 +78,1026 @@ fn test_load_and_execute() -> anyhow::Result<()> {
         .run(Some("add"), vec![WasmValue::I32(1), WasmValue::I32(2)])?;
     Ok(())
 }
+
+#[test]
+fn test_session_save_and_load() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_debugger(None, vec![], vec![])?;
+    let example_dir = std::path::Path::new(file!())
+        .parent()
+        .unwrap()
+        .join("simple-example");
+    let bytes = load_file(example_dir.join("calls.wasm").to_str().unwrap())?;
+    process
+        .debugger
+        .load_main_module(&bytes, String::from("calls.wasm"))?;
+    process.dispatch_command("breakpoint set -n inc", &mut context)?;
+    process.dispatch_command("breakpoint set -a 0x30", &mut context)?;
+
+    let session_file = temp_path("session.json");
+    let session_file = session_file.to_str().unwrap();
+    process.dispatch_command(&format!("session save {}", session_file), &mut context)?;
+
+    let (mut restored, mut context) = start_debugger(None, vec![], vec![])?;
+    restored.dispatch_command(&format!("session load {}", session_file), &mut context)?;
+    std::fs::remove_file(session_file)?;
+
+    let (restored_bytes, name) = restored.debugger.main_module_source().unwrap();
+    assert_eq!(restored_bytes, bytes.as_slice());
+    assert_eq!(name, "calls.wasm");
+    assert_eq!(
+        format!("{:?}", restored.debugger.breakpoints()),
+        format!("{:?}", process.debugger.breakpoints())
+    );
+    assert_eq!(restored.debugger.breakpoints().len(), 2);
+    assert!(restored.debugger.instance.is_some());
+    // `direct` names its param and local
+    assert_eq!(context.names.local_name(2, 0), "n");
+    Ok(())
+}
+
+#[test]
+fn test_breakpoints_survive_reload() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_debugger(None, vec![], vec![])?;
+    let example_dir = std::path::Path::new(file!())
+        .parent()
+        .unwrap()
+        .join("simple-example");
+    let bytes = load_file(example_dir.join("calc.wasm").to_str().unwrap())?;
+    reload_main_module(
+        &mut process.debugger,
+        &mut context,
+        &bytes,
+        String::from("calc.wasm"),
+    )?;
+    process.dispatch_command("breakpoint set -n add", &mut context)?;
+    process.dispatch_command("breakpoint set -a 0xffffff", &mut context)?;
+
+    reload_main_module(
+        &mut process.debugger,
+        &mut context,
+        &bytes,
+        String::from("calc.wasm"),
+    )?;
+    assert_eq!(process.debugger.breakpoints().len(), 2);
+    assert_eq!(
+        process.debugger.resolve_breakpoints(&*context.sourcemap),
+        vec![Breakpoint::Instruction {
+            inst_offset: 0xffffff
+        }]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_profile_counts_executed_instructions() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_calc_debugger()?;
+    let run = |process: &mut Process<MainDebugger>| {
+        process
+            .debugger
+            .run(Some("add"), vec![WasmValue::I32(1), WasmValue::I32(2)])
+    };
+
+    // Nothing is counted until profiling is started
+    run(&mut process)?;
+    assert!(process.debugger.profile()?.is_empty());
+
+    process.dispatch_command("profile --start", &mut context)?;
+    run(&mut process)?;
+    let profile = process.debugger.profile()?;
+    let (_, count) = profile
+        .iter()
+        .find(|(name, _)| name == "add")
+        .expect("add should be profiled");
+    assert!(*count > 0);
+
+    process.dispatch_command("profile --stop", &mut context)?;
+    run(&mut process)?;
+    assert_eq!(process.debugger.profile()?, profile);
+
+    process.dispatch_command("profile --reset", &mut context)?;
+    assert!(process.debugger.profile()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_coverage_records_executed_offsets() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_calc_debugger()?;
+    process.dispatch_command("coverage start", &mut context)?;
+    process
+        .debugger
+        .run(Some("add"), vec![WasmValue::I32(1), WasmValue::I32(2)])?;
+
+    let coverage = process.debugger.coverage()?;
+    let add = coverage
+        .iter()
+        .find(|func| func.name == "add")
+        .expect("add should be listed");
+    assert!(!add.covered_offsets.is_empty());
+    assert!(add.covered_offsets.len() <= add.instruction_count);
+    assert!(add.covered_offsets.windows(2).all(|w| w[0] < w[1]));
+
+    let coverage_file = temp_path("coverage.json");
+    let coverage_file = coverage_file.to_str().unwrap();
+    process.dispatch_command(&format!("coverage dump {}", coverage_file), &mut context)?;
+    let dumped = String::from_utf8(load_file(coverage_file)?)?;
+    std::fs::remove_file(coverage_file)?;
+    assert!(dumped.contains(r#""name":"add""#));
+
+    // Stopping keeps what was covered so far
+    process.dispatch_command("coverage stop", &mut context)?;
+    process
+        .debugger
+        .run(Some("mul"), vec![WasmValue::I32(1), WasmValue::I32(2)])?;
+    let coverage = process.debugger.coverage()?;
+    let covered_funcs: Vec<_> = coverage
+        .iter()
+        .filter(|func| !func.covered_offsets.is_empty())
+        .map(|func| func.name.as_str())
+        .collect();
+    assert_eq!(covered_funcs, vec!["add"]);
+    Ok(())
+}
+
+#[test]
+fn test_trace_writes_executed_instructions() -> anyhow::Result<()> {
+    let (mut process, _) = start_calc_debugger()?;
+
+    let trace_file = temp_path("trace.jsonl");
+    process
+        .debugger
+        .set_trace(Some(Box::new(std::fs::File::create(&trace_file)?)));
+    process
+        .debugger
+        .run(Some("add"), vec![WasmValue::I32(1), WasmValue::I32(2)])?;
+    process.debugger.set_trace(None);
+
+    let trace = String::from_utf8(load_file(trace_file.to_str().unwrap())?)?;
+    std::fs::remove_file(&trace_file)?;
+    assert!(!trace.is_empty());
+    assert!(trace.lines().all(|line| line.starts_with(r#"{"pc":"#)));
+    let add = trace
+        .lines()
+        .find(|line| line.contains(r#""op":"I32Add""#))
+        .expect("i32.add should be traced");
+    assert!(add.ends_with(r#""stack":["i32:2","i32:1"]}"#));
+    Ok(())
+}
+
+#[test]
+fn test_settings_set_validates_values() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_debugger(None, vec![], vec![])?;
+    process.dispatch_command("settings set disassemble-count 8", &mut context)?;
+    process.dispatch_command("settings set break-on-trap true", &mut context)?;
+    let opts = process.debugger.get_opts();
+    assert_eq!(opts.disassemble_count, 8);
+    assert!(opts.break_on_trap);
+
+    process.dispatch_command("settings set disassemble-count many", &mut context)?;
+    process.dispatch_command("settings set no-such-setting 1", &mut context)?;
+    assert_eq!(process.debugger.get_opts().disassemble_count, 8);
+    Ok(())
+}
+
+#[test]
+fn test_trap_reports_code_offset() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "trap") unreachable))
+    "#;
+    let (mut process, _) = start_wat_debugger(wat)?;
+    let err = match process.debugger.run(Some("trap"), vec![]) {
+        Ok(_) => panic!("expected a trap"),
+        Err(err) => err,
+    };
+    let trap = err.downcast_ref::<TrapError>().expect("should be a trap");
+    // Code offsets are relative to the code section, as in DWARF
+    assert_eq!(trap.code_offset, 3);
+    assert!(trap.message.contains("unreachable"));
+    Ok(())
+}
+
+#[test]
+fn test_break_on_trap_keeps_state() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "trap") nop unreachable))
+    "#;
+    let (mut process, _) = start_wat_debugger(wat)?;
+    let mut opts = process.debugger.get_opts();
+    opts.break_on_trap = true;
+    process.debugger.set_opts(opts);
+    match process.debugger.run(Some("trap"), vec![])? {
+        RunResult::Trap(err) => assert!(err.to_string().contains("unreachable")),
+        _ => panic!("expected to stop at the trap"),
+    }
+    assert!(process.debugger.is_running());
+    assert_eq!(process.debugger.frame().len(), 1);
+    // The faulting instruction is the current one, like a breakpoint's
+    let (insts, next_index) = process.debugger.instructions()?;
+    assert_eq!(next_index, 2);
+    assert_eq!(insts[next_index - 1].offset, 4);
+    Ok(())
+}
+
+#[test]
+fn test_break_on_unreachable_in_callee() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (call 1))
+          (func nop unreachable))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+    process.dispatch_command("settings set break-on-trap true", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Trap(_)
+    ));
+
+    // Both frames survive, and the unreachable is where the process stopped
+    lines.borrow_mut().clear();
+    process.dispatch_command("where", &mut context)?;
+    assert_eq!(process.debugger.frame().len(), 2);
+    assert_eq!(lines.borrow().len(), 1);
+    let output = &lines.borrow()[0];
+    assert!(output.contains(" 0x9: Unreachable"), "{}", output);
+    assert!(output.ends_with("(depth 2)"), "{}", output);
+    Ok(())
+}
+
+#[test]
+fn test_temporary_breakpoint_is_one_shot() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "nops") nop nop nop))
+    "#;
+    let (mut process, _) = start_wat_debugger(wat)?;
+    process.debugger.set_temporary_breakpoint(Some(4));
+    assert!(matches!(
+        process.debugger.run(Some("nops"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    assert_eq!(process.debugger.temporary_breakpoint(), None);
+    let (_, next_index) = process.debugger.instructions()?;
+    assert_eq!(next_index, 2);
+    assert!(matches!(process.debugger.process()?, RunResult::Finish(_)));
+    Ok(())
+}
+
+#[test]
+fn test_step_in_over_host_call() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (import "spectest" "print_i32" (func (param i32)))
+          (func (export "f") (call 0 (i32.const 42)) nop))
+    "#;
+    let (mut process, _) = start_wat_debugger(wat)?;
+    process.debugger.set_temporary_breakpoint(Some(3));
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    let (insts, next_index) = process.debugger.instructions()?;
+    assert_eq!(insts[next_index].offset, 5);
+    assert!(matches!(
+        process.debugger.step(StepStyle::InstIn)?,
+        Signal::Next
+    ));
+    // The host function ran without entering a new frame
+    assert_eq!(process.debugger.frame().len(), 1);
+    let (insts, next_index) = process.debugger.instructions()?;
+    assert_eq!(insts[next_index].offset, 7);
+    assert!(matches!(process.debugger.process()?, RunResult::Finish(_)));
+    Ok(())
+}
+
+#[test]
+fn test_function_breakpoint_by_index_or_export() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (call 1))
+          (func (export "g") nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+
+    process.dispatch_command("breakpoint set --function 1", &mut context)?;
+    assert_eq!(
+        process.debugger.breakpoints(),
+        vec![Breakpoint::FunctionIndex { index: 1 }]
+    );
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    assert_eq!(process.debugger.frame().len(), 2);
+
+    // Without a name section, "g" is only known from the export table
+    process
+        .debugger
+        .delete_breakpoint(Breakpoint::FunctionIndex { index: 1 });
+    process.dispatch_command("breakpoint set --function g", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    assert_eq!(process.debugger.frame().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_function_breakpoint_on_entry_function() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "main") (call 1))
+          (func (export "g") nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+
+    // The function started by `run` is never called, but still stops on entry
+    for spec in &["0", "main"] {
+        process.dispatch_command(&format!("breakpoint set --function {}", spec), &mut context)?;
+        assert!(matches!(
+            process.debugger.run(Some("main"), vec![])?,
+            RunResult::Breakpoint
+        ));
+        assert_eq!(process.debugger.frame().len(), 1);
+        assert!(matches!(process.debugger.process()?, RunResult::Finish(_)));
+        for breakpoint in process.debugger.breakpoints() {
+            process.debugger.delete_breakpoint(breakpoint);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct RecordingPrinter {
+    lines: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+}
+
+impl OutputPrinter for RecordingPrinter {
+    fn println(&self, output: &str) {
+        self.lines.borrow_mut().push(output.to_string());
+    }
+    fn eprintln(&self, output: &str) {
+        self.lines.borrow_mut().push(output.to_string());
+    }
+}
+
+#[test]
+fn test_breakpoint_ignore_count() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (call 1) (call 1) (call 1))
+          (func (export "g") nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("breakpoint set --function g --ignore 2", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    let breakpoint = Breakpoint::Function {
+        name: "g".to_string(),
+    };
+    assert_eq!(
+        process.debugger.breakpoint_hits(&breakpoint),
+        BreakpointHits {
+            hit_count: 3,
+            ignore_count: 2,
+        }
+    );
+    process.dispatch_command("breakpoint list", &mut context)?;
+    assert_eq!(
+        lines.borrow().last().unwrap(),
+        "1: g, hit count = 3, ignore count = 2"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_disassemble_raw_shows_encoding() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") nop (drop (i32.const 300))))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+    process.debugger.set_temporary_breakpoint(Some(3));
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    process.dispatch_command("disassemble --raw --count 2", &mut context)?;
+    assert_eq!(
+        *lines.borrow(),
+        vec![
+            "   0x00000003: 01                      Nop".to_string(),
+            "-> 0x00000004: 41 ac 02                I32Const { value: 300 }".to_string(),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_disassemble_functions_of_module() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") nop)
+          (func nop)
+          (func nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("disassemble --function-range 1 2", &mut context)?;
+    assert_eq!(
+        *lines.borrow(),
+        vec![
+            "func[1] (func[1]): () -> ()".to_string(),
+            "   0x00000007: Nop".to_string(),
+            "   0x00000008: End".to_string(),
+            "func[2] (func[2]): () -> ()".to_string(),
+            "   0x0000000b: Nop".to_string(),
+            "   0x0000000c: End".to_string(),
+        ]
+    );
+    lines.borrow_mut().clear();
+    process.dispatch_command("disassemble --all", &mut context)?;
+    assert_eq!(lines.borrow().len(), 9);
+    assert_eq!(lines.borrow()[0], "func[0] (func[0]): () -> ()");
+    Ok(())
+}
+
+#[test]
+fn test_where_summarizes_stop_location() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "nops") nop nop nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+    process.dispatch_command("breakpoint set -a 4", &mut context)?;
+    process.debugger.run(Some("nops"), vec![])?;
+
+    lines.borrow_mut().clear();
+    process.dispatch_command("where", &mut context)?;
+    let lines = lines.borrow();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].ends_with(" 0x4: Nop (depth 1)"), "{}", lines[0]);
+    Ok(())
+}
+
+#[test]
+fn test_stop_display_runs_configured_views() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "nops") nop nop nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+    process
+        .debugger
+        .set_breakpoint(Breakpoint::Instruction { inst_offset: 4 });
+    process.debugger.run(Some("nops"), vec![])?;
+
+    // Source isn't available without DWARF, so only the disassembly is shown
+    process.dispatch_command("thread step-inst-in", &mut context)?;
+    assert_eq!(
+        lines.borrow().first().map(String::as_str),
+        Some("-> 0x00000006: End")
+    );
+
+    lines.borrow_mut().clear();
+    process.dispatch_command("settings set stop-display \"\"", &mut context)?;
+    process.dispatch_command("thread step-inst-in", &mut context)?;
+    assert!(lines.borrow().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_memory_map_lists_layout() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (memory 1)
+          (global (export "__data_end") i32 (i32.const 64))
+          (global (export "__heap_base") i32 (i32.const 1024))
+          (data (i32.const 16) "hello")
+          (data (i32.const 32) "x"))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("memory map", &mut context)?;
+    assert_eq!(
+        *lines.borrow(),
+        vec![
+            "0x00000000-0x00010000 memory (1 pages)",
+            "0x00000010-0x00000015 data[0] (5 bytes)",
+            "0x00000020-0x00000021 data[1] (1 bytes)",
+            "0x00000040-0x00000400 stack",
+            "0x00000040            __data_end",
+            "0x00000400-0x00010000 heap",
+            "0x00000400            __heap_base",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_help_lists_commands_then_aliases_sorted() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_debugger(None, vec![], vec![])?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("help", &mut context)?;
+    let lines = lines.borrow();
+    assert_eq!(lines[0], "Available commands:");
+    let aliases_at = lines.iter().position(|line| line == "Aliases:").unwrap();
+    let names = |lines: &[String]| -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.trim_start().split(" -- ").next().unwrap().to_string())
+            .collect()
+    };
+    let commands = names(&lines[1..aliases_at]);
+    let aliases = names(&lines[aliases_at + 1..]);
+    assert!(commands.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(commands.contains(&"breakpoint".to_string()));
+    assert_eq!(aliases, vec!["bt", "run"]);
+    Ok(())
+}
+
+#[test]
+fn test_completer_offers_command_options() -> anyhow::Result<()> {
+    let (process, _context) = start_debugger(None, vec![], vec![])?;
+    let completer = process.completer();
+    assert_eq!(
+        completer.candidates("", "se"),
+        vec!["segment", "session", "settings"]
+    );
+    assert_eq!(
+        completer.candidates("breakpoint set ", "--"),
+        vec![
+            "--address",
+            "--file",
+            "--function",
+            "--help",
+            "--ignore",
+            "--line",
+            "--name"
+        ]
+    );
+    assert_eq!(completer.candidates("run ", "--t"), vec!["--timeout"]);
+    Ok(())
+}
+
+#[test]
+fn test_user_alias_expands_arguments() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_debugger(None, vec![], vec![])?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("alias bn breakpoint set -n $1", &mut context)?;
+    process.dispatch_command("alias settings list", &mut context)?;
+    process.dispatch_command("bn add", &mut context)?;
+    assert_eq!(
+        process.debugger.breakpoints(),
+        vec![Breakpoint::Function {
+            name: "add".to_string()
+        }]
+    );
+
+    process.dispatch_command("alias", &mut context)?;
+    assert!(lines
+        .borrow()
+        .contains(&"bn -- breakpoint set -n $1".to_string()));
+    assert!(lines
+        .borrow()
+        .iter()
+        .all(|line| !line.starts_with("settings ")));
+    assert!(process
+        .completer()
+        .candidates("unalias ", "b")
+        .contains(&"bn".to_string()));
+
+    process.dispatch_command("unalias bn", &mut context)?;
+    lines.borrow_mut().clear();
+    process.dispatch_command("alias", &mut context)?;
+    assert!(lines.borrow().iter().all(|line| !line.starts_with("bn ")));
+    Ok(())
+}
+
+#[test]
+fn test_source_stops_at_failing_line() -> anyhow::Result<()> {
+    let (mut process, mut context) = start_debugger(None, vec![], vec![])?;
+    let dir = std::env::temp_dir();
+    let inner = dir.join(format!("wasminspect-source-inner-{}", std::process::id()));
+    let outer = dir.join(format!("wasminspect-source-outer-{}", std::process::id()));
+    std::fs::write(&inner, "# set one breakpoint\n\nbreakpoint set -n add\n")?;
+    std::fs::write(
+        &outer,
+        format!("source {}\nbogus\nbreakpoint set -n sub\n", inner.display()),
+    )?;
+
+    let err = process
+        .source_file(outer.to_str().unwrap(), &mut context)
+        .unwrap_err();
+    std::fs::remove_file(&inner)?;
+    std::fs::remove_file(&outer)?;
+    assert_eq!(
+        format!("{:#}", err),
+        format!("{}:2: 'bogus' is not a valid command.", outer.display())
+    );
+    assert_eq!(
+        process.debugger.breakpoints(),
+        vec![Breakpoint::Function {
+            name: "add".to_string()
+        }]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_failed_commands_map_to_exit_status() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "_start") unreachable))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    context.printer = Box::new(RecordingPrinter::default());
+
+    let err = process
+        .execute_command("process launch", &mut context)
+        .unwrap_err();
+    assert_eq!(exit_status(&err), EXIT_TRAP);
+    let err = process.execute_command("bogus", &mut context).unwrap_err();
+    assert_eq!(exit_status(&err), EXIT_FAILURE);
+    Ok(())
+}
+
+#[test]
+fn test_launch_timeout() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "_start") (loop (br 0))))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("process launch --timeout 10ms", &mut context)?;
+    assert_eq!(lines.borrow()[0], "Process timed out after 10ms");
+    // The deadline is gone once the run stops, so the next stop is a breakpoint
+    lines.borrow_mut().clear();
+    process.dispatch_command("breakpoint set -a 0x3", &mut context)?;
+    process.dispatch_command("process continue", &mut context)?;
+    assert_eq!(lines.borrow()[0], "Hit breakpoint");
+    Ok(())
+}
+
+#[test]
+fn test_unreachable_in_rust_panic_names_origin() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func $app::main (export "f") (call 1))
+          (func $core::panicking::panic unreachable))
+    "#;
+    let (mut process, context) = start_wat_debugger(wat)?;
+    let err = match process.debugger.run(Some("f"), vec![]) {
+        Ok(_) => panic!("expected a trap"),
+        Err(err) => err,
+    };
+
+    let trap = err.downcast_ref::<TrapError>().expect("should be a trap");
+    let panic = trap.panic.as_ref().expect("should be a panic");
+    assert_eq!(panic.function, "app::main");
+    assert_eq!(panic.code_offset, 3);
+    let output = describe_error(&err, &context);
+    assert!(
+        output.ends_with(" >> panicked in `app::main` via `core::panicking::panic`"),
+        "{}",
+        output
+    );
+    Ok(())
+}
+
+#[test]
+fn test_eval_runs_commands_in_order() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "_start") unreachable))
+    "#;
+    let eval = |commands: &[&str]| {
+        let module_input = ModuleInput {
+            bytes: wat.as_bytes().to_vec(),
+            basename: String::from("trap.wat"),
+        };
+        let commands = commands.iter().map(|command| command.to_string()).collect();
+        run_eval(Some(module_input), commands, RunOptions::default())
+    };
+
+    eval(&["image dump", "help"])?;
+    // The first failing command ends the run
+    let err = eval(&["process launch", "bogus"]).unwrap_err();
+    assert_eq!(exit_status(&err), EXIT_TRAP);
+    let err = eval(&["bogus", "process launch"]).unwrap_err();
+    assert_eq!(exit_status(&err), EXIT_FAILURE);
+    Ok(())
+}
+
+#[derive(Default)]
+struct RecordPrinter {
+    records: std::rc::Rc<std::cell::RefCell<Vec<serde_json::Value>>>,
+}
+
+impl OutputPrinter for RecordPrinter {
+    fn println(&self, _output: &str) {}
+    fn eprintln(&self, _output: &str) {}
+    fn print_record(&self, _text: &str, record: &serde_json::Value) {
+        self.records.borrow_mut().push(record.clone());
+    }
+}
+
+#[test]
+fn test_commands_print_json_records() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (param i32) nop nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordPrinter::default();
+    let records = printer.records.clone();
+    context.printer = Box::new(printer);
+    process.debugger.set_temporary_breakpoint(Some(3));
+    process.debugger.run(Some("f"), vec![WasmValue::I32(7)])?;
+
+    process.dispatch_command("local read", &mut context)?;
+    process.dispatch_command("local read 0", &mut context)?;
+    process.dispatch_command("thread backtrace", &mut context)?;
+    let records = records.borrow();
+    assert_eq!(
+        records[0],
+        serde_json::json!({ "locals": [{ "index": 0, "value": "i32:7" }] })
+    );
+    assert_eq!(
+        records[1],
+        serde_json::json!({ "index": 0, "value": "i32:7" })
+    );
+    assert_eq!(records[2]["frames"].as_array().map(Vec::len), Some(1));
+    Ok(())
+}
+
+#[test]
+fn test_continue_skips_breakpoint_hits() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (call 1) (call 1) (call 1))
+          (func (export "g") nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    context.printer = Box::new(RecordingPrinter::default());
+
+    process.dispatch_command("breakpoint set --function g", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    let breakpoint = Breakpoint::Function {
+        name: "g".to_string(),
+    };
+    assert_eq!(process.debugger.breakpoint_hits(&breakpoint).hit_count, 1);
+
+    // The second hit is run through, the third stops
+    process.dispatch_command("process continue 2", &mut context)?;
+    assert_eq!(process.debugger.breakpoint_hits(&breakpoint).hit_count, 3);
+    assert!(matches!(process.debugger.process()?, RunResult::Finish(_)));
+    Ok(())
+}
+
+#[test]
+fn test_raw_backtrace() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (call 1) (call 1) (call 1))
+          (func (export "g") nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("breakpoint set --function g", &mut context)?;
+    process.debugger.run(Some("f"), vec![])?;
+    lines.borrow_mut().clear();
+    process.dispatch_command("bt --raw", &mut context)?;
+    assert_eq!(
+        *lines.borrow(),
+        vec!["0: func[1] 0xc (returns to 0x5)\n1: func[0] 0x3".to_string()]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_image_lookup() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func $f (call $g))
+          (func $g nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("image lookup --address 0x5", &mut context)?;
+    process.dispatch_command("image lookup --name g", &mut context)?;
+    assert_eq!(
+        *lines.borrow(),
+        vec![
+            "0x5: f (func[0]) + 0x2".to_string(),
+            "g (func[1]): 0x8..0xa".to_string(),
+        ]
+    );
+    assert!(process
+        .execute_command("image lookup --address 0x100", &mut context)
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_frame_variable_from_name_section() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (param $n i32) (local i64) nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+    let printer = RecordingPrinter::default();
+    let lines = printer.lines.clone();
+    context.printer = Box::new(printer);
+
+    process.dispatch_command("breakpoint set --function 0", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![WasmValue::I32(5)])?,
+        RunResult::Breakpoint
+    ));
+    lines.borrow_mut().clear();
+    process.dispatch_command("frame variable", &mut context)?;
+    process.dispatch_command("bt", &mut context)?;
+    assert_eq!(
+        *lines.borrow(),
+        vec![
+            "n: i32:5".to_string(),
+            "local[1]: i64:0".to_string(),
+            "0: func[0]".to_string(),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_poison_locals() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") (param i32) (result i32) (local i32)
+            (i32.add (local.get 0) (local.get 1))))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+
+    // Reading the parameter is fine, the declared local was never written
+    process.dispatch_command("settings set poison-locals true", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![WasmValue::I32(1)])?,
+        RunResult::Breakpoint
+    ));
+
+    process.dispatch_command("settings set poison-locals false", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![WasmValue::I32(1)])?,
+        RunResult::Finish(values) if values == vec![WasmValue::I32(1)]
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_step_over_calls() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") nop (call 1) (call 1))
+          (func (export "g") nop))
+    "#;
+    let (mut process, mut context) = start_wat_debugger(wat)?;
+
+    // Stops after the nop, right before the first call
+    process.dispatch_command("breakpoint set --address 0x3", &mut context)?;
+    assert!(matches!(
+        process.debugger.run(Some("f"), vec![])?,
+        RunResult::Breakpoint
+    ));
+    process.execute_command("thread step-inst-over", &mut context)?;
+    assert_eq!(process.debugger.frame().len(), 1);
+
+    // A breakpoint in the callee still stops the step
+    process.dispatch_command("breakpoint set --function g", &mut context)?;
+    process.execute_command("thread step-inst-over", &mut context)?;
+    assert_eq!(process.debugger.frame().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_progress_reports() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (func (export "f") nop nop nop nop))
+    "#;
+    let (mut process, _) = start_wat_debugger(wat)?;
+
+    let counts = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let recorded = counts.clone();
+    process.debugger.set_progress(Some((
+        2,
+        Box::new(move |count, _function: &str| recorded.borrow_mut().push(count)),
+    )));
+    process.debugger.run(Some("f"), vec![])?;
+    // Four nops and the end
+    assert_eq!(*counts.borrow(), vec![2, 4]);
+
+    process.debugger.set_progress(None);
+    process.debugger.run(Some("f"), vec![])?;
+    assert_eq!(counts.borrow().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_load_text_module() -> anyhow::Result<()> {
+    let wat = r#"
+        ;; Sniffed as text despite the name
+        (module
+          (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))
+    "#;
+    let module_input = ModuleInput {
+        bytes: wat.as_bytes().to_vec(),
+        basename: "add".to_string(),
+    };
+    let (mut process, _) = start_debugger(Some(module_input), vec![], vec![])?;
+    process.debugger.instantiate(HashMap::new(), &[])?;
+    let result = process
+        .debugger
+        .run(Some("add"), vec![WasmValue::I32(1), WasmValue::I32(2)])?;
+    assert!(matches!(result, RunResult::Finish(values) if values == vec![WasmValue::I32(3)]));
+
+    let err = start_debugger(
+        Some(ModuleInput {
+            bytes: b"(module (func $f (call $g)))".to_vec(),
+            basename: "broken.wat".to_string(),
+        }),
+        vec![],
+        vec![],
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("broken.wat"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_extended_const_module_loads() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+          (global i32 (i32.add (i32.const 1) (i32.const 2)))
+          (func (export "get") (result i32) (global.get 0)))
+    "#;
+    let config = Config::builder().extended_const(true).build();
+    let (mut process, _) = start_wat_debugger_with_config(wat, config)?;
+    match process.debugger.run(Some("get"), vec![])? {
+        RunResult::Finish(values) => assert_eq!(values, vec![WasmValue::I32(3)]),
+        _ => panic!("expected get to finish"),
+    }
+
+    let err = start_wat_debugger(wat).err().unwrap();
+    assert!(
+        err.to_string().contains("--enable extended-const"),
+        "{}",
+        err
+    );
+    Ok(())
+}
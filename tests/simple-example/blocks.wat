@@ -0,0 +1,15 @@
+(module
+  ;; Enters a parameterless block and a block with a param per iteration
+  (func (export "count") (param $n i32) (result i32) (local $acc i32)
+    (block $done
+      (loop $l
+        (br_if $done (i32.eqz (local.get $n)))
+        (block
+          (local.get $acc)
+          (block (param i32) (result i32)
+            (i32.const 1)
+            (i32.add))
+          (local.set $acc))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $l)))
+    (local.get $acc)))
@@ -0,0 +1,45 @@
+(module
+  (func (export "empty") (param i32) (result i32)
+    (block (result i32)
+      (i32.const 7) (local.get 0) (br_table 0)))
+  (func (export "single") (param i32) (result i32)
+    (block (result i32)
+      (block (result i32)
+        (i32.const 99) (i32.const 10) (local.get 0) (br_table 0 1))
+      (i32.const 1) (i32.add)))
+  (func (export "multi") (param i32) (result i32)
+    (block
+      (block
+        (block (local.get 0) (br_table 2 1 0))
+        (return (i32.const 100)))
+      (return (i32.const 101)))
+    (i32.const 102))
+  (func (export "function") (param i32) (result i32)
+    (i32.const 5) (local.get 0) (br_table 0 0))
+  (func (export "loop") (param i32) (result i32) (local i32)
+    (block $done
+      (loop $l
+        (local.set 1 (i32.add (local.get 1) (i32.const 1)))
+        (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+        (br_table $l $done (i32.eqz (local.get 0)))))
+    (local.get 1))
+  (func (export "loop_param") (param i32) (result i32)
+    (block $exit (result i32)
+      (local.get 0)
+      (loop $l (param i32) (result i32)
+        i32.const 1
+        i32.sub
+        local.tee 0
+        local.get 0
+        i32.eqz
+        br_table $l $exit)))
+  (func (export "if") (param i32) (result i32)
+    (if (result i32) (i32.const 1)
+      (then (i32.const 3) (local.get 0) (br_table 0 1))
+      (else (i32.const 4)))
+    (i32.const 10)
+    i32.add)
+  (func (export "multi_value") (param i32) (result i32)
+    (block (result i32 i32)
+      (i32.const 1) (i32.const 2) (local.get 0) (br_table 0 0))
+    i32.sub))
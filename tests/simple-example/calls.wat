@@ -0,0 +1,22 @@
+(module
+  (type $unary (func (param i32) (result i32)))
+  (table funcref (elem $inc $double))
+  (func $inc (type $unary) (i32.add (local.get 0) (i32.const 1)))
+  (func $double (type $unary) (i32.mul (local.get 0) (i32.const 2)))
+  (func (export "direct") (param $n i32) (result i32) (local $acc i32)
+    (block $done
+      (loop $l
+        (br_if $done (i32.eqz (local.get $n)))
+        (local.set $acc (call $inc (local.get $acc)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $l)))
+    (local.get $acc))
+  (func (export "indirect") (param $n i32) (param $f i32) (result i32) (local $acc i32)
+    (local.set $acc (i32.const 1))
+    (block $done
+      (loop $l
+        (br_if $done (i32.eqz (local.get $n)))
+        (local.set $acc (call_indirect (type $unary) (local.get $acc) (local.get $f)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $l)))
+    (local.get $acc)))
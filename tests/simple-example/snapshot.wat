@@ -0,0 +1,17 @@
+(module
+  (memory 1)
+  (global $counter (mut i32) (i32.const 0))
+  (table 2 funcref)
+  (elem declare func $f)
+  (func $f)
+  (func (export "mutate")
+    (i32.store (i32.const 8) (i32.const 42))
+    (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    (table.set (i32.const 1) (ref.func $f))
+    (drop (memory.grow (i32.const 1))))
+  ;; The stored value, the counter, whether the table slot is null and the memory size
+  (func (export "read") (result i32 i32 i32 i32)
+    (i32.load (i32.const 8))
+    (global.get $counter)
+    (ref.is_null (table.get (i32.const 1)))
+    (memory.size)))
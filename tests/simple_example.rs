@@ -2,7 +2,10 @@ extern crate wasminspect_vm;
 use wasminspect_vm::*;
 use wast_spec::instantiate_spectest;
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::path::Path;
+use std::rc::Rc;
 
 fn run_wasm(filename: &str, func: &str, args: Vec<WasmValue>, results: Vec<WasmValue>) {
     let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
@@ -23,6 +26,19 @@ fn run_wasm(filename: &str, func: &str, args: Vec<WasmValue>, results: Vec<WasmV
     }
 }
 
+/// Assemble the text module `wat`
+fn assemble(wat: &str) -> Vec<u8> {
+    wasminspect_debugger::assemble_if_text(wat.as_bytes(), "test.wat")
+        .unwrap()
+        .into_owned()
+}
+
+/// Load the text module `wat` into `instance`
+fn load_wat(instance: &mut WasmInstance, wat: &str) -> ModuleIndex {
+    let mut bytes = assemble(wat);
+    instance.load_module_from_module(None, &mut bytes).unwrap()
+}
+
 #[test]
 fn test_calc_add() {
     run_wasm(
@@ -52,3 +68,776 @@ fn test_calc_call() {
         vec![WasmValue::I32(7)],
     );
 }
+
+#[test]
+fn test_br_table() {
+    let cases: Vec<(&str, i32, i32)> = vec![
+        // An empty table always takes the default
+        ("empty", 0, 7),
+        ("empty", 1, 7),
+        ("empty", i32::MAX, 7),
+        // Operands are unsigned, so negative ones are out of range
+        ("empty", -1, 7),
+        ("single", 0, 11),
+        ("single", 1, 10),
+        ("single", 2, 10),
+        ("single", -1, 10),
+        ("multi", 0, 102),
+        ("multi", 1, 101),
+        ("multi", 2, 100),
+        ("multi", 3, 100),
+        ("multi", i32::MIN, 100),
+        ("function", 0, 5),
+        ("function", 1, 5),
+        ("loop", 1, 1),
+        ("loop", 5, 5),
+        // Branch values are carried through loop params, if and multi-value blocks
+        ("loop_param", 3, 0),
+        ("if", 0, 13),
+        ("if", 1, 3),
+        ("if", 2, 3),
+        ("multi_value", 0, -1),
+        ("multi_value", 7, -1),
+    ];
+    for (func, arg, expected) in cases {
+        run_wasm(
+            "br_table.wasm",
+            func,
+            vec![WasmValue::I32(arg)],
+            vec![WasmValue::I32(expected)],
+        );
+    }
+}
+
+#[test]
+fn test_calls() {
+    run_wasm(
+        "calls.wasm",
+        "direct",
+        vec![WasmValue::I32(5)],
+        vec![WasmValue::I32(5)],
+    );
+    run_wasm(
+        "calls.wasm",
+        "indirect",
+        vec![WasmValue::I32(5), WasmValue::I32(0)],
+        vec![WasmValue::I32(6)],
+    );
+    run_wasm(
+        "calls.wasm",
+        "indirect",
+        vec![WasmValue::I32(5), WasmValue::I32(1)],
+        vec![WasmValue::I32(32)],
+    );
+}
+
+/// Times tight call loops. Run with
+/// `cargo test --release --test simple_example bench_calls -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn bench_calls() {
+    const ITERATIONS: i32 = 1_000_000;
+    let cases = vec![
+        ("direct", vec![WasmValue::I32(ITERATIONS)], ITERATIONS),
+        (
+            "indirect",
+            vec![WasmValue::I32(ITERATIONS), WasmValue::I32(0)],
+            ITERATIONS + 1,
+        ),
+    ];
+    for (func, args, expected) in cases {
+        let started = std::time::Instant::now();
+        run_wasm("calls.wasm", func, args, vec![WasmValue::I32(expected)]);
+        println!("{}: {} calls in {:?}", func, ITERATIONS, started.elapsed());
+    }
+}
+
+#[test]
+fn test_blocks() {
+    for n in [0, 1, 10].iter() {
+        run_wasm(
+            "blocks.wasm",
+            "count",
+            vec![WasmValue::I32(*n)],
+            vec![WasmValue::I32(*n)],
+        );
+    }
+}
+
+/// Times block entries in a tight loop. Run with
+/// `cargo test --release --test simple_example bench_blocks -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn bench_blocks() {
+    const ITERATIONS: i32 = 1_000_000;
+    let started = std::time::Instant::now();
+    run_wasm(
+        "blocks.wasm",
+        "count",
+        vec![WasmValue::I32(ITERATIONS)],
+        vec![WasmValue::I32(ITERATIONS)],
+    );
+    println!("{} iterations in {:?}", ITERATIONS, started.elapsed());
+}
+
+#[test]
+fn test_store_snapshot_and_restore() {
+    let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
+    let mut instance = WasmInstance::new();
+    let module_index = instance
+        .load_module_from_file(
+            None,
+            example_dir
+                .join("snapshot.wasm")
+                .to_str()
+                .unwrap()
+                .to_string(),
+        )
+        .unwrap();
+    let config = Config::default();
+    let read = |instance: &mut WasmInstance| {
+        instance
+            .run(module_index, Some("read".to_string()), vec![], &config)
+            .unwrap()
+    };
+    let initial = vec![
+        WasmValue::I32(0),
+        WasmValue::I32(0),
+        WasmValue::I32(1),
+        WasmValue::I32(1),
+    ];
+    assert_eq!(read(&mut instance), initial);
+
+    let snapshot = instance.store.snapshot();
+    instance
+        .run(module_index, Some("mutate".to_string()), vec![], &config)
+        .unwrap();
+    assert_eq!(
+        read(&mut instance),
+        vec![
+            WasmValue::I32(42),
+            WasmValue::I32(1),
+            WasmValue::I32(0),
+            WasmValue::I32(2),
+        ]
+    );
+
+    instance.store.restore(&snapshot);
+    assert_eq!(read(&mut instance), initial);
+    // A snapshot can be restored any number of times
+    instance
+        .run(module_index, Some("mutate".to_string()), vec![], &config)
+        .unwrap();
+    instance.store.restore(&snapshot);
+    assert_eq!(read(&mut instance), initial);
+}
+
+#[test]
+fn test_select_wast() {
+    let wast = Path::new(file!())
+        .parent()
+        .unwrap()
+        .join("simple-example")
+        .join("select.wast");
+    let mut context = wast_spec::WastContext::new(Config::default());
+    if let Err(err) = context.run_file(&wast) {
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn test_float_min_max_wast() {
+    let wast = Path::new(file!())
+        .parent()
+        .unwrap()
+        .join("simple-example")
+        .join("float_min_max.wast");
+    let mut context = wast_spec::WastContext::new(Config::default());
+    if let Err(err) = context.run_file(&wast) {
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn test_select_operand_type_mismatch() {
+    // Not validated, so the executor is the one to catch the mixed operands
+    let wat = r#"
+        (module
+          (func (export "mixed") (result i32)
+            (select (i32.const 1) (i64.const 2) (i32.const 1))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let config = Config::default();
+    match instance.run(module_index, Some("mixed".to_string()), vec![], &config) {
+        Err(WasmError::ExecutionError(Trap::UnexpectedStackValueType { .. })) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected a type mismatch but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_undefined_type_index() {
+    // Not validated, so the executor is the one to catch the bad type indices
+    let wat = r#"
+        (module
+          (table 1 funcref)
+          (func (export "indirect") (result i32) (call_indirect (type 5) (i32.const 0)))
+          (func (export "block") (result i32) (block (type 7)) (i32.const 0)))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let config = Config::default();
+    for (func, expected) in [("indirect", 5), ("block", 7)].iter() {
+        match instance.run(module_index, Some(func.to_string()), vec![], &config) {
+            Err(WasmError::ExecutionError(Trap::UndefinedType(index))) => {
+                assert_eq!(index, *expected)
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(result) => panic!("expected an undefined type but got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_invalid_local_and_global_index() {
+    // Not validated, so the executor is the one to catch the bad indices
+    let wat = r#"
+        (module
+          (func (export "local_get") (result i32) (local.get 3))
+          (func (export "local_set") (local.set 3 (i32.const 0)))
+          (func (export "global_get") (result i32) (global.get 2))
+          (func (export "global_set") (global.set 2 (i32.const 0))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let config = Config::default();
+    for func in ["local_get", "local_set"].iter() {
+        match instance.run(module_index, Some(func.to_string()), vec![], &config) {
+            Err(WasmError::ExecutionError(Trap::InvalidLocalIndex(3))) => {}
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(result) => panic!("expected an invalid local but got {:?}", result),
+        }
+    }
+    for func in ["global_get", "global_set"].iter() {
+        match instance.run(module_index, Some(func.to_string()), vec![], &config) {
+            Err(WasmError::ExecutionError(Trap::InvalidGlobalIndex(2))) => {}
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(result) => panic!("expected an invalid global but got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_fuel_limit() {
+    let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
+    let mut instance = WasmInstance::new();
+    instance.load_host_module("spectest".to_string(), instantiate_spectest());
+    let module_index = instance
+        .load_module_from_file(
+            None,
+            example_dir.join("calc.wasm").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+    let args = vec![WasmValue::I32(1), WasmValue::I32(2)];
+
+    let config = Config::builder().fuel(Some(1)).build();
+    match instance.run(module_index, Some("add".to_string()), args.clone(), &config) {
+        Err(WasmError::ExecutionError(Trap::OutOfFuel)) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected to run out of fuel but got {:?}", result),
+    }
+
+    let config = Config::builder().fuel(Some(1000)).build();
+    let result = instance.run(module_index, Some("add".to_string()), args.clone(), &config);
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(3)]);
+
+    // Weighted instructions use up the same fuel sooner
+    let costs = CostTable {
+        other: 1000,
+        ..CostTable::default()
+    };
+    let config = Config::builder().fuel(Some(1000)).costs(costs).build();
+    match instance.run(module_index, Some("add".to_string()), args, &config) {
+        Err(WasmError::ExecutionError(Trap::OutOfFuel)) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected to run out of fuel but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_argument_type_mismatch() {
+    let example_dir = Path::new(file!()).parent().unwrap().join("simple-example");
+    let mut instance = WasmInstance::new();
+    instance.load_host_module("spectest".to_string(), instantiate_spectest());
+    let module_index = instance
+        .load_module_from_file(
+            None,
+            example_dir.join("calc.wasm").to_str().unwrap().to_string(),
+        )
+        .unwrap();
+    let config = Config::default();
+    for args in vec![
+        vec![WasmValue::I32(1)],
+        vec![WasmValue::I32(1), WasmValue::I64(2)],
+    ] {
+        match instance.run(module_index, Some("add".to_string()), args, &config) {
+            Err(WasmError::ArgumentTypeMismatch { expected, .. }) => assert_eq!(expected.len(), 2),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(result) => panic!("expected an argument error but got {:?}", result),
+        }
+    }
+}
+
+#[test]
+fn test_call_stack_exhausted() {
+    let wat = r#"
+        (module
+          (func (export "depth") (param i32) (result i32)
+            (if (result i32) (local.get 0)
+              (then (call 0 (i32.sub (local.get 0) (i32.const 1))))
+              (else (i32.const 0)))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let mut depth = |n: i32, config: &Config| {
+        instance.run(
+            module_index,
+            Some("depth".to_string()),
+            vec![WasmValue::I32(n)],
+            config,
+        )
+    };
+
+    // `depth(n)` nests n + 1 frames
+    let config = Config::builder().max_call_depth(10).build();
+    assert_eq!(depth(9, &config).unwrap(), vec![WasmValue::I32(0)]);
+    match depth(10, &config) {
+        Err(WasmError::ExecutionError(Trap::CallStackExhausted)) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected to exhaust the call stack but got {:?}", result),
+    }
+
+    let config = Config::default();
+    assert_eq!(config.max_call_depth(), DEFAULT_MAX_CALL_DEPTH);
+    match depth(i32::MAX, &config) {
+        Err(WasmError::ExecutionError(Trap::CallStackExhausted)) => {}
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected to exhaust the call stack but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_canonicalize_nans() {
+    let wat = r#"
+        (module
+          (func (export "add") (param f32 f32) (result f32)
+            (f32.add (local.get 0) (local.get 1))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    // A negative NaN with a payload
+    let args = vec![
+        WasmValue::F32(0xffc0_1234),
+        WasmValue::F32(1.0f32.to_bits()),
+    ];
+
+    let config = Config::builder().canonicalize_nans(true).build();
+    let result = instance.run(module_index, Some("add".to_string()), args, &config);
+    assert_eq!(
+        result.unwrap(),
+        vec![WasmValue::F32(F32::CANONICAL_NAN_BITS)]
+    );
+
+    // Non-NaN results are untouched
+    let args = vec![
+        WasmValue::F32(1.0f32.to_bits()),
+        WasmValue::F32(2.0f32.to_bits()),
+    ];
+    let result = instance.run(module_index, Some("add".to_string()), args, &config);
+    assert_eq!(result.unwrap(), vec![WasmValue::F32(3.0f32.to_bits())]);
+}
+
+#[test]
+fn test_uncaught_exception() {
+    let wat = r#"
+        (module
+          (tag (param i32))
+          (func (export "throw") (throw 0 (i32.const 42))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let config = Config::builder().exceptions(true).build();
+    match instance.run(module_index, Some("throw".to_string()), vec![], &config) {
+        Err(WasmError::ExecutionError(Trap::UncaughtException { tag, values })) => {
+            assert_eq!(tag, 0);
+            assert_eq!(values, vec![WasmValue::I32(42)]);
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected an uncaught exception but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_import_memory_limits() {
+    let bytes = assemble(r#"(module (import "host" "memory" (memory 2)))"#);
+    let memory = Rc::new(RefCell::new(HostMemory::new(1, Some(3))));
+    assert_eq!(
+        memory.borrow().limits(),
+        Limits {
+            min: 1,
+            max: Some(3)
+        }
+    );
+    let mut instance = WasmInstance::new();
+    let mut host = HashMap::new();
+    host.insert("memory".to_string(), HostValue::Mem(memory.clone()));
+    instance.load_host_module("host".to_string(), host);
+
+    let err = instance
+        .load_module_from_module(None, &mut bytes.clone())
+        .err()
+        .expect("a 1 page memory can't satisfy a 2 page import");
+    assert!(err.to_string().contains("incompatible import type"));
+
+    // Matching looks at the current size rather than the initial one
+    memory.borrow_mut().grow(1).unwrap();
+    instance
+        .load_module_from_module(None, &mut bytes.clone())
+        .unwrap();
+}
+
+#[test]
+fn test_host_function_error_names_import() {
+    let wat = r#"
+        (module
+          (import "host" "fail" (func))
+          (func (export "run") (call 0)))
+    "#;
+    let mut bytes = assemble(wat);
+    let ty = wasmparser::FuncType {
+        params: vec![].into_boxed_slice(),
+        returns: vec![].into_boxed_slice(),
+    };
+    let fail = HostFuncBody::new(ty, |_, _, _, _| {
+        let error = std::io::Error::new(std::io::ErrorKind::Other, "bad descriptor");
+        Err(Trap::HostFunctionError(Box::new(error)))
+    });
+    let mut host = HashMap::new();
+    host.insert("fail".to_string(), HostValue::Func(fail));
+    let mut instance = WasmInstance::new();
+    instance.load_host_module("host".to_string(), host);
+    let module_index = instance.load_module_from_module(None, &mut bytes).unwrap();
+
+    let config = Config::default();
+    match instance.run(module_index, Some("run".to_string()), vec![], &config) {
+        Err(WasmError::ExecutionError(trap)) => assert_eq!(
+            trap.to_string(),
+            "host function host.fail failed: bad descriptor"
+        ),
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected the host function to fail but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_indirect_call_type_mismatch_signatures() {
+    let wat = r#"
+        (module
+          (type (func (result i32)))
+          (type (func (param i32 i32) (result i32)))
+          (table 1 funcref)
+          (func (export "f") (type 0) (call_indirect (type 0) (i32.const 0)))
+          (func (type 1) (local.get 0))
+          (elem (i32.const 0) func 1))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let config = Config::default();
+    match instance.run(module_index, Some("f".to_string()), vec![], &config) {
+        Err(WasmError::ExecutionError(trap)) => {
+            assert!(trap.callee().is_some());
+            assert_eq!(
+                trap.to_string(),
+                "indirect call type mismatch for 'func[1]' at table[0][0]:
+ >> call_indirect instruction expected () -> i32
+ >> but actual implementation has      (i32, i32) -> i32"
+            )
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected a type mismatch but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_validation_error_offset() {
+    #[rustfmt::skip]
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // type section with an invalid form byte
+        0x01, 0x04, 0x01, 0x61, 0x00, 0x00,
+    ];
+    let err = validate(&bytes, &Config::default()).unwrap_err();
+    assert_eq!(err.offset, 11);
+    assert!(!err.message.is_empty());
+}
+
+#[test]
+fn test_unsupported_instruction_traps() {
+    let wat = r#"
+        (module
+          (func (export "f") (drop (v128.const i32x4 0 0 0 0))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let config = Config::builder().simd(true).build();
+    match instance.run(module_index, Some("f".to_string()), vec![], &config) {
+        Err(WasmError::ExecutionError(Trap::UnsupportedInstruction { opcode })) => {
+            assert_eq!(opcode, "v128.const")
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected an unsupported instruction but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_disabled_feature_traps() {
+    let wat = r#"
+        (module
+          (func (export "f") (drop (v128.const i32x4 0 0 0 0))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    match instance.run(
+        module_index,
+        Some("f".to_string()),
+        vec![],
+        &Config::default(),
+    ) {
+        Err(WasmError::ExecutionError(Trap::FeatureDisabled { opcode, feature })) => {
+            assert_eq!(opcode, "v128.const");
+            assert_eq!(feature, "simd");
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(result) => panic!("expected a disabled feature but got {:?}", result),
+    }
+}
+
+#[test]
+fn test_enumerate_and_invoke_exports() {
+    let wat = r#"
+        (module
+          (func (export "id") (param i32) (result i32) local.get 0))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let exports = instance.exports(module_index);
+    assert_eq!(exports.len(), 1);
+    assert_eq!(exports[0].name, "id");
+    match &exports[0].kind {
+        ExportKind::Func(ty) => {
+            assert_eq!(ty.params.to_vec(), vec![wasmparser::Type::I32]);
+            assert_eq!(ty.returns.to_vec(), vec![wasmparser::Type::I32]);
+        }
+        other => panic!("expected a function but got {:?}", other),
+    }
+    assert!(instance.get_func(module_index, "missing").is_none());
+    let func_addr = instance.get_func(module_index, "id").unwrap();
+    let result = instance.invoke(func_addr, vec![WasmValue::I32(42)], &Config::default());
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(42)]);
+}
+
+struct BreakAt(usize);
+
+impl Interceptor for BreakAt {
+    fn invoke_func(
+        &self,
+        _name: &str,
+        _executor: &Executor,
+        _store: &Store,
+    ) -> Result<Signal, Trap> {
+        Ok(Signal::Next)
+    }
+    fn execute_inst(&self, inst: &Instruction, _executor: &Executor) -> Result<Signal, Trap> {
+        if inst.offset == self.0 {
+            Ok(Signal::Breakpoint)
+        } else {
+            Ok(Signal::Next)
+        }
+    }
+    fn after_store(&self, _addr: usize, _bytes: &[u8]) -> Result<Signal, Trap> {
+        Ok(Signal::Next)
+    }
+}
+
+#[test]
+fn test_invoke_func_stops_at_breakpoint() {
+    let wat = r#"
+        (module
+          (func (export "f") (param i32) (result i32)
+            (i32.add (local.get 0) (i32.const 1))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let func_addr = instance.get_func(module_index, "f").unwrap();
+    let config = Config::default();
+    let interceptor = BreakAt(5);
+    let args = vec![WasmValue::I32(1)];
+    let executor = match invoke_func(func_addr, args, &mut instance.store, &interceptor, &config) {
+        Ok(Invocation::Breakpoint(executor)) => executor,
+        Ok(Invocation::Finished(_)) => panic!("expected to stop at the breakpoint"),
+        Err(err) => panic!("unexpected error: {}", err),
+    };
+    // The instruction at the breakpoint has run
+    assert_eq!(executor.pc.inst_index().0, 2);
+    match resume_func(executor, &instance.store, &interceptor, &config) {
+        Ok(Invocation::Finished(values)) => assert_eq!(values, vec![WasmValue::I32(2)]),
+        Ok(Invocation::Breakpoint(_)) => panic!("expected to finish"),
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+struct CountInsts(Rc<RefCell<usize>>);
+
+impl Interceptor for CountInsts {
+    fn invoke_func(
+        &self,
+        _name: &str,
+        _executor: &Executor,
+        _store: &Store,
+    ) -> Result<Signal, Trap> {
+        Ok(Signal::Next)
+    }
+    fn execute_inst(&self, _inst: &Instruction, _executor: &Executor) -> Result<Signal, Trap> {
+        *self.0.borrow_mut() += 1;
+        Ok(Signal::Next)
+    }
+    fn after_store(&self, _addr: usize, _bytes: &[u8]) -> Result<Signal, Trap> {
+        Ok(Signal::Next)
+    }
+}
+
+#[test]
+fn test_instance_consults_added_interceptors() {
+    let wat = r#"
+        (module
+          (func (export "f") (param i32) (result i32)
+            (i32.add (local.get 0) (i32.const 1))))
+    "#;
+    let mut instance = WasmInstance::new();
+    let module_index = load_wat(&mut instance, wat);
+    let count = Rc::new(RefCell::new(0));
+    instance.add_interceptor(Box::new(BreakAt(5)));
+    instance.add_interceptor(Box::new(CountInsts(count.clone())));
+
+    // The breakpoint doesn't hide the instruction from the counter, nor
+    // stop the call
+    let func_addr = instance.get_func(module_index, "f").unwrap();
+    let result = instance.invoke(func_addr, vec![WasmValue::I32(1)], &Config::default());
+    assert_eq!(result.unwrap(), vec![WasmValue::I32(2)]);
+    assert_eq!(*count.borrow(), 4);
+}
+
+#[test]
+fn test_config_caps_memory_size() {
+    let wat = r#"
+        (module
+          (memory 1)
+          (func (export "grow") (result i32) (memory.grow (i32.const 1))))
+    "#;
+    let bytes = assemble(wat);
+    let grow = |config: &Config| {
+        let mut instance = WasmInstance::new();
+        let loaded = Rc::new(RefCell::new(0));
+        let count = loaded.clone();
+        instance.on_instantiate(move |_| *count.borrow_mut() += 1);
+        let module_index = instance
+            .load_module_from_module_with_config(None, &mut bytes.clone(), config)
+            .unwrap();
+        assert_eq!(*loaded.borrow(), 1);
+        let func_addr = instance.get_func(module_index, "grow").unwrap();
+        instance.invoke(func_addr, vec![], config).unwrap()
+    };
+    assert_eq!(grow(&Config::default()), vec![WasmValue::I32(1)]);
+    let capped = Config::builder().max_memory_pages(Some(1)).build();
+    assert_eq!(grow(&capped), vec![WasmValue::I32(-1)]);
+
+    let config = Config::builder().max_memory_pages(Some(0)).build();
+    let err = WasmInstance::new()
+        .load_module_from_module_with_config(None, &mut bytes.clone(), &config)
+        .unwrap_err();
+    assert!(err.to_string().contains("exceeds the configured limit"));
+}
+
+fn link_error(bytes: &[u8]) -> String {
+    let mut instance = WasmInstance::new();
+    instance.load_host_module("spectest".to_string(), instantiate_spectest());
+    let err = instance
+        .store
+        .load_module(None, bytes)
+        .expect_err("expected module to fail to link");
+    err.to_string()
+}
+
+#[test]
+fn test_unlinkable_imports_report_mismatch() {
+    let func = assemble(r#"(module (import "spectest" "print_i32" (func (param i64))))"#);
+    assert_eq!(
+        link_error(&func),
+        "incompatible import type, \"print_i32\" expected (i64) -> () but got (i32) -> ()"
+    );
+
+    let global = assemble(r#"(module (import "spectest" "global_i32" (global (mut i32))))"#);
+    assert_eq!(
+        link_error(&global),
+        "incompatible import type, expected mutable global but got immutable"
+    );
+
+    let memory = assemble(r#"(module (import "spectest" "memory" (memory 2)))"#);
+    assert!(link_error(&memory).starts_with("incompatible import type in memory"));
+
+    let table = assemble(r#"(module (import "spectest" "table" (table 10 externref)))"#);
+    assert_eq!(
+        link_error(&table),
+        "incompatible import type, expected externref table, got funcref"
+    );
+
+    let unknown_module = assemble(r#"(module (import "nowhere" "f" (func)))"#);
+    assert!(link_error(&unknown_module).starts_with("unknown import"));
+}
+
+#[test]
+fn test_on_instantiate_reports_modules() {
+    let wat = r#"
+        (module
+          (import "spectest" "print_i32" (func (param i32)))
+          (export "f" (func 0)))
+    "#;
+    let mut bytes = assemble(wat);
+    let summaries = Rc::new(RefCell::new(Vec::new()));
+    let mut instance = WasmInstance::new();
+    let recorded = summaries.clone();
+    instance.on_instantiate(move |summary| recorded.borrow_mut().push(summary.clone()));
+    let host_index = instance.load_host_module("spectest".to_string(), instantiate_spectest());
+    let module_index = instance
+        .load_module_from_module(Some("main".to_string()), &mut bytes)
+        .unwrap();
+
+    let summaries = summaries.borrow();
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(summaries[0].module_index, host_index);
+    assert_eq!(summaries[0].name.as_deref(), Some("spectest"));
+    assert!(summaries[0].imports.is_empty());
+    assert!(summaries[0]
+        .exports
+        .iter()
+        .any(|export| export.name == "print_i32"));
+
+    assert_eq!(summaries[1].module_index, module_index);
+    assert_eq!(
+        summaries[1].imports,
+        vec![("spectest".to_string(), "print_i32".to_string())]
+    );
+    assert_eq!(summaries[1].exports.len(), 1);
+    assert_eq!(summaries[1].exports[0].name, "f");
+}